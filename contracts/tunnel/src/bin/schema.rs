@@ -1,10 +1,71 @@
-use cosmwasm_schema::write_api;
-use cosmwasm_tunnel::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use std::env::current_dir;
+use std::fs::{create_dir_all, write};
+
+use cosmwasm_schema::{
+    export_schema, export_schema_with_title, generate_api, schema_for, QueryResponses,
+};
+use cosmwasm_tunnel::{
+    v1, ChannelMetadata, DispatchMigrateResponse, ErrorResponse, ExecuteMsg, ForwardResponse,
+    InstantiateMsg, InstantiateResponse, MigrateMsg, PacketMsg, QueryMsg, ReceiveIcaResponseMsg,
+    StdAck, SudoMsg, TunnelAck, TunnelReceiverMsg, WhoAmIResponse, IBC_APP_VERSION,
+    LATEST_APP_VERSION,
+};
+use ic_tunnel::{TunnelCustomMsg, BINDING};
 
 fn main() {
-    write_api! {
+    // The schema of a tunnel built for a binding is in its own directory
+    let mut schema_dir = current_dir().unwrap();
+    schema_dir.push("schema");
+    if let Some(binding) = BINDING {
+        schema_dir.push(binding);
+    }
+    create_dir_all(&schema_dir).unwrap();
+
+    let api = generate_api! {
         instantiate: InstantiateMsg,
         query: QueryMsg,
-        execute: ExecuteMsg
+        execute: ExecuteMsg<TunnelCustomMsg>,
+        migrate: MigrateMsg,
+        sudo: SudoMsg
     }
+    .render();
+    let path = schema_dir.join(concat!(env!("CARGO_PKG_NAME"), ".json"));
+    write(&path, api.to_string().unwrap() + "\n").unwrap();
+    println!("Exported the full API as {}", path.to_str().unwrap());
+
+    // The IBC protocol and the callback interface, by protocol version
+    let mut out_dir = schema_dir.clone();
+    out_dir.push(LATEST_APP_VERSION);
+    create_dir_all(&out_dir).unwrap();
+
+    export_schema_with_title(
+        &schema_for!(PacketMsg<TunnelCustomMsg>),
+        &out_dir,
+        "PacketMsg",
+    );
+    export_schema(&schema_for!(ChannelMetadata), &out_dir);
+    export_schema(&schema_for!(StdAck), &out_dir);
+    export_schema(&schema_for!(TunnelAck), &out_dir);
+    export_schema(&schema_for!(InstantiateResponse), &out_dir);
+    export_schema(&schema_for!(DispatchMigrateResponse), &out_dir);
+    export_schema(&schema_for!(WhoAmIResponse), &out_dir);
+    export_schema(&schema_for!(ForwardResponse), &out_dir);
+    export_schema(&schema_for!(ErrorResponse), &out_dir);
+    export_schema(&schema_for!(ReceiveIcaResponseMsg), &out_dir);
+    export_schema(&schema_for!(TunnelReceiverMsg), &out_dir);
+
+    // The packets and acks of the channels still speaking the first version
+    let mut out_dir = schema_dir;
+    out_dir.push(IBC_APP_VERSION);
+    create_dir_all(&out_dir).unwrap();
+
+    export_schema_with_title(
+        &schema_for!(v1::PacketMsg<TunnelCustomMsg>),
+        &out_dir,
+        "PacketMsg",
+    );
+    export_schema(&schema_for!(StdAck), &out_dir);
+    export_schema(&schema_for!(InstantiateResponse), &out_dir);
+    export_schema(&schema_for!(DispatchMigrateResponse), &out_dir);
+    export_schema(&schema_for!(WhoAmIResponse), &out_dir);
 }
@@ -1,47 +1,224 @@
 use crate::error::ContractError;
-use cosmwasm_tunnel::{check_order, check_version, IBC_APP_VERSION};
+use crate::host::sudo_ica;
+use crate::migrations::{parse_version, run_migrations};
+use crate::remote::sudo_governance;
+use crate::state::{CHANNEL_METADATA, CHANNEL_VERSIONS, NONCE_WINDOW, PROPOSED_ENCODINGS};
+use cosmwasm_tunnel::events::{TunnelInstantiated, TunnelMigrated};
+use cosmwasm_tunnel::{
+    channel_version, check_order, negotiate_version, ChannelMetadata, InstantiateMsg, MigrateMsg,
+    Negotiated, PacketCodec, PacketEncoding, ProtocolVersion, SudoMsg, TunnelRole,
+    MAX_NONCE_WINDOW,
+};
 
 use cosmwasm_std::{
     entry_point, DepsMut, Env, Event, Ibc3ChannelOpenResponse, IbcBasicResponse,
     IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse,
-    MessageInfo, Response, StdResult,
+    MessageInfo, Response, StdResult, Storage,
 };
+use cw2::{set_contract_version, CONTRACT};
+
+pub const CONTRACT_NAME: &str = "crates.io:ic-tunnel";
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The `TunnelHost` and `TunnelRemote` handlers of this contract
+pub struct Tunnel;
+
+// Tunnels instantiated before cw2 was introduced did not store a contract version
+const UNVERSIONED_CONTRACT_VERSION: &str = "0.1.0";
+
+/// Whether the tunnel is built with `role`, see the `host` and `remote` features
+pub fn has_role(role: TunnelRole) -> bool {
+    match role {
+        TunnelRole::Host => cfg!(feature = "host"),
+        TunnelRole::Remote => cfg!(feature = "remote"),
+    }
+}
+
+/// Rejects traffic meant for a role the tunnel is not built with
+pub fn ensure_role(role: TunnelRole) -> Result<(), ContractError> {
+    if has_role(role) {
+        Ok(())
+    } else {
+        Err(ContractError::WrongRole(role))
+    }
+}
+
+/// The roles the tunnel is built with
+pub fn roles() -> Vec<TunnelRole> {
+    [TunnelRole::Host, TunnelRole::Remote]
+        .into_iter()
+        .filter(|role| has_role(*role))
+        .collect()
+}
 
 #[entry_point]
-pub fn instantiate(_deps: DepsMut, _env: Env, _info: MessageInfo) -> StdResult<Response> {
-    let event = Event::new("ica-tunnel.V1.MsgInstantiated");
-    Ok(Response::new().add_event(event))
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    save_nonce_window(deps.storage, msg.nonce_window.unwrap_or(MAX_NONCE_WINDOW))?;
+    let event = TunnelInstantiated {
+        roles: roles()
+            .into_iter()
+            .map(|role| format!("{role:?}").to_lowercase())
+            .collect(),
+    };
+    Ok(Response::new().add_event(event.into()))
+}
+
+#[entry_point]
+/// The ICS-27 packet lifecycle on a host tunnel, governance on a remote tunnel
+pub fn sudo(deps: DepsMut, _env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::Ica(msg) => sudo_ica(deps, msg),
+        SudoMsg::Governance(msg) => sudo_governance(deps, msg),
+    }
+}
+
+#[entry_point]
+/// Runs the state migrations between the stored and the new contract version,
+/// migrating to an older version is not allowed
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let from = match CONTRACT.may_load(deps.storage)? {
+        Some(stored) if stored.contract != CONTRACT_NAME => {
+            return Err(ContractError::InvalidMigrationContract(stored.contract))
+        }
+        Some(stored) => stored.version,
+        None => UNVERSIONED_CONTRACT_VERSION.to_string(),
+    };
+    let from_version = parse_version(&from)?;
+    let to_version = parse_version(CONTRACT_VERSION)?;
+    if from_version > to_version {
+        return Err(ContractError::CannotMigrateDowngrade {
+            from,
+            to: CONTRACT_VERSION.to_string(),
+        });
+    }
+
+    let applied = run_migrations(deps.storage, &from_version, &to_version)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    if let Some(nonce_window) = msg.nonce_window {
+        save_nonce_window(deps.storage, nonce_window)?;
+    }
+
+    let event = TunnelMigrated {
+        from_version: from,
+        to_version: CONTRACT_VERSION.to_string(),
+        migrations: applied.into_iter().map(String::from).collect(),
+    };
+    Ok(Response::new().add_event(event.into()))
+}
+
+fn save_nonce_window(storage: &mut dyn Storage, nonce_window: u64) -> Result<(), ContractError> {
+    if nonce_window == 0 || nonce_window > MAX_NONCE_WINDOW {
+        return Err(ContractError::InvalidNonceWindow(MAX_NONCE_WINDOW));
+    }
+    Ok(NONCE_WINDOW.save(storage, &nonce_window)?)
+}
+
+/// The channel metadata of this tunnel
+pub fn channel_metadata(env: &Env) -> ChannelMetadata {
+    ChannelMetadata::new(env.block.chain_id.clone(), roles())
+}
+
+fn save_negotiated(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    negotiated: &Negotiated,
+) -> StdResult<()> {
+    CHANNEL_VERSIONS.save(storage, channel_id, &negotiated.version)?;
+    match &negotiated.counterparty {
+        Some(metadata) => CHANNEL_METADATA.save(storage, channel_id, metadata),
+        None => {
+            CHANNEL_METADATA.remove(storage, channel_id);
+            Ok(())
+        }
+    }
+}
+
+/// The protocol version of the channel, channels opened before versions were negotiated are v1
+pub fn protocol_version(storage: &dyn Storage, channel_id: &str) -> StdResult<ProtocolVersion> {
+    Ok(CHANNEL_VERSIONS
+        .may_load(storage, channel_id)?
+        .unwrap_or(ProtocolVersion::V1))
+}
+
+/// The packet encoding of the channel, both ends use the one in the counterparty's metadata
+pub fn channel_encoding(storage: &dyn Storage, channel_id: &str) -> StdResult<PacketEncoding> {
+    Ok(CHANNEL_METADATA
+        .may_load(storage, channel_id)?
+        .map(|metadata| metadata.encoding)
+        .unwrap_or_default())
+}
+
+/// How the packets and acks of the channel are written, by its protocol version and encoding
+pub fn channel_codec(storage: &dyn Storage, channel_id: &str) -> StdResult<PacketCodec> {
+    Ok(PacketCodec {
+        version: protocol_version(storage, channel_id)?,
+        encoding: channel_encoding(storage, channel_id)?,
+    })
 }
 
 #[entry_point]
 /// enforces ordering and versioing constraints
 pub fn ibc_channel_open(
-    _deps: DepsMut,
-    _env: Env,
+    deps: DepsMut,
+    env: Env,
     msg: IbcChannelOpenMsg,
 ) -> Result<IbcChannelOpenResponse, ContractError> {
     let channel = msg.channel();
 
     check_order(&channel.order)?;
-    // In ibcv3 we don't check the version string passed in the message
-    // and only check the counterparty version.
-    if let Some(counter_version) = msg.counterparty_version() {
-        check_version(counter_version)?;
+    let (version, encoding) = match msg.counterparty_version() {
+        // on try, the counterparty proposes its version and encoding
+        Some(counter_version) => {
+            let negotiated = negotiate_version(counter_version)?;
+            save_negotiated(deps.storage, &channel.endpoint.channel_id, &negotiated)?;
+            (negotiated.version, negotiated.encoding)
+        }
+        // on init, the relayer may ask for `cw-tunnel-v1` or for metadata with another encoding,
+        // an empty version leaves the choice to us
+        None if channel.version.is_empty() => (ProtocolVersion::LATEST, PacketEncoding::default()),
+        None => {
+            let proposed = negotiate_version(&channel.version)?;
+            (proposed.version, proposed.encoding)
+        }
+    };
+    if msg.counterparty_version().is_none() {
+        PROPOSED_ENCODINGS.save(deps.storage, &channel.endpoint.channel_id, &encoding)?;
     }
 
-    // We return the version we need (which could be different than the counterparty version)
+    // We return our own metadata, the counterparty keeps it for the channel
     Ok(Some(Ibc3ChannelOpenResponse {
-        version: IBC_APP_VERSION.to_string(),
+        version: channel_version(version, encoding, &channel_metadata(&env))?,
     }))
 }
 
 #[entry_point]
-/// On connect, we do not do anything other than just logging the information
+/// On connect, we keep the metadata of the counterparty if it opened the channel (on ack)
 pub fn ibc_channel_connect(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
     msg: IbcChannelConnectMsg,
-) -> StdResult<IbcBasicResponse> {
+) -> Result<IbcBasicResponse, ContractError> {
+    if let Some(counter_version) = msg.counterparty_version() {
+        let channel_id = &msg.channel().endpoint.channel_id;
+        let negotiated = negotiate_version(counter_version)?;
+        // the counterparty answers with the encoding we proposed, both ends use it
+        if let Some(proposed) = PROPOSED_ENCODINGS.may_load(deps.storage, channel_id)? {
+            if proposed != negotiated.encoding {
+                return Err(ContractError::EncodingMismatch {
+                    proposed,
+                    answered: negotiated.encoding,
+                });
+            }
+            PROPOSED_ENCODINGS.remove(deps.storage, channel_id);
+        }
+        save_negotiated(deps.storage, channel_id, &negotiated)?;
+    }
+
     // We do not use channel here because the channels can be closed permisionlessly
     // connection_id: this is the id for the light client on the counterparty chain
     // port_id: this is the counterparty module / wasm smart contract
@@ -1,9 +1,12 @@
 use thiserror::Error;
 
 use cosmwasm_std::StdError;
-use cw_utils::ParseReplyError;
+use cw_utils::{ParseReplyError, PaymentError};
 
-use cosmwasm_tunnel::ChannelError;
+use cosmwasm_tunnel::{
+    ChannelError, CosmosMsgKind, PacketEncoding, TunnelError, TunnelErrorCode, TunnelOperation,
+    TunnelRole, MAX_CHUNKS, MAX_UPLOADS,
+};
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
@@ -16,6 +19,15 @@ pub enum ContractError {
     #[error("{0}")]
     ChannelError(#[from] ChannelError),
 
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("The counterparty answered the proposed {proposed:?} encoding with {answered:?}")]
+    EncodingMismatch {
+        proposed: PacketEncoding,
+        answered: PacketEncoding,
+    },
+
     #[error("Cannot register over an existing channel")]
     ChannelAlreadyRegistered,
 
@@ -25,6 +37,159 @@ pub enum ContractError {
     #[error("Invalid Connection Id")]
     InvalidConnectionId,
 
+    #[error("Account not found")]
+    AccountNotFound,
+
     #[error("Unauthorized")]
     Unauthorized,
+
+    #[error("Operator grant has expired")]
+    OperatorExpired,
+
+    #[error("Operator is not allowed to dispatch {0:?} messages")]
+    MsgKindNotAllowed(CosmosMsgKind),
+
+    #[error("Nonce {0} has already been received or is outside of the window")]
+    InvalidNonce(u64),
+
+    #[error("Nonce window must be between 1 and {0}")]
+    InvalidNonceWindow(u64),
+
+    #[error("Invalid forward")]
+    InvalidForward,
+
+    #[error("Forward loop, packet has already passed chain {0}")]
+    ForwardLoop(String),
+
+    #[error("Forward exceeds the maximum of {0} hops")]
+    HopLimitExceeded(usize),
+
+    #[error("A forward with job id {0} is already pending")]
+    ForwardPending(String),
+
+    #[error("Cannot migrate from contract {0}")]
+    InvalidMigrationContract(String),
+
+    #[error("Cannot migrate from version {from} to older version {to}")]
+    CannotMigrateDowngrade { from: String, to: String },
+
+    #[error("Invalid contract version: {0}")]
+    InvalidVersion(String),
+
+    #[error("This tunnel is not built with the {0:?} role")]
+    WrongRole(TunnelRole),
+
+    #[error("The counterparty of the channel does not support {0:?}")]
+    UnsupportedOperation(TunnelOperation),
+
+    #[error("Invalid signing key")]
+    InvalidSigningKey,
+
+    #[error("No signing key registered for the controller")]
+    SigningKeyNotFound,
+
+    #[error("No session key {0} registered for the controller")]
+    SessionKeyNotFound(String),
+
+    #[error("Session key has expired")]
+    SessionExpired,
+
+    #[error("Session key cannot dispatch on channel {0}")]
+    ChannelNotAllowed(String),
+
+    #[error("The funds {0:?} messages spend cannot be limited")]
+    UnknownSpend(CosmosMsgKind),
+
+    #[error("Session spend limit exceeded for {0}")]
+    SpendLimitExceeded(String),
+
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    #[error("Signed operation has expired")]
+    SignatureExpired,
+
+    #[error("Expected signed nonce {expected}, got {nonce}")]
+    InvalidSignedNonce { expected: u64, nonce: u64 },
+
+    #[error("Invalid chunk")]
+    InvalidChunk,
+
+    #[error("The chunks do not match the hash of the packet")]
+    ChunkHashMismatch,
+
+    #[error("Message policy violation: {0}")]
+    PolicyViolation(String),
+
+    #[error("Proxy spend limit exceeded for {0}")]
+    ProxySpendLimitExceeded(String),
+
+    #[error("A proxy with a spend limit cannot execute itself")]
+    ProxySelfExecute,
+
+    #[error("A proxy with a spend limit cannot be migrated")]
+    LimitedProxyMigration,
+
+    #[error("The controller can only tighten the spend limit")]
+    SpendLimitLoosened,
+
+    #[error("The packet timeout must be more than 0 seconds")]
+    InvalidTimeout,
+
+    #[error("The packet needs {0} chunks, more than the maximum of {MAX_CHUNKS}")]
+    PacketTooLarge(usize),
+
+    #[error("The controller already has {MAX_UPLOADS} incomplete packets")]
+    TooManyUploads,
+}
+
+/// The errors of the default handlers, as the variants of this contract where there is one
+impl From<TunnelError> for ContractError {
+    fn from(err: TunnelError) -> Self {
+        match err {
+            TunnelError::Std(err) => ContractError::Std(err),
+            TunnelError::ParseReply(err) => ContractError::ParseReply(err),
+            TunnelError::AccountNotFound => ContractError::AccountNotFound,
+            TunnelError::AccountAlreadyExists => ContractError::ChannelAlreadyRegistered,
+            TunnelError::InvalidConnectionId => ContractError::InvalidConnectionId,
+            TunnelError::Unauthorized => ContractError::Unauthorized,
+            TunnelError::InvalidReplyId => ContractError::InvalidReplyId,
+            TunnelError::InvalidForward => ContractError::InvalidForward,
+            TunnelError::UnsupportedOperation(op) => ContractError::UnsupportedOperation(op),
+            TunnelError::PolicyViolation(reason) => ContractError::PolicyViolation(reason),
+            TunnelError::InvalidTimeout => ContractError::InvalidTimeout,
+            TunnelError::PacketTooLarge(chunks) => ContractError::PacketTooLarge(chunks),
+        }
+    }
+}
+
+impl ContractError {
+    /// The code returned to the host chain in the error ack
+    pub fn code(&self) -> TunnelErrorCode {
+        match self {
+            ContractError::Std(StdError::ParseErr { .. }) => TunnelErrorCode::InvalidPacket,
+            ContractError::AccountNotFound => TunnelErrorCode::AccountNotFound,
+            ContractError::ChannelAlreadyRegistered => TunnelErrorCode::AccountAlreadyExists,
+            ContractError::InvalidConnectionId => TunnelErrorCode::InvalidConnection,
+            ContractError::Unauthorized
+            | ContractError::OperatorExpired
+            | ContractError::MsgKindNotAllowed(_) => TunnelErrorCode::Unauthorized,
+            ContractError::InvalidForward
+            | ContractError::ForwardLoop(_)
+            | ContractError::HopLimitExceeded(_)
+            | ContractError::ForwardPending(_)
+            | ContractError::UnsupportedOperation(_) => TunnelErrorCode::InvalidRoute,
+            ContractError::InvalidNonce(_) => TunnelErrorCode::InvalidNonce,
+            ContractError::InvalidChunk
+            | ContractError::ChunkHashMismatch
+            | ContractError::TooManyUploads => TunnelErrorCode::InvalidPacket,
+            ContractError::WrongRole(_) => TunnelErrorCode::WrongRole,
+            ContractError::PolicyViolation(_) => TunnelErrorCode::PolicyViolation,
+            ContractError::ProxySpendLimitExceeded(_)
+            | ContractError::UnknownSpend(_)
+            | ContractError::ProxySelfExecute
+            | ContractError::LimitedProxyMigration => TunnelErrorCode::SpendLimitExceeded,
+            _ => TunnelErrorCode::Unknown,
+        }
+    }
 }
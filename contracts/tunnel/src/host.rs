@@ -1,148 +1,1111 @@
+use crate::contract::{channel_codec, ensure_role, Tunnel};
 use crate::error::ContractError;
+use crate::remote::get_connection_id_from_channel;
+use crate::state::{
+    ForwardHop, IcaAccount, CHANNEL_METADATA, DEFAULT_LIMIT, FORWARD_ROUTES, ICA_ACCOUNTS,
+    ICA_CHANNELS, ICA_OWNERS, MAX_LIMIT, OPERATORS, PENDING_FORWARDS, SENT_NONCES, SESSION_KEYS,
+    SESSION_NONCES, SESSION_SPENT, SIGNED_NONCES, SIGNING_KEYS,
+};
+use crate::TunnelCustomMsg;
+use cosmwasm_tunnel::events::{
+    DispatchRequested, ForwardRequested, InstantiationRequested, InterchainAccountOpened,
+    InterchainAccountRequested, MigrationRequested, OperatorGranted, OperatorRevoked,
+    RemoteAddrRequested, SessionKeyRegistered, SessionKeyRevoked, SigningKeyRegistered,
+    SigningKeyRevoked,
+};
+use cosmwasm_tunnel::ica::{
+    decode_ica_ack, ica_controller_port, ica_owner, register_interchain_account_msg, send_tx_msg,
+    IcaMemo, IcaMetadata, IcaSudoMsg, IcaTxResponse,
+};
 use cosmwasm_tunnel::{
-    ExecuteMsg, PacketMsg, PACKET_LIFETIME,
+    spent_funds, split_forwarded_controller, ChannelMetadata, CosmosMsgKind, ErrorResponse,
+    ExecuteMsg, InterchainAccountResponse, ListOperatorsResponse, NonceResponse, OperatorGrant,
+    OperatorInfo, OperatorResponse, OperatorScope, OutgoingPacket, PacketCodec, PacketMsg,
+    SessionKey, SessionKeyResponse, SignedOperation, SigningKey, SigningKeyResponse, StdAck,
+    TunnelErrorCode, TunnelHost, TunnelOperation, TunnelRole, WhoAmIResponse, MAX_FORWARD_HOPS,
+    PACKET_LIFETIME,
 };
+use cw_storage_plus::Bound;
+use cw_utils::nonpayable;
+use sha2::{Digest, Sha256};
 
 use cosmwasm_std::{
-    entry_point, to_binary, CosmosMsg, DepsMut, Env, Event, IbcBasicResponse, IbcMsg, IbcPacketAckMsg, IbcPacketTimeoutMsg, MessageInfo,
-    Response, StdResult,
+    entry_point, from_slice, to_binary, Addr, Binary, CosmosMsg, Deps, DepsMut, Env,
+    IbcBasicResponse, IbcMsg, IbcPacketAckMsg, IbcPacketTimeoutMsg, MessageInfo, Order, Reply,
+    Response, StdResult, Storage, SubMsg,
 };
 
-#[entry_point]
+#[cfg_attr(feature = "host", entry_point)]
+/// The operations of the controllers, only exported with the `host` feature.
+/// The spend limits the proxies set are handled by `remote::execute`, exported in remote-only builds.
 pub fn execute(
-    _deps: DepsMut,
+    deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: ExecuteMsg,
+    msg: ExecuteMsg<TunnelCustomMsg>,
 ) -> Result<Response, ContractError> {
+    ensure_role(execute_role(&msg))?;
+    nonpayable(&info)?;
     match msg {
         ExecuteMsg::RemoteInstantiate {
             inst_msg,
             code_id,
             job_id,
             channel_id,
-        } => execute_remote_instantiate(info, env, inst_msg, code_id, job_id, channel_id),
+            timeout,
+        } => execute_remote_instantiate(
+            deps, info, env, inst_msg, code_id, job_id, channel_id, timeout,
+        ),
         ExecuteMsg::RemoteMigrate {
             migrate_msg,
             job_id,
             new_code_id,
             channel_id,
-        } => execute_remote_migrate(info, env, migrate_msg, new_code_id, job_id, channel_id),
+            fail_on_error,
+            timeout,
+        } => execute_remote_migrate(
+            deps,
+            info,
+            env,
+            migrate_msg,
+            new_code_id,
+            job_id,
+            channel_id,
+            fail_on_error,
+            timeout,
+        ),
         ExecuteMsg::RemoteDispatch {
             dispatch_msg,
             job_id,
             channel_id,
-        } => execute_remote_dispatch(info, env, dispatch_msg, job_id, channel_id),
-        ExecuteMsg::QueryRemoteAddr { channel_id } => execute_remote_query(info, env, channel_id),
+            fail_on_error,
+            timeout,
+        } => execute_remote_dispatch(
+            deps,
+            info,
+            env,
+            dispatch_msg,
+            job_id,
+            channel_id,
+            fail_on_error,
+            timeout,
+        ),
+        ExecuteMsg::QueryRemoteAddr {
+            channel_id,
+            timeout,
+        } => execute_remote_query(deps, info, env, channel_id, timeout),
+        ExecuteMsg::RegisterInterchainAccount {
+            connection_id,
+            counterparty_connection_id,
+            job_id,
+        } => execute_register_interchain_account(
+            deps,
+            info,
+            env,
+            connection_id,
+            counterparty_connection_id,
+            job_id,
+        ),
+        ExecuteMsg::RemoteForward { msg, hops } => {
+            execute_remote_forward(deps, info, env, *msg, hops)
+        }
+        ExecuteMsg::GrantOperator { operator, grant } => {
+            execute_grant_operator(deps, info, operator, grant)
+        }
+        ExecuteMsg::RevokeOperator { operator } => execute_revoke_operator(deps, info, operator),
+        ExecuteMsg::ExecuteAs { controller, msg } => execute_as(deps, env, info, controller, *msg),
+        ExecuteMsg::RegisterSigningKey { key } => execute_register_signing_key(deps, info, key),
+        ExecuteMsg::RevokeSigningKey {} => execute_revoke_signing_key(deps, info),
+        ExecuteMsg::SubmitSigned { payload, signature } => {
+            execute_submit_signed(deps, env, info, payload, signature)
+        }
+        ExecuteMsg::RegisterSessionKey { name, session } => {
+            execute_register_session_key(deps, env, info, name, session)
+        }
+        ExecuteMsg::RevokeSessionKey { name } => execute_revoke_session_key(deps, info, name),
+        msg @ ExecuteMsg::SetSpendLimit { .. } => crate::remote::execute(deps, env, info, msg),
     }
 }
 
-fn create_ibc_msg(channel_id: String, data: PacketMsg, env: Env) -> Result<IbcMsg, ContractError> {
-    Ok(IbcMsg::SendPacket {
-        channel_id,
-        data: to_binary(&data)?,
-        timeout: env.block.time.plus_seconds(PACKET_LIFETIME).into(),
-    })
+/// The role handling the message
+fn execute_role(msg: &ExecuteMsg<TunnelCustomMsg>) -> TunnelRole {
+    match msg {
+        ExecuteMsg::SetSpendLimit { .. } => TunnelRole::Remote,
+        _ => TunnelRole::Host,
+    }
+}
+
+pub fn execute_grant_operator(
+    deps: DepsMut,
+    info: MessageInfo,
+    operator: String,
+    grant: OperatorGrant,
+) -> Result<Response, ContractError> {
+    let operator = deps.api.addr_validate(&operator)?;
+    OPERATORS.save(deps.storage, (&info.sender, &operator), &grant)?;
+
+    let event = OperatorGranted {
+        controller: info.sender.to_string(),
+        operator: operator.to_string(),
+    };
+    Ok(Response::new().add_event(event.into()))
+}
+
+pub fn execute_revoke_operator(
+    deps: DepsMut,
+    info: MessageInfo,
+    operator: String,
+) -> Result<Response, ContractError> {
+    let operator = deps.api.addr_validate(&operator)?;
+    OPERATORS.remove(deps.storage, (&info.sender, &operator));
+
+    let event = OperatorRevoked {
+        controller: info.sender.to_string(),
+        operator: operator.to_string(),
+    };
+    Ok(Response::new().add_event(event.into()))
+}
+
+/// Sends `msg` as the controller if the sender has a valid grant for it
+pub fn execute_as(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    controller: String,
+    msg: ExecuteMsg<TunnelCustomMsg>,
+) -> Result<Response, ContractError> {
+    let controller = deps.api.addr_validate(&controller)?;
+    let grant = OPERATORS
+        .may_load(deps.storage, (&controller, &info.sender))?
+        .ok_or(ContractError::Unauthorized)?;
+    if grant.expires.is_some_and(|e| e.is_expired(&env.block)) {
+        return Err(ContractError::OperatorExpired);
+    }
+    check_operator_grant(&grant, &msg)?;
+
+    let operator = info.sender;
+    let info = MessageInfo {
+        sender: controller,
+        funds: info.funds,
+    };
+    Ok(execute(deps, env, info, msg)?.add_attribute("operator", operator))
+}
+
+fn check_operator_grant(
+    grant: &OperatorGrant,
+    msg: &ExecuteMsg<TunnelCustomMsg>,
+) -> Result<(), ContractError> {
+    let scope = match msg {
+        ExecuteMsg::RemoteDispatch { dispatch_msg, .. } => {
+            let kind = CosmosMsgKind::of(dispatch_msg)?;
+            if let Some(allowed) = &grant.allowed_msgs {
+                if !allowed.contains(&kind) {
+                    return Err(ContractError::MsgKindNotAllowed(kind));
+                }
+            }
+            OperatorScope::Dispatch
+        }
+        ExecuteMsg::RemoteMigrate { .. } => OperatorScope::Migrate,
+        ExecuteMsg::QueryRemoteAddr { .. } => OperatorScope::Query,
+        ExecuteMsg::RemoteForward { msg, .. } => return check_operator_grant(grant, msg),
+        _ => return Err(ContractError::Unauthorized),
+    };
+    if !grant.scopes.contains(&scope) {
+        return Err(ContractError::Unauthorized);
+    }
+    Ok(())
+}
+
+pub fn execute_register_signing_key(
+    deps: DepsMut,
+    info: MessageInfo,
+    key: SigningKey,
+) -> Result<Response, ContractError> {
+    validate_signing_key(&key)?;
+    SIGNING_KEYS.save(deps.storage, &info.sender, &key)?;
+
+    let event = SigningKeyRegistered {
+        controller: info.sender.to_string(),
+    };
+    Ok(Response::new().add_event(event.into()))
+}
+
+pub fn execute_revoke_signing_key(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    SIGNING_KEYS.remove(deps.storage, &info.sender);
+
+    let event = SigningKeyRevoked {
+        controller: info.sender.to_string(),
+    };
+    Ok(Response::new().add_event(event.into()))
+}
+
+fn validate_signing_key(key: &SigningKey) -> Result<(), ContractError> {
+    let valid = match key {
+        SigningKey::Secp256k1(key) => matches!(key.len(), 33 | 65),
+        SigningKey::Ed25519(key) => key.len() == 32,
+    };
+    if !valid {
+        return Err(ContractError::InvalidSigningKey);
+    }
+    Ok(())
+}
+
+pub fn execute_register_session_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: String,
+    session: SessionKey,
+) -> Result<Response, ContractError> {
+    validate_signing_key(&session.key)?;
+    if session.expires.is_expired(&env.block) {
+        return Err(ContractError::SessionExpired);
+    }
+    // the spend limit could not hold for messages spending unknown funds
+    if let Some(kind) = session
+        .allowed_msgs
+        .iter()
+        .find(|kind| !kind.has_known_spend())
+    {
+        return Err(ContractError::UnknownSpend(*kind));
+    }
+    SESSION_KEYS.save(deps.storage, (&info.sender, &name), &session)?;
+    SESSION_SPENT.remove(deps.storage, (&info.sender, &name));
+
+    let event = SessionKeyRegistered {
+        controller: info.sender.to_string(),
+        name,
+    };
+    Ok(Response::new().add_event(event.into()))
+}
+
+pub fn execute_revoke_session_key(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: String,
+) -> Result<Response, ContractError> {
+    SESSION_KEYS.remove(deps.storage, (&info.sender, &name));
+    SESSION_SPENT.remove(deps.storage, (&info.sender, &name));
+
+    let event = SessionKeyRevoked {
+        controller: info.sender.to_string(),
+        name,
+    };
+    Ok(Response::new().add_event(event.into()))
+}
+
+/// Sends the signed operation as its controller, the sender only relays it.
+/// Operations signed with a session key are checked against the session.
+pub fn execute_submit_signed(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payload: Binary,
+    signature: Binary,
+) -> Result<Response, ContractError> {
+    let op: SignedOperation<TunnelCustomMsg> = from_slice(&payload)?;
+    let controller = deps.api.addr_validate(&op.controller)?;
+    let session = match &op.session {
+        Some(name) => Some(
+            SESSION_KEYS
+                .may_load(deps.storage, (&controller, name))?
+                .ok_or_else(|| ContractError::SessionKeyNotFound(name.clone()))?,
+        ),
+        None => None,
+    };
+    let key = match &session {
+        Some(session) => session.key.clone(),
+        None => SIGNING_KEYS
+            .may_load(deps.storage, &controller)?
+            .ok_or(ContractError::SigningKeyNotFound)?,
+    };
+    let verified = match &key {
+        SigningKey::Secp256k1(key) => {
+            deps.api
+                .secp256k1_verify(&Sha256::digest(payload.as_slice()), &signature, key)
+        }
+        SigningKey::Ed25519(key) => deps.api.ed25519_verify(&payload, &signature, key),
+    };
+    if verified != Ok(true) {
+        return Err(ContractError::InvalidSignature);
+    }
+
+    if op.chain_id != env.block.chain_id || op.tunnel != env.contract.address {
+        return Err(ContractError::Unauthorized);
+    }
+    if op.expires.is_expired(&env.block) {
+        return Err(ContractError::SignatureExpired);
+    }
+    // each session key has its own nonces, it does not race the signing key of the controller
+    let last = match &op.session {
+        Some(name) => SESSION_NONCES.may_load(deps.storage, (&controller, name))?,
+        None => SIGNED_NONCES.may_load(deps.storage, &controller)?,
+    };
+    let expected = last.unwrap_or_default() + 1;
+    if op.nonce != expected {
+        return Err(ContractError::InvalidSignedNonce {
+            expected,
+            nonce: op.nonce,
+        });
+    }
+    match &op.session {
+        Some(name) => SESSION_NONCES.save(deps.storage, (&controller, name), &op.nonce)?,
+        None => SIGNED_NONCES.save(deps.storage, &controller, &op.nonce)?,
+    }
+    match (&op.session, session) {
+        (Some(name), Some(session)) => {
+            check_session(deps.storage, &env, &controller, name, &session, &op.msg)?
+        }
+        _ => check_signed_msg(&op.msg)?,
+    }
+
+    let relayer = info.sender;
+    let info = MessageInfo {
+        sender: controller,
+        funds: vec![],
+    };
+    Ok(execute(deps, env, info, op.msg)?.add_attribute("relayer", relayer))
+}
+
+/// Signed operations are the remote operations, a key cannot grant operators or change keys
+fn check_signed_msg(msg: &ExecuteMsg<TunnelCustomMsg>) -> Result<(), ContractError> {
+    match msg {
+        ExecuteMsg::RemoteInstantiate { .. }
+        | ExecuteMsg::RemoteMigrate { .. }
+        | ExecuteMsg::RemoteDispatch { .. }
+        | ExecuteMsg::QueryRemoteAddr { .. } => Ok(()),
+        ExecuteMsg::RemoteForward { msg, .. } => check_signed_msg(msg),
+        _ => Err(ContractError::Unauthorized),
+    }
+}
+
+/// A session can only dispatch on its channels, the kinds of messages it allows and within its spend limit.
+/// The funds count as spent once the dispatch is sent, they are not credited back if it fails
+fn check_session(
+    storage: &mut dyn Storage,
+    env: &Env,
+    controller: &Addr,
+    name: &str,
+    session: &SessionKey,
+    msg: &ExecuteMsg<TunnelCustomMsg>,
+) -> Result<(), ContractError> {
+    if session.expires.is_expired(&env.block) {
+        return Err(ContractError::SessionExpired);
+    }
+    let (dispatch_msg, channel_id) = match msg {
+        ExecuteMsg::RemoteDispatch {
+            dispatch_msg,
+            channel_id,
+            ..
+        } => (dispatch_msg, channel_id),
+        _ => return Err(ContractError::Unauthorized),
+    };
+    if !session.channels.contains(channel_id) {
+        return Err(ContractError::ChannelNotAllowed(channel_id.clone()));
+    }
+    let kind = CosmosMsgKind::of(dispatch_msg)?;
+    if !session.allowed_msgs.contains(&kind) {
+        return Err(ContractError::MsgKindNotAllowed(kind));
+    }
+    if !kind.has_known_spend() {
+        return Err(ContractError::UnknownSpend(kind));
+    }
+
+    let mut spent = SESSION_SPENT
+        .may_load(storage, (controller, name))?
+        .unwrap_or_default();
+    for coin in spent_funds(dispatch_msg)? {
+        let limit = session
+            .spend_limit
+            .iter()
+            .find(|limit| limit.denom == coin.denom)
+            .map(|limit| limit.amount)
+            .unwrap_or_default();
+        let total = match spent.iter_mut().find(|c| c.denom == coin.denom) {
+            Some(c) => {
+                c.amount += coin.amount;
+                c.amount
+            }
+            None => {
+                spent.push(coin.clone());
+                coin.amount
+            }
+        };
+        if total > limit {
+            return Err(ContractError::SpendLimitExceeded(coin.denom));
+        }
+    }
+    SESSION_SPENT.save(storage, (controller, name), &spent)?;
+    Ok(())
 }
 
+impl TunnelHost<TunnelCustomMsg> for Tunnel {
+    type Error = ContractError;
+
+    /// Nonces increase per (connection, controller), starting at 1
+    fn next_nonce(
+        &self,
+        deps: DepsMut,
+        channel_id: &str,
+        controller: &str,
+    ) -> Result<u64, ContractError> {
+        let connection_id =
+            get_connection_id_from_channel(deps.as_ref(), channel_id.to_string(), None)?;
+        let nonce = sent_nonce(deps.as_ref(), channel_id, controller)? + 1;
+        SENT_NONCES.save(deps.storage, (&connection_id, controller), &nonce)?;
+        Ok(nonce)
+    }
+
+    fn codec(&self, storage: &dyn Storage, channel_id: &str) -> Result<PacketCodec, ContractError> {
+        Ok(channel_codec(storage, channel_id)?)
+    }
+
+    fn counterparty(
+        &self,
+        deps: Deps,
+        channel_id: &str,
+    ) -> Result<Option<ChannelMetadata>, ContractError> {
+        Ok(CHANNEL_METADATA.may_load(deps.storage, channel_id)?)
+    }
+
+    /// Returns the ack of `packet` to its controller: a callback to a controller on this chain,
+    /// or a `ForwardAck` towards the origin of a forwarded packet
+    fn deliver_ack(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        channel_id: &str,
+        packet: &PacketMsg<TunnelCustomMsg>,
+        ack: StdAck,
+    ) -> Result<IbcBasicResponse, ContractError> {
+        let res = IbcBasicResponse::new();
+        let forward = matches!(
+            packet,
+            PacketMsg::Forward { .. }
+                | PacketMsg::Chunk {
+                    operation: TunnelOperation::Forward,
+                    ..
+                }
+        );
+        match packet {
+            // The ack of the forwarded operation is returned in a `ForwardAck`,
+            // so only a failed forward is delivered here
+            _ if forward && matches!(ack, StdAck::Result(_)) => return Ok(res),
+            PacketMsg::ForwardAck { .. } => return Ok(res),
+            _ => {}
+        }
+
+        // the chunks of a packet do not carry its nonce, their job cannot be forwarded twice at once
+        let hop = packet.nonce().map(|nonce| ForwardHop {
+            channel_id: channel_id.to_string(),
+            nonce,
+        });
+        let job_id = packet.job_id();
+        match split_forwarded_controller(packet.controller()) {
+            // without a job or a route of this packet, e.g. already taken by an ack of the same job, the ack is not returned
+            Some(_) => match job_id {
+                Some(job_id)
+                    if FORWARD_ROUTES
+                        .may_load(deps.storage, (packet.controller(), job_id))?
+                        .is_some_and(|route| hop.is_none() || hop == Some(route.sent)) =>
+                {
+                    Ok(res.add_message(forward_ack_msg(
+                        deps.storage,
+                        env,
+                        packet.controller(),
+                        job_id,
+                        hop.as_ref(),
+                        ack,
+                    )?))
+                }
+                _ => Ok(res),
+            },
+            None => {
+                if let (true, Some(job_id)) = (forward, job_id) {
+                    let key = (packet.controller(), job_id.as_str());
+                    if hop.is_none() || PENDING_FORWARDS.may_load(deps.storage, key)? == hop {
+                        PENDING_FORWARDS.remove(deps.storage, key);
+                    }
+                }
+                Ok(res.add_submessages(callback_msg(packet.controller(), job_id, ack)?))
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn execute_remote_instantiate(
+    deps: DepsMut,
     info: MessageInfo,
     env: Env,
-    inst_msg: CosmosMsg,
+    inst_msg: CosmosMsg<TunnelCustomMsg>,
     code_id: u64,
     job_id: Option<String>,
     channel_id: String,
+    timeout: Option<u64>,
 ) -> Result<Response, ContractError> {
-    let packet: PacketMsg = PacketMsg::Instantiate {
+    let packet = PacketMsg::Instantiate {
         controller: info.sender.to_string(),
         inst_msg,
         code_id,
         job_id: job_id.clone(),
+        nonce: 0,
     };
-    let msg = create_ibc_msg(channel_id.clone(), packet, env)?;
+    let msgs = Tunnel.send_packet(
+        deps,
+        &env,
+        OutgoingPacket {
+            channel_id: channel_id.clone(),
+            packet,
+            timeout,
+        },
+    )?;
 
-    let event = Event::new("ica-tunnel.V1.HostMsg.InstantiationRequested")
-        .add_attribute("channel_id", channel_id)
-        .add_attribute("job_id", format!("{job_id:?}"));
+    let event = InstantiationRequested {
+        channel_id,
+        controller: info.sender.to_string(),
+        job_id,
+    };
 
-    Ok(Response::new().add_message(msg).add_event(event))
+    Ok(Response::new().add_messages(msgs).add_event(event.into()))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_remote_migrate(
+    deps: DepsMut,
     info: MessageInfo,
     env: Env,
-    migration_msg: CosmosMsg,
+    migration_msg: CosmosMsg<TunnelCustomMsg>,
     new_code_id: u64,
     job_id: Option<String>,
     channel_id: String,
+    fail_on_error: bool,
+    timeout: Option<u64>,
 ) -> Result<Response, ContractError> {
-    let packet: PacketMsg = PacketMsg::Migrate {
+    let packet = PacketMsg::Migrate {
         controller: info.sender.to_string(),
         migration_msg,
         job_id: job_id.clone(),
         new_code_id,
+        fail_on_error,
+        nonce: 0,
     };
-    let msg = create_ibc_msg(channel_id.clone(), packet, env)?;
+    let msgs = Tunnel.send_packet(
+        deps,
+        &env,
+        OutgoingPacket {
+            channel_id: channel_id.clone(),
+            packet,
+            timeout,
+        },
+    )?;
 
-    let event = Event::new("ica-tunnel.V1.HostMsg.MigrationRequested")
-        .add_attribute("channel_id", channel_id)
-        .add_attribute("job_id", format!("{job_id:?}"));
+    let event = MigrationRequested {
+        channel_id,
+        controller: info.sender.to_string(),
+        job_id,
+    };
 
-    Ok(Response::new().add_message(msg).add_event(event))
+    Ok(Response::new().add_messages(msgs).add_event(event.into()))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_remote_dispatch(
+    deps: DepsMut,
     info: MessageInfo,
     env: Env,
-    msg: CosmosMsg,
+    msg: CosmosMsg<TunnelCustomMsg>,
     job_id: Option<String>,
     channel_id: String,
+    fail_on_error: bool,
+    timeout: Option<u64>,
 ) -> Result<Response, ContractError> {
-    let packet: PacketMsg = PacketMsg::Dispatch {
+    if let Some((controller, connection_id)) = ICA_CHANNELS.may_load(deps.storage, &channel_id)? {
+        if controller != info.sender {
+            return Err(ContractError::Unauthorized);
+        }
+        return execute_ica_dispatch(deps, info, msg, job_id, channel_id, connection_id, timeout);
+    }
+
+    let packet = PacketMsg::Dispatch {
         controller: info.sender.to_string(),
         msg,
         job_id: job_id.clone(),
+        fail_on_error,
+        nonce: 0,
     };
-    let msg = create_ibc_msg(channel_id.clone(), packet, env)?;
+    let msgs = Tunnel.send_packet(
+        deps,
+        &env,
+        OutgoingPacket {
+            channel_id: channel_id.clone(),
+            packet,
+            timeout,
+        },
+    )?;
 
-    let event = Event::new("ica-tunnel.V1.HostMsg.DispatchRequested")
-        .add_attribute("channel_id", channel_id)
-        .add_attribute("job_id", format!("{job_id:?}"));
+    let event = DispatchRequested {
+        channel_id,
+        controller: info.sender.to_string(),
+        job_id,
+    };
 
-    Ok(Response::new().add_message(msg).add_event(event))
+    Ok(Response::new().add_messages(msgs).add_event(event.into()))
+}
+
+/// Sends the message to the native interchain account on the ICS-27 channel.
+/// ICS-27 txs are atomic, a failing message is always acknowledged with an error.
+#[allow(clippy::too_many_arguments)]
+fn execute_ica_dispatch(
+    deps: DepsMut,
+    info: MessageInfo,
+    msg: CosmosMsg<TunnelCustomMsg>,
+    job_id: Option<String>,
+    channel_id: String,
+    connection_id: String,
+    timeout: Option<u64>,
+) -> Result<Response, ContractError> {
+    if timeout == Some(0) {
+        return Err(ContractError::InvalidTimeout);
+    }
+    let account = ICA_ACCOUNTS.load(deps.storage, (&info.sender, &connection_id))?;
+    let memo = IcaMemo {
+        controller: info.sender.to_string(),
+        job_id: job_id.clone(),
+    };
+    let msg: CosmosMsg = send_tx_msg(
+        account.owner,
+        connection_id,
+        vec![msg],
+        &memo,
+        timeout.unwrap_or(PACKET_LIFETIME),
+    )?;
+
+    let event = DispatchRequested {
+        channel_id,
+        controller: info.sender.to_string(),
+        job_id,
+    };
+
+    Ok(Response::new().add_message(msg).add_event(event.into()))
+}
+
+pub fn execute_register_interchain_account(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    connection_id: String,
+    counterparty_connection_id: String,
+    job_id: Option<String>,
+) -> Result<Response, ContractError> {
+    // the controller can register again while no channel is open, to retry a registration
+    // or after a timeout closed the channel, the account keeps its owner and so its address
+    let key = (&info.sender, connection_id.as_str());
+    let owner = match ICA_ACCOUNTS.may_load(deps.storage, key)? {
+        Some(account) if account.channel_id.is_some() => {
+            return Err(ContractError::ChannelAlreadyRegistered)
+        }
+        Some(account) => account.owner,
+        None => ica_owner(env.contract.address.as_str(), info.sender.as_str()),
+    };
+    ICA_ACCOUNTS.save(
+        deps.storage,
+        key,
+        &IcaAccount {
+            controller: info.sender.clone(),
+            owner: owner.clone(),
+            channel_id: None,
+            address: None,
+            job_id: job_id.clone(),
+        },
+    )?;
+    ICA_OWNERS.save(
+        deps.storage,
+        (&ica_controller_port(&owner), &connection_id),
+        &info.sender,
+    )?;
+    let msg: CosmosMsg =
+        register_interchain_account_msg(owner, connection_id.clone(), counterparty_connection_id)?;
+
+    let event = InterchainAccountRequested {
+        connection_id,
+        controller: info.sender.to_string(),
+        job_id,
+    };
+
+    Ok(Response::new().add_message(msg).add_event(event.into()))
+}
+
+pub fn execute_remote_forward(
+    mut deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    msg: ExecuteMsg<TunnelCustomMsg>,
+    hops: Vec<String>,
+) -> Result<Response, ContractError> {
+    let OutgoingPacket {
+        channel_id,
+        packet,
+        timeout,
+    } = Tunnel
+        .create_packet(info.sender.to_string(), msg)
+        .ok_or(ContractError::InvalidForward)?;
+    if hops.is_empty() {
+        return Err(ContractError::InvalidForward);
+    }
+    if hops.len() > MAX_FORWARD_HOPS {
+        return Err(ContractError::HopLimitExceeded(MAX_FORWARD_HOPS));
+    }
+
+    let job_id = packet.job_id().cloned();
+    if let Some(job_id) = &job_id {
+        if PENDING_FORWARDS.has(deps.storage, (info.sender.as_str(), job_id)) {
+            return Err(ContractError::ForwardPending(job_id.clone()));
+        }
+    }
+    let packet = PacketMsg::Forward {
+        hops: hops.clone(),
+        visited: vec![env.block.chain_id.clone()],
+        packet: Box::new(packet),
+    };
+    let msgs = Tunnel.send_packet(
+        deps.branch(),
+        &env,
+        OutgoingPacket {
+            channel_id: channel_id.clone(),
+            packet,
+            timeout,
+        },
+    )?;
+    if let Some(job_id) = &job_id {
+        let hop = ForwardHop {
+            channel_id: channel_id.clone(),
+            nonce: sent_nonce(deps.as_ref(), &channel_id, info.sender.as_str())?,
+        };
+        PENDING_FORWARDS.save(deps.storage, (info.sender.as_str(), job_id), &hop)?;
+    }
+
+    let event = ForwardRequested {
+        channel_id,
+        controller: info.sender.to_string(),
+        hops,
+        job_id,
+    };
+
+    Ok(Response::new().add_messages(msgs).add_event(event.into()))
 }
 
 pub fn execute_remote_query(
+    deps: DepsMut,
     info: MessageInfo,
     env: Env,
     channel_id: String,
+    timeout: Option<u64>,
 ) -> Result<Response, ContractError> {
-    let packet: PacketMsg = PacketMsg::WhoAmI {
+    let packet = PacketMsg::WhoAmI {
         controller: info.sender.to_string(),
+        nonce: 0,
     };
-    let msg = create_ibc_msg(channel_id.clone(), packet, env)?;
+    let msgs = Tunnel.send_packet(
+        deps,
+        &env,
+        OutgoingPacket {
+            channel_id: channel_id.clone(),
+            packet,
+            timeout,
+        },
+    )?;
 
-    let event = Event::new("ica-tunnel.V1.HostMsg.RemoteAddrRequested")
-        .add_attribute("channel_id", channel_id)
-        .add_attribute("controller", info.sender);
+    let event = RemoteAddrRequested {
+        channel_id,
+        controller: info.sender.to_string(),
+    };
 
-    Ok(Response::new().add_message(msg).add_event(event))
+    Ok(Response::new().add_messages(msgs).add_event(event.into()))
 }
 
 #[entry_point]
+/// Forwards the ack to the controller if the packet was sent with a `job_id`
 pub fn ibc_packet_ack(
-    _deps: DepsMut,
-    _env: Env,
-    _msg: IbcPacketAckMsg,
-) -> StdResult<IbcBasicResponse> {
-    Ok(IbcBasicResponse::new().add_attribute("action", "ibc_packet_ack"))
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketAckMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    Tunnel.packet_ack(deps, env, msg)
 }
 
 #[entry_point]
+/// Notifies the controller with a `TunnelErrorCode::Timeout` error if the packet was sent with a `job_id`
 pub fn ibc_packet_timeout(
-    _deps: DepsMut,
-    _env: Env,
-    _msg: IbcPacketTimeoutMsg,
-) -> StdResult<IbcBasicResponse> {
-    Ok(IbcBasicResponse::new().add_attribute("action", "ibc_packet_timeout"))
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketTimeoutMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    Tunnel.packet_timeout(deps, env, msg)
+}
+
+/// Sends the ack of the forwarded packet of `controller` (as `forwarded_controller`) and `job_id`
+/// back on the channel it was received from, and removes its route.
+/// An ack received from `hop`, of the forwarded packet or in a `ForwardAck`, is only returned if the packet was sent there.
+pub fn forward_ack_msg(
+    storage: &mut dyn Storage,
+    env: &Env,
+    controller: &str,
+    job_id: &str,
+    hop: Option<&ForwardHop>,
+    ack: StdAck,
+) -> Result<IbcMsg, ContractError> {
+    let route = FORWARD_ROUTES
+        .may_load(storage, (controller, job_id))?
+        .ok_or(ContractError::Unauthorized)?;
+    if hop.is_some_and(|hop| *hop != route.sent) {
+        return Err(ContractError::Unauthorized);
+    }
+    FORWARD_ROUTES.remove(storage, (controller, job_id));
+    let (_, _, origin_controller) =
+        split_forwarded_controller(controller).ok_or(ContractError::InvalidForward)?;
+    let channel_id = route.received.channel_id;
+    let packet = PacketMsg::ForwardAck {
+        controller: origin_controller.to_string(),
+        job_id: Some(job_id.to_string()),
+        nonce: route.received.nonce,
+        ack,
+    };
+    Ok(Tunnel.ibc_msg(
+        env,
+        channel_codec(storage, &channel_id)?,
+        OutgoingPacket {
+            channel_id,
+            packet,
+            timeout: None,
+        },
+    )?)
+}
+
+/// See `TunnelHost::callback_msg`
+pub fn callback_msg(
+    controller: &str,
+    job_id: Option<&String>,
+    ack: StdAck,
+) -> StdResult<Option<SubMsg>> {
+    Tunnel.callback_msg(controller, job_id, ack)
+}
+
+/// Handles the ICS-27 packet lifecycle of the native interchain accounts,
+/// delivered by the chain's ICA controller middleware through `sudo`
+pub fn sudo_ica(deps: DepsMut, msg: IcaSudoMsg) -> Result<Response, ContractError> {
+    ensure_role(TunnelRole::Host)?;
+    match msg {
+        IcaSudoMsg::OpenAck {
+            port_id,
+            channel_id,
+            counterparty_version,
+            ..
+        } => sudo_open_ack(deps, port_id, channel_id, counterparty_version),
+        IcaSudoMsg::Response { request, data } => {
+            let memo = request.ica_memo()?;
+            let ack = StdAck::Result(to_binary(&IcaTxResponse {
+                msg_responses: decode_ica_ack(&data)?,
+                job_id: memo.job_id.clone(),
+            })?);
+            sudo_callback(memo, ack, "ica_response")
+        }
+        IcaSudoMsg::Error { request, details } => {
+            let memo = request.ica_memo()?;
+            let ack = StdAck::error_with(&ErrorResponse {
+                code: TunnelErrorCode::ExecutionFailed,
+                message: details,
+                job_id: memo.job_id.clone(),
+            })?;
+            sudo_callback(memo, ack, "ica_error")
+        }
+        IcaSudoMsg::Timeout { request } => {
+            // ICS-27 channels are ordered, a timeout closes the channel
+            if let Some(channel_id) = &request.source_channel {
+                if let Some((controller, connection_id)) =
+                    ICA_CHANNELS.may_load(deps.storage, channel_id)?
+                {
+                    ICA_CHANNELS.remove(deps.storage, channel_id);
+                    ICA_ACCOUNTS.update(
+                        deps.storage,
+                        (&controller, &connection_id),
+                        |account| {
+                            let mut account = account.ok_or(ContractError::AccountNotFound)?;
+                            account.channel_id = None;
+                            Ok::<_, ContractError>(account)
+                        },
+                    )?;
+                }
+            }
+            let memo = request.ica_memo()?;
+            let ack = StdAck::error_with(&ErrorResponse {
+                code: TunnelErrorCode::Timeout,
+                message: "ICS-27 Packet Timeout".to_string(),
+                job_id: memo.job_id.clone(),
+            })?;
+            sudo_callback(memo, ack, "ica_timeout")
+        }
+    }
+}
+
+fn sudo_open_ack(
+    deps: DepsMut,
+    port_id: String,
+    channel_id: String,
+    counterparty_version: String,
+) -> Result<Response, ContractError> {
+    let metadata: IcaMetadata = from_slice(counterparty_version.as_bytes())?;
+    let connection_id = metadata.controller_connection_id;
+    // only the ports of the accounts the controllers registered are ours
+    let controller = ICA_OWNERS
+        .may_load(deps.storage, (&port_id, &connection_id))?
+        .ok_or(ContractError::Unauthorized)?;
+    let key = (&controller, connection_id.as_str());
+    let mut account = ICA_ACCOUNTS
+        .may_load(deps.storage, key)?
+        .ok_or(ContractError::AccountNotFound)?;
+    account.channel_id = Some(channel_id.clone());
+    account.address = Some(metadata.address.clone());
+    ICA_ACCOUNTS.save(deps.storage, key, &account)?;
+    ICA_CHANNELS.save(deps.storage, &channel_id, &(controller, connection_id))?;
+
+    let event = InterchainAccountOpened {
+        channel_id,
+        controller: account.controller.to_string(),
+        address: metadata.address.clone(),
+    };
+    let ack = StdAck::Result(to_binary(&WhoAmIResponse {
+        account: metadata.address,
+    })?);
+
+    Ok(Response::new()
+        .add_submessages(callback_msg(
+            account.controller.as_str(),
+            account.job_id.as_ref(),
+            ack,
+        )?)
+        .add_event(event.into()))
+}
+
+fn sudo_callback(memo: IcaMemo, ack: StdAck, action: &str) -> Result<Response, ContractError> {
+    Ok(Response::new()
+        .add_submessages(callback_msg(&memo.controller, memo.job_id.as_ref(), ack)?)
+        .add_attribute("action", action))
+}
+
+/// The nonce of the last packet sent for `controller` on the connection of `channel_id`, 0 if none
+pub fn sent_nonce(deps: Deps, channel_id: &str, controller: &str) -> Result<u64, ContractError> {
+    let connection_id = get_connection_id_from_channel(deps, channel_id.to_string(), None)?;
+    Ok(SENT_NONCES
+        .may_load(deps.storage, (&connection_id, controller))?
+        .unwrap_or_default())
+}
+
+pub fn query_sent_nonce(
+    deps: Deps,
+    connection_id: String,
+    controller: String,
+) -> StdResult<NonceResponse> {
+    let nonce = SENT_NONCES
+        .may_load(deps.storage, (&connection_id, &controller))?
+        .unwrap_or_default();
+    Ok(NonceResponse { nonce })
+}
+
+pub fn query_signing_key(deps: Deps, controller: String) -> StdResult<SigningKeyResponse> {
+    let controller = deps.api.addr_validate(&controller)?;
+    let key = SIGNING_KEYS.may_load(deps.storage, &controller)?;
+    Ok(SigningKeyResponse { key })
+}
+
+pub fn query_session_key(
+    deps: Deps,
+    controller: String,
+    name: String,
+) -> StdResult<SessionKeyResponse> {
+    let controller = deps.api.addr_validate(&controller)?;
+    let session = SESSION_KEYS.may_load(deps.storage, (&controller, &name))?;
+    let spent = SESSION_SPENT
+        .may_load(deps.storage, (&controller, &name))?
+        .unwrap_or_default();
+    let nonce = SESSION_NONCES
+        .may_load(deps.storage, (&controller, &name))?
+        .unwrap_or_default();
+    Ok(SessionKeyResponse {
+        session,
+        spent,
+        nonce,
+    })
+}
+
+pub fn query_signed_nonce(deps: Deps, controller: String) -> StdResult<NonceResponse> {
+    let controller = deps.api.addr_validate(&controller)?;
+    let nonce = SIGNED_NONCES
+        .may_load(deps.storage, &controller)?
+        .unwrap_or_default();
+    Ok(NonceResponse { nonce })
+}
+
+pub fn query_operator(
+    deps: Deps,
+    controller: String,
+    operator: String,
+) -> StdResult<OperatorResponse> {
+    let controller = deps.api.addr_validate(&controller)?;
+    let operator = deps.api.addr_validate(&operator)?;
+    let grant = OPERATORS.may_load(deps.storage, (&controller, &operator))?;
+    Ok(OperatorResponse { grant })
+}
+
+pub fn query_list_operators(
+    deps: Deps,
+    controller: String,
+    start_after: Option<String>,
+    limit: Option<u64>,
+) -> StdResult<ListOperatorsResponse> {
+    let controller = deps.api.addr_validate(&controller)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let start_bound = start_after.as_ref().map(Bound::exclusive);
+
+    let operators = OPERATORS
+        .prefix(&controller)
+        .range(deps.storage, start_bound, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (operator, grant) = item?;
+            Ok(OperatorInfo {
+                operator: operator.into(),
+                grant,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListOperatorsResponse { operators })
+}
+
+pub fn query_interchain_account(
+    deps: Deps,
+    controller: String,
+    connection_id: String,
+) -> StdResult<InterchainAccountResponse> {
+    let controller = deps.api.addr_validate(&controller)?;
+    let account = ICA_ACCOUNTS.load(deps.storage, (&controller, &connection_id))?;
+    Ok(InterchainAccountResponse {
+        controller: account.controller.into(),
+        owner: account.owner,
+        channel_id: account.channel_id,
+        address: account.address,
+    })
+}
+
+pub fn reply_ack_callback(reply: Reply) -> Result<Response, ContractError> {
+    let err = reply.result.into_result().err().unwrap_or_default();
+    Ok(Response::new()
+        .add_attribute("action", "reply_ack_callback")
+        .add_attribute("error", err))
 }
@@ -1,8 +1,34 @@
 pub mod contract;
 pub mod error;
+pub mod migrations;
 pub mod state;
 
 // module used in the host chain - where the controllers live
 pub mod host;
 // module used in the remote chain - where the ICA or other contracts live
 pub mod remote;
+
+// the entry points the contract exports, to call them as the chain does
+pub use crate::contract::{
+    ibc_channel_close, ibc_channel_connect, ibc_channel_open, instantiate, migrate, sudo,
+};
+#[cfg(feature = "host")]
+pub use crate::host::execute;
+pub use crate::host::{ibc_packet_ack, ibc_packet_timeout};
+#[cfg(not(feature = "host"))]
+pub use crate::remote::execute;
+pub use crate::remote::{ibc_packet_receive, query, reply};
+
+/// The custom message binding (`CosmosMsg::Custom`) of the remote chains this tunnel dispatches to,
+/// selected by the cargo feature of the binding, see `cosmwasm_tunnel::bindings`
+#[cfg(not(feature = "token-factory"))]
+pub type TunnelCustomMsg = cosmwasm_std::Empty;
+#[cfg(feature = "token-factory")]
+pub type TunnelCustomMsg = cosmwasm_tunnel::bindings::TokenFactoryMsg;
+
+/// The binding the tunnel is built for, its schema is generated in `schema/{binding}`
+pub const BINDING: Option<&str> = if cfg!(feature = "token-factory") {
+    Some("token-factory")
+} else {
+    None
+};
@@ -0,0 +1,55 @@
+use cosmwasm_std::Storage;
+use cosmwasm_tunnel::MAX_NONCE_WINDOW;
+use semver::Version;
+
+use crate::error::ContractError;
+use crate::state::{NONCE_WINDOW, PENDING};
+
+/// A state migration, applied when migrating from a version older than `version`
+pub struct Migration {
+    /// The first contract version with the migrated state layout
+    pub version: &'static str,
+    pub migrate: fn(&mut dyn Storage) -> Result<(), ContractError>,
+}
+
+/// All state migrations, ordered by version.
+/// A change to the storage layout (e.g. the key of `ACCOUNTS`) adds a migration here
+/// for the version that introduces it, moving the old entries to the new layout.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: "0.2.0",
+    migrate: migrate_v0_2_0,
+}];
+
+pub fn parse_version(version: &str) -> Result<Version, ContractError> {
+    Version::parse(version).map_err(|e| ContractError::InvalidVersion(e.to_string()))
+}
+
+/// Applies the migrations in `(from, to]` in order, returns the applied versions
+pub fn run_migrations(
+    storage: &mut dyn Storage,
+    from: &Version,
+    to: &Version,
+) -> Result<Vec<&'static str>, ContractError> {
+    let mut applied = vec![];
+    for migration in MIGRATIONS {
+        let version = parse_version(migration.version)?;
+        if &version > from && &version <= to {
+            (migration.migrate)(storage)?;
+            applied.push(migration.version);
+        }
+    }
+    Ok(applied)
+}
+
+/// From the first release, whose state was only `pending` and `accounts`.
+/// `ACCOUNTS` kept its `(connection, port, controller)` key and `Addr` value, so the proxies of the
+/// controllers are kept as they are and receive their nonces in `RECEIVED_NONCES` under the same key.
+/// The `pending` sender left by a call whose reply never ran does not have the fields of `SenderInfo`
+/// (e.g. `fail_on_error`) and is dropped, and the nonce window is stored as instantiated tunnels have it.
+fn migrate_v0_2_0(storage: &mut dyn Storage) -> Result<(), ContractError> {
+    PENDING.remove(storage);
+    if NONCE_WINDOW.may_load(storage)?.is_none() {
+        NONCE_WINDOW.save(storage, &MAX_NONCE_WINDOW)?;
+    }
+    Ok(())
+}
@@ -1,23 +1,40 @@
+use crate::contract::{channel_codec, channel_encoding, ensure_role, protocol_version, Tunnel};
 use crate::error::ContractError;
+use crate::host::{
+    callback_msg, forward_ack_msg, query_interchain_account, query_list_operators, query_operator,
+    query_sent_nonce, query_session_key, query_signed_nonce, query_signing_key, reply_ack_callback,
+    sent_nonce,
+};
 use crate::state::{
-    SenderInfo, ACCOUNTS, DEFAULT_LIMIT, INIT_CALLBACK_ID, MAX_LIMIT, MIGRATE_CALLBACK_ID, PENDING,
-    RECEIVE_DISPATCH_ID,
+    ForwardHop, ForwardRoute, Spend, Upload, ACCOUNTS, CHANNEL_METADATA, CHUNKS, DEFAULT_LIMIT,
+    FORWARD_ROUTES, GLOBAL_MSG_POLICY, MAX_LIMIT, MSG_POLICIES, NONCE_WINDOW, PENDING,
+    PENDING_FORWARDS, PENDING_SPEND, RECEIVED_NONCES, SPENDS, SPEND_LIMITS, UPLOADS,
 };
+use crate::TunnelCustomMsg;
+use cosmwasm_tunnel::events::{MsgPolicyRemoved, MsgPolicySet, SpendLimitRemoved, SpendLimitSet};
 use cosmwasm_tunnel::{
-    AccountInfo, AccountResponse, DispatchMigrateResponse, InstantiateResponse,
-    ListAccountsResponse, PacketMsg, QueryMsg, StdAck, WhoAmIResponse,
+    chunk_hash, forwarded_controller, spent_funds, split_forwarded_controller, AccountInfo,
+    AccountResponse, ChannelMetadataResponse, ChunkResponse, CosmosMsgKind, ExecuteMsg,
+    ForwardResponse, GovernanceMsg, ListAccountsResponse, MsgPolicy, MsgPolicyResponse,
+    NonceResponse, OutgoingPacket, PacketChunk, PacketCodec, PacketMsg, PacketSource, QueryMsg,
+    SenderInfo, SpendLimit, SpendLimitResponse, StdAck, TunnelErrorCode, TunnelHost, TunnelRemote,
+    TunnelRole, ACK_CALLBACK_ID, CHUNK_UPLOAD_LIFETIME, MAX_CHUNKS, MAX_FORWARD_HOPS,
+    MAX_NONCE_WINDOW, MAX_UPLOADS, RECEIVE_DISPATCH_ID,
 };
 
 use cosmwasm_std::{
-    entry_point, from_slice, to_binary, wasm_execute, CosmosMsg, Deps, DepsMut, Env, Event,
-    IbcEndpoint, IbcPacketReceiveMsg, IbcQuery, IbcReceiveResponse, Order, QueryRequest,
-    QueryResponse, Reply, Response, StdResult, SubMsg, WasmMsg,
+    entry_point, to_binary, Addr, Coin, CosmosMsg, Deps, DepsMut, Env, IbcPacketReceiveMsg,
+    IbcQuery, IbcReceiveResponse, MessageInfo, Order, QueryRequest, QueryResponse, Reply, Response,
+    StdError, StdResult, Storage, SubMsgResult, Uint128, WasmMsg,
 };
 use cw_storage_plus::Bound;
-use cw_utils::parse_reply_instantiate_data;
+use cw_utils::nonpayable;
 
 #[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
+    if let Some(role) = query_role(&msg) {
+        ensure_role(role).map_err(|e| StdError::generic_err(e.to_string()))?;
+    }
     match msg {
         QueryMsg::Account {
             connection_id,
@@ -27,9 +44,79 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
         QueryMsg::ListAccounts { start_after, limit } => {
             to_binary(&query_list_accounts(deps, start_after, limit)?)
         }
+        QueryMsg::InterchainAccount {
+            controller,
+            connection_id,
+        } => to_binary(&query_interchain_account(deps, controller, connection_id)?),
+        QueryMsg::ReceivedNonce {
+            connection_id,
+            port_id,
+            controller,
+        } => to_binary(&query_received_nonce(
+            deps,
+            connection_id,
+            port_id,
+            controller,
+        )?),
+        QueryMsg::SentNonce {
+            connection_id,
+            controller,
+        } => to_binary(&query_sent_nonce(deps, connection_id, controller)?),
+        QueryMsg::Operator {
+            controller,
+            operator,
+        } => to_binary(&query_operator(deps, controller, operator)?),
+        QueryMsg::ListOperators {
+            controller,
+            start_after,
+            limit,
+        } => to_binary(&query_list_operators(deps, controller, start_after, limit)?),
+        QueryMsg::SigningKey { controller } => to_binary(&query_signing_key(deps, controller)?),
+        QueryMsg::SessionKey { controller, name } => {
+            to_binary(&query_session_key(deps, controller, name)?)
+        }
+        QueryMsg::SignedNonce { controller } => to_binary(&query_signed_nonce(deps, controller)?),
+        QueryMsg::MsgPolicy { account } => to_binary(&query_msg_policy(deps, account)?),
+        QueryMsg::SpendLimit { account } => to_binary(&query_spend_limit(deps, env, account)?),
+        QueryMsg::ChannelMetadata { channel_id } => {
+            to_binary(&query_channel_metadata(deps, channel_id)?)
+        }
+    }
+}
+
+/// The role whose state is queried, `None` for the state of both
+fn query_role(msg: &QueryMsg) -> Option<TunnelRole> {
+    match msg {
+        QueryMsg::Account { .. }
+        | QueryMsg::ListAccounts { .. }
+        | QueryMsg::ReceivedNonce { .. }
+        | QueryMsg::MsgPolicy { .. }
+        | QueryMsg::SpendLimit { .. } => Some(TunnelRole::Remote),
+        QueryMsg::InterchainAccount { .. }
+        | QueryMsg::SentNonce { .. }
+        | QueryMsg::Operator { .. }
+        | QueryMsg::ListOperators { .. }
+        | QueryMsg::SigningKey { .. }
+        | QueryMsg::SessionKey { .. }
+        | QueryMsg::SignedNonce { .. } => Some(TunnelRole::Host),
+        QueryMsg::ChannelMetadata { .. } => None,
     }
 }
 
+pub fn query_channel_metadata(
+    deps: Deps,
+    channel_id: String,
+) -> StdResult<ChannelMetadataResponse> {
+    let version = protocol_version(deps.storage, &channel_id)?;
+    let encoding = channel_encoding(deps.storage, &channel_id)?;
+    let metadata = CHANNEL_METADATA.may_load(deps.storage, &channel_id)?;
+    Ok(ChannelMetadataResponse {
+        version,
+        encoding,
+        metadata,
+    })
+}
+
 pub fn query_account(
     deps: Deps,
     connection: String,
@@ -69,6 +156,400 @@ pub fn query_list_accounts(
     Ok(ListAccountsResponse { accounts })
 }
 
+impl TunnelRemote<TunnelCustomMsg> for Tunnel {
+    type Error = ContractError;
+
+    fn account(
+        &self,
+        deps: Deps,
+        connection_id: &str,
+        port_id: &str,
+        controller: &str,
+    ) -> Result<Option<Addr>, ContractError> {
+        Ok(ACCOUNTS.may_load(deps.storage, (connection_id, port_id, controller))?)
+    }
+
+    fn save_account(
+        &self,
+        deps: DepsMut,
+        sender: &SenderInfo,
+        account: &Addr,
+    ) -> Result<(), ContractError> {
+        let key = (
+            sender.connection_id.as_str(),
+            sender.port_id.as_str(),
+            sender.controller.as_str(),
+        );
+        Ok(ACCOUNTS.save(deps.storage, key, account)?)
+    }
+
+    fn save_pending(
+        &self,
+        storage: &mut dyn Storage,
+        sender: &SenderInfo,
+    ) -> Result<(), ContractError> {
+        Ok(PENDING.save(storage, sender)?)
+    }
+
+    fn take_pending(&self, storage: &mut dyn Storage) -> Result<SenderInfo, ContractError> {
+        let sender = PENDING.load(storage)?;
+        PENDING.remove(storage);
+        Ok(sender)
+    }
+
+    fn check_nonce(
+        &self,
+        deps: DepsMut,
+        source: &PacketSource,
+        controller: &str,
+        nonce: u64,
+    ) -> Result<(), ContractError> {
+        receive_nonce(
+            deps,
+            &source.connection_id,
+            &source.port_id,
+            controller,
+            nonce,
+        )
+    }
+
+    fn codec(&self, storage: &dyn Storage, channel_id: &str) -> Result<PacketCodec, ContractError> {
+        Ok(channel_codec(storage, channel_id)?)
+    }
+
+    /// Rejects packets meant for a role the tunnel is not built with,
+    /// forwarding sends packets on so it needs the host role as well
+    fn authorize(
+        &self,
+        _deps: Deps,
+        _source: &PacketSource,
+        packet: &PacketMsg<TunnelCustomMsg>,
+    ) -> Result<(), ContractError> {
+        ensure_role(packet.receiver_role())?;
+        if let PacketMsg::Forward { .. } = packet {
+            ensure_role(TunnelRole::Host)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the message against the policy of the proxy, or the global policy, and the spend limit of the proxy
+    fn check_dispatch(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        _sender: &SenderInfo,
+        account: &Addr,
+        msg: &CosmosMsg<TunnelCustomMsg>,
+    ) -> Result<(), ContractError> {
+        if let Some(policy) = msg_policy(deps.storage, Some(account))? {
+            policy.check(deps.api, msg)?;
+        }
+        check_spend_limit(deps, env, account, msg)
+    }
+
+    /// A migration could replace the proxy with code dispatching anything,
+    /// a proxy with a message policy or a spend limit is only migrated once governance removes them
+    fn check_migrate(
+        &self,
+        deps: Deps,
+        _sender: &SenderInfo,
+        account: &Addr,
+        _migrate_msg: &CosmosMsg<TunnelCustomMsg>,
+        _new_code_id: u64,
+    ) -> Result<(), ContractError> {
+        if msg_policy(deps.storage, Some(account))?.is_some() {
+            return Err(ContractError::PolicyViolation(
+                "the proxy cannot be migrated while a message policy is set".to_string(),
+            ));
+        }
+        // nor one with a spend limit, until governance removes the limit
+        if SPEND_LIMITS.has(deps.storage, account) {
+            return Err(ContractError::LimitedProxyMigration);
+        }
+        Ok(())
+    }
+
+    fn error_code(&self, err: &ContractError) -> TunnelErrorCode {
+        err.code()
+    }
+
+    fn receive_forward(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        source: PacketSource,
+        hops: Vec<String>,
+        visited: Vec<String>,
+        packet: PacketMsg<TunnelCustomMsg>,
+    ) -> Result<IbcReceiveResponse, ContractError> {
+        receive_forward(deps, env, source, hops, visited, packet)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn receive_forward_ack(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        source: PacketSource,
+        controller: String,
+        job_id: Option<String>,
+        nonce: u64,
+        ack: StdAck,
+    ) -> Result<IbcReceiveResponse, ContractError> {
+        receive_forward_ack(deps, env, source, controller, job_id, nonce, ack)
+    }
+
+    fn receive_chunk(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        source: PacketSource,
+        controller: String,
+        job_id: Option<String>,
+        chunk: PacketChunk,
+    ) -> Result<IbcReceiveResponse, ContractError> {
+        receive_chunk(deps, env, source, controller, job_id, chunk)
+    }
+}
+
+/// Handles the governance messages of `sudo`, on a remote tunnel
+pub fn sudo_governance(deps: DepsMut, msg: GovernanceMsg) -> Result<Response, ContractError> {
+    ensure_role(TunnelRole::Remote)?;
+    match msg {
+        GovernanceMsg::SetMsgPolicy { account, policy } => {
+            policy.validate(deps.api)?;
+            match &account {
+                Some(account) => {
+                    let account = deps.api.addr_validate(account)?;
+                    MSG_POLICIES.save(deps.storage, &account, &policy)?
+                }
+                None => GLOBAL_MSG_POLICY.save(deps.storage, &policy)?,
+            }
+            Ok(Response::new()
+                .add_event(MsgPolicySet { account }.into())
+                .add_attribute("action", "set_msg_policy"))
+        }
+        GovernanceMsg::SetSpendLimit { account, limit } => {
+            let account = deps.api.addr_validate(&account)?;
+            set_spend_limit(deps.storage, &account, &limit)
+        }
+        GovernanceMsg::RemoveSpendLimit { account } => {
+            let account = deps.api.addr_validate(&account)?;
+            SPEND_LIMITS.remove(deps.storage, &account);
+            SPENDS.remove(deps.storage, &account);
+            Ok(Response::new()
+                .add_event(
+                    SpendLimitRemoved {
+                        account: account.into(),
+                    }
+                    .into(),
+                )
+                .add_attribute("action", "remove_spend_limit"))
+        }
+        GovernanceMsg::RemoveMsgPolicy { account } => {
+            match &account {
+                Some(account) => {
+                    let account = deps.api.addr_validate(account)?;
+                    MSG_POLICIES.remove(deps.storage, &account)
+                }
+                None => GLOBAL_MSG_POLICY.remove(deps.storage),
+            }
+            Ok(Response::new()
+                .add_event(MsgPolicyRemoved { account }.into())
+                .add_attribute("action", "remove_msg_policy"))
+        }
+    }
+}
+
+/// The policy of the proxy `account`, falling back to the global policy
+fn msg_policy(storage: &dyn Storage, account: Option<&Addr>) -> StdResult<Option<MsgPolicy>> {
+    if let Some(account) = account {
+        if let Some(policy) = MSG_POLICIES.may_load(storage, account)? {
+            return Ok(Some(policy));
+        }
+    }
+    GLOBAL_MSG_POLICY.may_load(storage)
+}
+
+#[cfg_attr(not(feature = "host"), entry_point)]
+/// The spend limits the proxies set, exported in the builds without the `host` feature
+/// where the operations of the controllers are rejected
+pub fn execute(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg<TunnelCustomMsg>,
+) -> Result<Response, ContractError> {
+    ensure_role(TunnelRole::Remote)?;
+    nonpayable(&info)?;
+    match msg {
+        ExecuteMsg::SetSpendLimit { limit } => execute_set_spend_limit(deps, info, limit),
+        _ => Err(ContractError::WrongRole(TunnelRole::Host)),
+    }
+}
+
+/// Sets the spend limit of the sending proxy, which can only tighten the limit it has
+pub fn execute_set_spend_limit(
+    deps: DepsMut,
+    info: MessageInfo,
+    limit: SpendLimit,
+) -> Result<Response, ContractError> {
+    if let Some(current) = SPEND_LIMITS.may_load(deps.storage, &info.sender)? {
+        if !tightens(&limit, &current) {
+            return Err(ContractError::SpendLimitLoosened);
+        }
+    }
+    set_spend_limit(deps.storage, &info.sender, &limit)
+}
+
+/// Whether `limit` allows at most what `current` allows
+fn tightens(limit: &SpendLimit, current: &SpendLimit) -> bool {
+    limit.period >= current.period
+        && current.max.iter().all(|max| {
+            limit
+                .max
+                .iter()
+                .any(|coin| coin.denom == max.denom && coin.amount <= max.amount)
+        })
+}
+
+/// Sets the limit, what the proxy spent in the current period still counts against it
+fn set_spend_limit(
+    storage: &mut dyn Storage,
+    account: &Addr,
+    limit: &SpendLimit,
+) -> Result<Response, ContractError> {
+    SPEND_LIMITS.save(storage, account, limit)?;
+    Ok(Response::new()
+        .add_event(
+            SpendLimitSet {
+                account: account.into(),
+            }
+            .into(),
+        )
+        .add_attribute("action", "set_spend_limit"))
+}
+
+/// Checks the funds `msg` spends keep the proxy within its spend limit,
+/// they are recorded by `record_spend` once the dispatch succeeds
+fn check_spend_limit(
+    deps: DepsMut,
+    env: &Env,
+    account: &Addr,
+    msg: &CosmosMsg<TunnelCustomMsg>,
+) -> Result<(), ContractError> {
+    let storage = deps.storage;
+    let limit = match SPEND_LIMITS.may_load(storage, account)? {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+    // the limit only holds for messages whose spend is known
+    let kind = CosmosMsgKind::of(msg)?;
+    if !kind.has_known_spend() {
+        return Err(ContractError::UnknownSpend(kind));
+    }
+    // the proxy executing itself could spend funds the message does not carry
+    if let CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) = msg {
+        if deps.api.addr_canonicalize(contract_addr).ok()
+            == Some(deps.api.addr_canonicalize(account.as_str())?)
+        {
+            return Err(ContractError::ProxySelfExecute);
+        }
+    }
+    let spent = spent_in_period(storage, env, account, &limit)?;
+    let amount: Vec<Coin> = spent_funds(msg)?
+        .into_iter()
+        .filter(|coin| limit.max.iter().any(|max| max.denom == coin.denom))
+        .collect();
+    for max in &limit.max {
+        let total = amount_of(&spent, &max.denom).checked_add(amount_of(&amount, &max.denom));
+        if !matches!(total, Ok(total) if total <= max.amount) {
+            return Err(ContractError::ProxySpendLimitExceeded(max.denom.clone()));
+        }
+    }
+    if !amount.is_empty() {
+        let spend = Spend {
+            time: env.block.time,
+            amount,
+        };
+        PENDING_SPEND.save(storage, &(account.clone(), spend))?;
+    }
+    Ok(())
+}
+
+/// Records the spend of the dispatch in the period of the limit if its submessage succeeded
+fn record_spend(
+    storage: &mut dyn Storage,
+    env: &Env,
+    result: &SubMsgResult,
+) -> Result<(), ContractError> {
+    let (account, spend) = match PENDING_SPEND.may_load(storage)? {
+        Some(pending) => pending,
+        None => return Ok(()),
+    };
+    PENDING_SPEND.remove(storage);
+    if result.is_err() {
+        return Ok(());
+    }
+    let period = SPEND_LIMITS.load(storage, &account)?.period;
+    let mut spends = SPENDS.may_load(storage, &account)?.unwrap_or_default();
+    spends.retain(|spend| spend.time.plus_seconds(period) > env.block.time);
+    spends.push(spend);
+    SPENDS.save(storage, &account, &spends)?;
+    Ok(())
+}
+
+/// The total the proxy spent in the limited denoms over the last period of its limit
+fn spent_in_period(
+    storage: &dyn Storage,
+    env: &Env,
+    account: &Addr,
+    limit: &SpendLimit,
+) -> StdResult<Vec<Coin>> {
+    let spends = SPENDS.may_load(storage, account)?.unwrap_or_default();
+    Ok(limit
+        .max
+        .iter()
+        .map(|max| {
+            let amount = spends
+                .iter()
+                .filter(|spend| spend.time.plus_seconds(limit.period) > env.block.time)
+                .map(|spend| amount_of(&spend.amount, &max.denom))
+                .fold(Uint128::zero(), Uint128::saturating_add);
+            Coin::new(amount.u128(), &max.denom)
+        })
+        .filter(|coin| !coin.amount.is_zero())
+        .collect())
+}
+
+fn amount_of(coins: &[Coin], denom: &str) -> Uint128 {
+    coins
+        .iter()
+        .filter(|coin| coin.denom == denom)
+        .fold(Uint128::zero(), |total, coin| {
+            total.saturating_add(coin.amount)
+        })
+}
+
+pub fn query_spend_limit(deps: Deps, env: Env, account: String) -> StdResult<SpendLimitResponse> {
+    let account = deps.api.addr_validate(&account)?;
+    let limit = SPEND_LIMITS.may_load(deps.storage, &account)?;
+    let spent = match &limit {
+        Some(limit) => spent_in_period(deps.storage, &env, &account, limit)?,
+        None => vec![],
+    };
+    Ok(SpendLimitResponse { limit, spent })
+}
+
+pub fn query_msg_policy(deps: Deps, account: Option<String>) -> StdResult<MsgPolicyResponse> {
+    let account = account
+        .map(|account| deps.api.addr_validate(&account))
+        .transpose()?;
+    Ok(MsgPolicyResponse {
+        policy: msg_policy(deps.storage, account.as_ref())?,
+    })
+}
+
 #[entry_point]
 /// we look for a the proper reflect contract to relay to and send the message
 /// We cannot return any meaningful response value as we do not know the response value
@@ -78,256 +559,290 @@ pub fn ibc_packet_receive(
     env: Env,
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    (|| {
-        let packet = msg.packet;
-        let msg: PacketMsg = from_slice(&packet.data)?;
-        // The sender chain's light client id
-        let connection_id = get_connection_id_from_channel(deps.as_ref(), packet.dest)?;
-        // The sender's port id
-        let port_id = packet.src.port_id;
-
-        match msg {
-            PacketMsg::Instantiate {
-                controller,
-                inst_msg,
-                job_id,
-                code_id,
-            } => receieve_instantiate(
-                deps,
-                env,
-                connection_id,
-                port_id,
-                controller,
-                inst_msg,
-                job_id,
-                code_id,
-            ),
-            PacketMsg::Migrate {
-                controller,
-                migration_msg,
-                job_id,
-                new_code_id,
-            } => receieve_migrate(
-                deps,
-                connection_id,
-                port_id,
-                controller,
-                migration_msg,
-                job_id,
-                new_code_id,
-            ),
-            PacketMsg::Dispatch {
-                msg,
-                controller,
-                job_id,
-            } => receive_dispatch(deps, connection_id, port_id, controller, msg, job_id),
-            PacketMsg::WhoAmI { controller } => {
-                receive_who_am_i(deps, connection_id, port_id, controller)
-            }
-        }
-    })()
-    .or_else(|e| {
-        Ok(IbcReceiveResponse::new().set_ack(StdAck::fail(format!("IBC Packet Error: {e}"))))
-    })
+    Tunnel.receive(deps, env, msg)
 }
 
-#[allow(clippy::too_many_arguments)]
-fn receieve_instantiate(
-    deps: DepsMut,
+// processes PacketMsg::Forward variant
+fn receive_forward(
+    mut deps: DepsMut,
     env: Env,
-    connection_id: String,
-    port_id: String,
-    controller: String,
-    inst_msg: CosmosMsg,
-    job_id: Option<String>,
-    code_id: u64,
+    source: PacketSource,
+    hops: Vec<String>,
+    mut visited: Vec<String>,
+    packet: PacketMsg<TunnelCustomMsg>,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let msg = WasmMsg::Instantiate {
-        admin: Some(env.contract.address.to_string()),
-        code_id,
-        msg: to_binary(&inst_msg)?,
-        // TODO: allow user to deposit funds to this contract for this
-        funds: vec![],
-        label: format!("cosmwasm-ica-{connection_id}-{port_id}-{controller}"),
-    };
-    let msg = SubMsg::reply_on_success(msg, INIT_CALLBACK_ID);
+    if matches!(
+        packet,
+        PacketMsg::Forward { .. } | PacketMsg::ForwardAck { .. } | PacketMsg::Chunk { .. }
+    ) {
+        return Err(ContractError::InvalidForward);
+    }
+    if visited.contains(&env.block.chain_id) {
+        return Err(ContractError::ForwardLoop(env.block.chain_id));
+    }
+    // the origin chain is the first in `visited`
+    if visited.len() + hops.len() > MAX_FORWARD_HOPS + 1 {
+        return Err(ContractError::HopLimitExceeded(MAX_FORWARD_HOPS));
+    }
+    let (next_channel_id, hops) = hops.split_first().ok_or(ContractError::InvalidForward)?;
 
-    // store the relevant calling chain (host) data to be handled
-    PENDING.save(
-        deps.storage,
-        &SenderInfo {
-            connection_id,
-            port_id,
-            controller,
-            job_id,
+    // the proxy on the next chain is keyed by the origin of the packet
+    let job_id = packet.job_id().cloned();
+    let received_nonce = packet.nonce().ok_or(ContractError::InvalidForward)?;
+    let controller =
+        forwarded_controller(&source.connection_id, &source.port_id, packet.controller());
+    // the route of a job is kept until its ack is returned
+    if let Some(job_id) = &job_id {
+        if FORWARD_ROUTES.has(deps.storage, (&controller, job_id)) {
+            return Err(ContractError::ForwardPending(job_id.clone()));
+        }
+    }
+    let packet = packet.with_controller(controller.clone());
+    let packet = if hops.is_empty() {
+        packet
+    } else {
+        visited.push(env.block.chain_id.clone());
+        PacketMsg::Forward {
+            hops: hops.to_vec(),
+            visited,
+            packet: Box::new(packet),
+        }
+    };
+    let msgs = Tunnel.send_packet(
+        deps.branch(),
+        &env,
+        OutgoingPacket {
+            channel_id: next_channel_id.clone(),
+            packet,
+            timeout: None,
         },
     )?;
+    // the ack is only returned for a job, see `forward_ack_msg`
+    if let Some(job_id) = &job_id {
+        let route = ForwardRoute {
+            sent: ForwardHop {
+                channel_id: next_channel_id.clone(),
+                nonce: sent_nonce(deps.as_ref(), next_channel_id, &controller)?,
+            },
+            received: ForwardHop {
+                channel_id: source.channel_id.clone(),
+                nonce: received_nonce,
+            },
+        };
+        FORWARD_ROUTES.save(deps.storage, (&controller, job_id), &route)?;
+    }
 
     Ok(IbcReceiveResponse::new()
-        .add_submessage(msg)
-        .add_attribute("action", "recieve_instantiate"))
+        .set_ack(source.codec.success(&ForwardResponse { job_id })?)
+        .add_messages(msgs)
+        .add_attribute("action", "receive_forward"))
 }
 
-fn receieve_migrate(
-    deps: DepsMut,
-    connection_id: String,
-    port_id: String,
-    controller: String,
-    migrate_msg: CosmosMsg,
-    job_id: Option<String>,
-    new_code_id: u64,
-) -> Result<IbcReceiveResponse, ContractError> {
-    let account = ACCOUNTS.load(deps.storage, (&connection_id, &port_id, &controller))?;
-
-    let msg = to_binary(&migrate_msg)?;
-    let submsg = WasmMsg::Migrate {
-        contract_addr: account.to_string(),
-        new_code_id,
-        msg,
-    };
-    let msg = SubMsg::reply_always(submsg, MIGRATE_CALLBACK_ID);
-
-    PENDING.save(
-        deps.storage,
-        &SenderInfo {
-            connection_id,
-            port_id,
-            controller,
-            job_id,
-        },
-    )?;
+/// Drops the expired uploads of `origin` and their chunks
+fn remove_expired_uploads(
+    storage: &mut dyn Storage,
+    env: &Env,
+    origin: &str,
+) -> Result<(), ContractError> {
+    let expired = UPLOADS
+        .prefix(origin)
+        .range(storage, None, None, Order::Ascending)
+        .filter_map(|item| match item {
+            Ok((hash, upload)) if upload.expires <= env.block.time => Some(Ok(hash)),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    for hash in expired {
+        remove_upload(storage, origin, &hash)?;
+    }
+    Ok(())
+}
 
-    Ok(IbcReceiveResponse::new()
-        .add_submessage(msg)
-        .add_attribute("action", "recieve_migrate"))
+fn remove_upload(storage: &mut dyn Storage, origin: &str, hash: &[u8]) -> StdResult<()> {
+    let indexes = CHUNKS
+        .prefix((origin, hash))
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for index in indexes {
+        CHUNKS.remove(storage, (origin, hash, index));
+    }
+    UPLOADS.remove(storage, (origin, hash));
+    Ok(())
 }
 
-// processes PacketMsg::Dispatch variant
-fn receive_dispatch(
+// processes PacketMsg::Chunk variant, the packet is run once all of its chunks are received
+fn receive_chunk(
     deps: DepsMut,
-    connection_id: String,
-    port_id: String,
+    env: Env,
+    source: PacketSource,
     controller: String,
-    msg: CosmosMsg,
     job_id: Option<String>,
+    chunk: PacketChunk,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let account = ACCOUNTS.load(deps.storage, (&connection_id, &port_id, &controller))?;
-    let wasm_msg = wasm_execute(account, &msg, vec![])?;
+    if chunk.total == 0 || chunk.total > MAX_CHUNKS || chunk.index >= chunk.total {
+        return Err(ContractError::InvalidChunk);
+    }
+    let origin = forwarded_controller(&source.connection_id, &source.port_id, &controller);
+    let key = (origin.as_str(), chunk.hash.as_slice());
 
-    let msg = SubMsg::reply_always(wasm_msg, RECEIVE_DISPATCH_ID);
+    remove_expired_uploads(deps.storage, &env, &origin)?;
+    let mut upload = match UPLOADS.may_load(deps.storage, key)? {
+        Some(upload) => upload,
+        None => {
+            let uploads = UPLOADS
+                .prefix(&origin)
+                .keys(deps.storage, None, None, Order::Ascending)
+                .count();
+            if uploads >= MAX_UPLOADS as usize {
+                return Err(ContractError::TooManyUploads);
+            }
+            Upload {
+                total: chunk.total,
+                received: 0,
+                expires: env.block.time.plus_seconds(CHUNK_UPLOAD_LIFETIME),
+            }
+        }
+    };
+    if upload.total != chunk.total {
+        return Err(ContractError::InvalidChunk);
+    }
+    // a chunk received again, e.g. resent after a timeout, is only counted once
+    let chunk_key = (origin.as_str(), chunk.hash.as_slice(), chunk.index);
+    if !CHUNKS.has(deps.storage, chunk_key) {
+        CHUNKS.save(deps.storage, chunk_key, &chunk.data)?;
+        upload.received += 1;
+    }
 
-    PENDING.save(
-        deps.storage,
-        &SenderInfo {
-            connection_id,
-            port_id,
-            controller,
-            job_id,
-        },
-    )?;
+    if upload.received < upload.total {
+        UPLOADS.save(deps.storage, key, &upload)?;
+        return Ok(IbcReceiveResponse::new()
+            .set_ack(source.codec.success(&ChunkResponse {
+                received: upload.received,
+                total: upload.total,
+                job_id,
+            })?)
+            .add_attribute("action", "receive_chunk"));
+    }
 
-    Ok(IbcReceiveResponse::new()
-        .add_submessage(msg)
-        .add_attribute("action", "receive_dispatch"))
+    let data = CHUNKS
+        .prefix(key)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, data)| data.0))
+        .collect::<StdResult<Vec<_>>>()?
+        .concat();
+    remove_upload(deps.storage, key.0, key.1)?;
+    if chunk_hash(&data) != chunk.hash {
+        return Err(ContractError::ChunkHashMismatch);
+    }
+    let packet: PacketMsg<TunnelCustomMsg> = source.codec.decode_packet(&data)?;
+    if let PacketMsg::Chunk { .. } = packet {
+        return Err(ContractError::InvalidChunk);
+    }
+    if packet.controller() != controller {
+        return Err(ContractError::Unauthorized);
+    }
+    Tunnel.receive_decoded(deps, env, source, packet)
 }
 
-// processes PacketMsg::WhoAmI variant
-fn receive_who_am_i(
+// processes PacketMsg::ForwardAck variant
+fn receive_forward_ack(
     deps: DepsMut,
-    connection: String,
-    port: String,
+    env: Env,
+    source: PacketSource,
     controller: String,
+    job_id: Option<String>,
+    nonce: u64,
+    ack: StdAck,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let account = ACCOUNTS.load(deps.storage, (&connection, &port, &controller))?;
-    let response = WhoAmIResponse {
-        account: account.into(),
+    let res = IbcReceiveResponse::new()
+        .set_ack(source.codec.success(&ForwardResponse {
+            job_id: job_id.clone(),
+        })?)
+        .add_attribute("action", "receive_forward_ack");
+
+    // only the ack of a pending forward, returned on the channel it was sent on with its nonce, is accepted
+    let job = job_id.as_deref().ok_or(ContractError::Unauthorized)?;
+    let hop = ForwardHop {
+        channel_id: source.channel_id,
+        nonce,
     };
-    let acknowledgement = StdAck::success(&response);
-    Ok(IbcReceiveResponse::new()
-        .set_ack(acknowledgement)
-        .add_attribute("action", "receive_who_am_i"))
+    match split_forwarded_controller(&controller) {
+        Some(_) => Ok(res.add_message(forward_ack_msg(
+            deps.storage,
+            &env,
+            &controller,
+            job,
+            Some(&hop),
+            ack,
+        )?)),
+        None => {
+            if PENDING_FORWARDS.may_load(deps.storage, (&controller, job))? != Some(hop) {
+                return Err(ContractError::Unauthorized);
+            }
+            PENDING_FORWARDS.remove(deps.storage, (&controller, job));
+            Ok(res.add_submessages(callback_msg(&controller, job_id.as_ref(), ack)?))
+        }
+    }
 }
 
 #[entry_point]
-pub fn reply(deps: DepsMut, _env: Env, reply: Reply) -> Result<Response, ContractError> {
+pub fn reply(deps: DepsMut, env: Env, reply: Reply) -> Result<Response, ContractError> {
     match reply.id {
-        INIT_CALLBACK_ID => reply_init_callback(deps, reply),
-        RECEIVE_DISPATCH_ID => reply_dispatch_callback(deps, reply),
-        MIGRATE_CALLBACK_ID => reply_migrate_callback(deps, reply),
-        _ => Err(ContractError::InvalidReplyId),
+        ACK_CALLBACK_ID => reply_ack_callback(reply),
+        RECEIVE_DISPATCH_ID => {
+            record_spend(deps.storage, &env, &reply.result)?;
+            Tunnel.reply(deps, env, reply)
+        }
+        _ => Tunnel.reply(deps, env, reply),
     }
 }
 
-pub fn reply_init_callback(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
-    let SenderInfo {
-        connection_id,
-        port_id,
-        controller,
-        job_id,
-    } = PENDING.load(deps.storage)?;
-    PENDING.remove(deps.storage);
-
-    // parse contract address from reply data
-    let raw_addr = parse_reply_instantiate_data(reply)?.contract_address;
-    let new_contract_addr = deps.api.addr_validate(&raw_addr)?;
-
-    // Save this new account so it is callable in the future
-    if ACCOUNTS
-        .may_load(deps.storage, (&connection_id, &port_id, &controller))?
-        .is_some()
-    {
-        return Err(ContractError::ChannelAlreadyRegistered);
-    }
-    ACCOUNTS.save(
-        deps.storage,
-        (&connection_id, &port_id, &controller),
-        &new_contract_addr,
-    )?;
-    let event = Event::new("ica-tunnel.V1.MsgICAInstantiated").add_attributes(vec![
-        ("contract_addr", new_contract_addr.to_string()),
-        (
-            "Controller",
-            format!("{connection_id}-{port_id}-{controller}"),
-        ),
-    ]);
-
-    // Send Ack to the sending chain
-    let data = StdAck::success(&InstantiateResponse {
-        contract_address: new_contract_addr.to_string(),
-        job_id,
-    });
-
-    Ok(Response::new().set_data(data).add_event(event))
-}
-
-pub fn reply_dispatch_callback(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
-    let info = PENDING.load(deps.storage)?;
-    PENDING.remove(deps.storage);
-    let data = StdAck::success(&DispatchMigrateResponse {
-        result: reply.result,
-        job_id: info.job_id,
-    });
-    Ok(Response::new().set_data(data))
+/// Checks the nonce against replays and records it
+fn receive_nonce(
+    deps: DepsMut,
+    connection_id: &str,
+    port_id: &str,
+    controller: &str,
+    nonce: u64,
+) -> Result<(), ContractError> {
+    let window = NONCE_WINDOW
+        .may_load(deps.storage)?
+        .unwrap_or(MAX_NONCE_WINDOW);
+    let key = (connection_id, port_id, controller);
+    let mut received = RECEIVED_NONCES
+        .may_load(deps.storage, key)?
+        .unwrap_or_default();
+    received.receive(nonce, window)?;
+    RECEIVED_NONCES.save(deps.storage, key, &received)?;
+    Ok(())
 }
 
-pub fn reply_migrate_callback(deps: DepsMut, reply: Reply) -> Result<Response, ContractError> {
-    let info = PENDING.load(deps.storage)?;
-    let data = StdAck::success(&DispatchMigrateResponse {
-        result: reply.result,
-        job_id: info.job_id,
-    });
-    Ok(Response::new().set_data(data))
+pub fn query_received_nonce(
+    deps: Deps,
+    connection_id: String,
+    port_id: String,
+    controller: String,
+) -> StdResult<NonceResponse> {
+    let received = RECEIVED_NONCES
+        .may_load(deps.storage, (&connection_id, &port_id, &controller))?
+        .unwrap_or_default();
+    Ok(NonceResponse {
+        nonce: received.highest,
+    })
 }
 
-fn get_connection_id_from_channel(
+/// The connection of the channel on this chain, `port_id` defaults to this contract's port
+pub fn get_connection_id_from_channel(
     deps: Deps,
-    my_endpoint: IbcEndpoint,
+    channel_id: String,
+    port_id: Option<String>,
 ) -> Result<String, ContractError> {
     use cosmwasm_std::ChannelResponse;
     let channel_resp: ChannelResponse =
         deps.querier.query(&QueryRequest::Ibc(IbcQuery::Channel {
-            channel_id: my_endpoint.channel_id,
-            port_id: Some(my_endpoint.port_id),
+            channel_id,
+            port_id,
         }))?;
 
     Ok(channel_resp
@@ -1,27 +1,153 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Binary, Coin, Timestamp};
+use cosmwasm_tunnel::{
+    ChannelMetadata, MsgPolicy, OperatorGrant, PacketEncoding, ProtocolVersion, SenderInfo,
+    SessionKey, SigningKey, SpendLimit,
+};
+
+use crate::error::ContractError;
 use cw_storage_plus::{Item, Map};
 
 #[cw_serde]
-pub struct SenderInfo {
-    /// Sender's light client id on this chain
-    pub connection_id: String,
-    /// Sender's module / cw contract id
-    pub port_id: String,
-    /// Sender's account on the remote chain
-    pub controller: String,
-    /// Job id for ref on the sending chain
+pub struct IcaAccount {
+    /// The controller on this chain that registered the interchain account
+    pub controller: Addr,
+    /// The owner of the interchain account on the ICA controller module, see `ica_owner`
+    pub owner: String,
+    /// The ICS-27 channel, set when the channel is open
+    pub channel_id: Option<String>,
+    /// The interchain account on the remote chain, set when the channel is open
+    pub address: Option<String>,
+    /// Job id of the registration, for the callback when the channel is open
     pub job_id: Option<String>,
 }
 
+/// The nonces received from a controller, the highest and a window of the ones below it
+#[cw_serde]
+#[derive(Default)]
+pub struct ReceivedNonces {
+    pub highest: u64,
+    /// Bit `i` is set if nonce `highest - i` has been received
+    pub received: u64,
+}
+
+/// The funds a proxy spent in the limited denoms of its `SpendLimit`, kept for the period of the limit
+#[cw_serde]
+pub struct Spend {
+    pub time: Timestamp,
+    pub amount: Vec<Coin>,
+}
+
+/// A forwarded packet sent by this tunnel, a `ForwardAck` of the packet is only accepted
+/// from the channel it was sent on and with the nonce it was sent with
+#[cw_serde]
+pub struct ForwardHop {
+    pub channel_id: String,
+    pub nonce: u64,
+}
+
+/// A forwarded packet sent on by this (intermediate) tunnel, see `FORWARD_ROUTES`
+#[cw_serde]
+pub struct ForwardRoute {
+    /// Where the packet was sent on
+    pub sent: ForwardHop,
+    /// Where the packet was received from, its ack is returned on this channel with this nonce
+    pub received: ForwardHop,
+}
+
+/// A chunked packet being received, see `PacketMsg::Chunk`
+#[cw_serde]
+pub struct Upload {
+    pub total: u32,
+    /// The number of distinct chunks received
+    pub received: u32,
+    /// Incomplete uploads are dropped after this time
+    pub expires: Timestamp,
+}
+
+impl ReceivedNonces {
+    /// Marks `nonce` as received, fails if it already has been or is outside of the window
+    pub fn receive(&mut self, nonce: u64, window: u64) -> Result<(), ContractError> {
+        if nonce == 0 {
+            return Err(ContractError::InvalidNonce(nonce));
+        }
+        if nonce > self.highest {
+            let shift = nonce - self.highest;
+            self.received = if shift >= u64::BITS as u64 {
+                0
+            } else {
+                self.received << shift
+            };
+            self.received |= 1;
+            self.highest = nonce;
+            return Ok(());
+        }
+        let offset = self.highest - nonce;
+        if offset >= window || self.received & (1 << offset) != 0 {
+            return Err(ContractError::InvalidNonce(nonce));
+        }
+        self.received |= 1 << offset;
+        Ok(())
+    }
+}
+
 // Pending storage data structure for any operations, removed on success, replace by new call on failure
 // Mainly used to pass IBC information to be handled in `reply`
 pub const PENDING: Item<SenderInfo> = Item::new("pending");
 // We map the ibc endpoint (trusted) and the relayed sender (untrusted) here
 pub const ACCOUNTS: Map<(&str, &str, &str), Addr> = Map::new("accounts");
+// The native interchain accounts by (controller, connection). The ICA controller module derives the port
+// from the owner, so each controller registers with an owner of its own and has its own account
+pub const ICA_ACCOUNTS: Map<(&Addr, &str), IcaAccount> = Map::new("controller_ica_accounts");
+// The controller of the interchain account registered on (port, connection), to find it on `OpenAck`
+pub const ICA_OWNERS: Map<(&str, &str), Addr> = Map::new("ica_owners");
+// ICS-27 channel id to the (controller, connection) of its interchain account
+pub const ICA_CHANNELS: Map<&str, (Addr, String)> = Map::new("controller_ica_channels");
+// The forwarded packets awaiting their ack on an intermediate chain, by (controller, job_id) of the packet
+// sent on, the controller as `forwarded_controller`. Removed once the ack is returned
+pub const FORWARD_ROUTES: Map<(&str, &str), ForwardRoute> = Map::new("forward_routes");
+// The last nonce sent by this (host) tunnel, by (connection, controller)
+pub const SENT_NONCES: Map<(&str, &str), u64> = Map::new("sent_nonces");
+// The nonces received by this (remote) tunnel, by (connection, port, controller) as `ACCOUNTS`
+pub const RECEIVED_NONCES: Map<(&str, &str, &str), ReceivedNonces> = Map::new("received_nonces");
+// How far below the highest received nonce out of order packets are accepted
+pub const NONCE_WINDOW: Item<u64> = Item::new("nonce_window");
+// Operators allowed to send operations on behalf of a controller, by (controller, operator)
+pub const OPERATORS: Map<(&Addr, &Addr), OperatorGrant> = Map::new("operators");
+// The key each controller signs `SubmitSigned` operations with
+pub const SIGNING_KEYS: Map<&Addr, SigningKey> = Map::new("signing_keys");
+// The session keys of each controller, by (controller, name)
+pub const SESSION_KEYS: Map<(&Addr, &str), SessionKey> = Map::new("session_keys");
+// The funds spent by each session, reset when the session is registered again
+pub const SESSION_SPENT: Map<(&Addr, &str), Vec<Coin>> = Map::new("session_spent");
+// The nonce of the last operation signed with the signing key of each controller
+pub const SIGNED_NONCES: Map<&Addr, u64> = Map::new("signed_nonces");
+// The nonce of the last operation signed with each session key, by (controller, name).
+// It is kept when the session is revoked, so its operations cannot be replayed if the name is registered again
+pub const SESSION_NONCES: Map<(&Addr, &str), u64> = Map::new("session_nonces");
+// The protocol version negotiated in the channel handshake, by channel id on this chain.
+// Channels opened before versions were negotiated are not stored, they are `cw-tunnel-v1` channels
+pub const CHANNEL_VERSIONS: Map<&str, ProtocolVersion> = Map::new("channel_versions");
+// The metadata the counterparty tunnel sent in the channel handshake, by channel id on this chain
+pub const CHANNEL_METADATA: Map<&str, ChannelMetadata> = Map::new("channel_metadata");
+// The packet encoding this tunnel proposed when opening a channel, by channel id, until the counterparty answers
+pub const PROPOSED_ENCODINGS: Map<&str, PacketEncoding> = Map::new("proposed_encodings");
+// Forwarded jobs awaiting their ack on the origin chain, (controller, job_id) to the first hop
+pub const PENDING_FORWARDS: Map<(&str, &str), ForwardHop> = Map::new("pending_forwards");
+// Chunked packets being received, by (origin controller, packet hash), the origin as `forwarded_controller`
+pub const UPLOADS: Map<(&str, &[u8]), Upload> = Map::new("uploads");
+// The chunks of the uploads, by (origin controller, packet hash, index)
+pub const CHUNKS: Map<(&str, &[u8], u32), Binary> = Map::new("chunks");
+// The message policy set by governance for the proxies without their own, on this (remote) tunnel
+pub const GLOBAL_MSG_POLICY: Item<MsgPolicy> = Item::new("global_msg_policy");
+// The message policies set by governance for single proxies, by proxy address
+pub const MSG_POLICIES: Map<&Addr, MsgPolicy> = Map::new("msg_policies");
+// The spend limits of the proxies, by proxy address
+pub const SPEND_LIMITS: Map<&Addr, SpendLimit> = Map::new("spend_limits");
+// The spends of each proxy within the period of its spend limit, oldest first
+pub const SPENDS: Map<&Addr, Vec<Spend>> = Map::new("spends");
+// The spend of the dispatch whose submessage is running, recorded in `reply` if it succeeds
+pub const PENDING_SPEND: Item<(Addr, Spend)> = Item::new("pending_spend");
 
-pub const RECEIVE_DISPATCH_ID: u64 = 1234;
-pub const INIT_CALLBACK_ID: u64 = 7890;
-pub const MIGRATE_CALLBACK_ID: u64 = 7899;
 pub const DEFAULT_LIMIT: u64 = 50;
 pub const MAX_LIMIT: u64 = 300;
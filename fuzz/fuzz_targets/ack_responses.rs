@@ -0,0 +1,24 @@
+//! The result data of a `StdAck` is decoded into the response of the sent packet, never panics
+#![no_main]
+use cosmwasm_std::Binary;
+use cosmwasm_tunnel::{
+    DispatchMigrateResponse, ForwardResponse, InstantiateResponse, StdAck, TunnelAck,
+    WhoAmIResponse,
+};
+use libfuzzer_sys::fuzz_target;
+use tunnel_fuzz::{acknowledge, sent_packets};
+use tunnel_testing::TestEnv;
+
+fuzz_target!(|data: &[u8]| {
+    let ack = StdAck::Result(Binary::from(data));
+    let _ = ack.try_decode::<InstantiateResponse>();
+    let _ = ack.try_decode::<DispatchMigrateResponse>();
+    let _ = ack.try_decode::<WhoAmIResponse>();
+    let _ = ack.try_decode::<ForwardResponse>();
+
+    let mut env = TestEnv::new();
+    for sent in sent_packets() {
+        let _ = TunnelAck::from_std_ack(&sent, &ack);
+        acknowledge(&mut env, &sent, &ack.ack());
+    }
+});
@@ -0,0 +1,22 @@
+//! Packet data received from the counterparty is decoded and acknowledged, never panics
+#![no_main]
+use cosmwasm_std::{from_slice, to_vec};
+use cosmwasm_tunnel::{PacketMsg, TunnelErrorCode};
+use libfuzzer_sys::fuzz_target;
+use tunnel_fuzz::{error_code, receive};
+use tunnel_testing::TestEnv;
+
+fuzz_target!(|data: &[u8]| {
+    let decoded = PacketMsg::<cosmwasm_std::Empty>::decode(data);
+    if let Ok(packet) = &decoded {
+        let encoded = to_vec(packet).expect("decoded packet serializes");
+        let again: PacketMsg = from_slice(&encoded).expect("encoded packet decodes");
+        assert_eq!(&again, packet);
+    }
+
+    let mut env = TestEnv::new();
+    let res = receive(&mut env, data);
+    if decoded.is_err() {
+        assert_eq!(error_code(&res.acknowledgement), TunnelErrorCode::InvalidPacket);
+    }
+});
@@ -0,0 +1,29 @@
+//! Packet data received on a proto channel is decoded and acknowledged, never panics
+#![no_main]
+use cosmwasm_std::Empty;
+use cosmwasm_tunnel::{ErrorResponse, PacketEncoding, PacketMsg, StdAck, TunnelErrorCode};
+use libfuzzer_sys::fuzz_target;
+use tunnel_fuzz::receive;
+use tunnel_testing::TestEnv;
+
+fuzz_target!(|data: &[u8]| {
+    let proto = PacketEncoding::Proto;
+    let decoded = proto.decode_packet::<Empty>(data);
+    if let Ok(packet) = &decoded {
+        let encoded = proto.encode_packet(packet).expect("decoded packet encodes");
+        let again: PacketMsg = proto.decode_packet(&encoded).expect("encoded packet decodes");
+        assert_eq!(&again, packet);
+    }
+
+    let mut env = TestEnv::with_encoding(proto);
+    let res = receive(&mut env, data);
+    if decoded.is_err() {
+        match proto.decode_ack(&res.acknowledgement).expect("the ack is proto") {
+            StdAck::Error(err) => {
+                let code = ErrorResponse::from_ack_error(&err).code;
+                assert_eq!(code, TunnelErrorCode::InvalidPacket);
+            }
+            StdAck::Result(_) => panic!("expected an error ack"),
+        }
+    }
+});
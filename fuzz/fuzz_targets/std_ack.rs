@@ -0,0 +1,19 @@
+//! Acknowledgements from the counterparty are decoded and delivered to the controller, never panic
+#![no_main]
+use cosmwasm_std::{from_slice, Binary};
+use cosmwasm_tunnel::{ErrorResponse, StdAck, TunnelAck};
+use libfuzzer_sys::fuzz_target;
+use tunnel_fuzz::{acknowledge, sent_packets};
+use tunnel_testing::TestEnv;
+
+fuzz_target!(|ack: &[u8]| {
+    if let Ok(StdAck::Error(err)) = from_slice::<StdAck>(ack) {
+        ErrorResponse::from_ack_error(&err);
+    }
+
+    let mut env = TestEnv::new();
+    for sent in sent_packets() {
+        let _ = TunnelAck::decode(&sent, &Binary::from(ack));
+        acknowledge(&mut env, &sent, ack);
+    }
+});
@@ -0,0 +1,99 @@
+//! Shared setup of the fuzz targets, the packets are delivered to the tunnels of a `TestEnv`
+use cosmwasm_std::{
+    from_slice, Addr, Binary, IbcAcknowledgement, IbcBasicResponse, IbcEndpoint, IbcPacket,
+    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcReceiveResponse, IbcTimeout,
+};
+use cosmwasm_tunnel::{
+    split_packet, ErrorResponse, PacketMsg, StdAck, TunnelErrorCode, PACKET_LIFETIME,
+};
+use tunnel_testing::{ibc_port, TestEnv, HOST_CHANNEL_ID, REMOTE_CHANNEL_ID};
+
+/// A packet from the host tunnel to the remote tunnel carrying `data`
+pub fn packet(env: &TestEnv, data: &[u8]) -> IbcPacket {
+    IbcPacket::new(
+        data,
+        IbcEndpoint {
+            port_id: ibc_port(&env.host.tunnel()),
+            channel_id: HOST_CHANNEL_ID.to_string(),
+        },
+        IbcEndpoint {
+            port_id: ibc_port(&env.remote.tunnel()),
+            channel_id: REMOTE_CHANNEL_ID.to_string(),
+        },
+        1,
+        IbcTimeout::with_timestamp(env.remote.block.time.plus_seconds(PACKET_LIFETIME)),
+    )
+}
+
+/// Delivers `data` to the remote tunnel, which must acknowledge rather than fail
+pub fn receive(env: &mut TestEnv, data: &[u8]) -> IbcReceiveResponse {
+    let msg = IbcPacketReceiveMsg::new(packet(env, data), Addr::unchecked("relayer"));
+    env.remote
+        .with_tunnel(|deps, env| ic_tunnel::remote::ibc_packet_receive(deps, env, msg))
+        .expect("ibc_packet_receive acknowledges")
+}
+
+/// Acknowledges the host tunnel's `sent` packet with `ack`, which it must accept
+pub fn acknowledge(env: &mut TestEnv, sent: &PacketMsg, ack: &[u8]) -> IbcBasicResponse {
+    let data = cosmwasm_std::to_vec(sent).expect("packet serializes");
+    let msg = IbcPacketAckMsg::new(
+        IbcAcknowledgement::new(Binary::from(ack)),
+        packet(env, &data),
+        Addr::unchecked("relayer"),
+    );
+    env.host
+        .with_tunnel(|deps, env| ic_tunnel::host::ibc_packet_ack(deps, env, msg))
+        .expect("ibc_packet_ack accepts any ack")
+}
+
+/// The code of an error ack, panics on a result
+pub fn error_code(ack: &[u8]) -> TunnelErrorCode {
+    match from_slice(ack).expect("the ack is a StdAck") {
+        StdAck::Error(err) => ErrorResponse::from_ack_error(&err).code,
+        StdAck::Result(_) => panic!("expected an error ack"),
+    }
+}
+
+/// One packet of each kind, as sent by the host tunnel
+pub fn sent_packets() -> Vec<PacketMsg> {
+    let controller = "controller".to_string();
+    let job_id = Some("job".to_string());
+    let msg = cosmwasm_std::BankMsg::Burn { amount: vec![] }.into();
+    let dispatch = PacketMsg::Dispatch {
+        controller: controller.clone(),
+        msg,
+        job_id: job_id.clone(),
+        fail_on_error: false,
+        nonce: 1,
+    };
+    let data = cosmwasm_std::to_vec(&dispatch).expect("packet serializes");
+    let chunk = split_packet(&dispatch, &data, data.len() / 2).remove(0);
+    vec![
+        PacketMsg::Instantiate {
+            controller: controller.clone(),
+            inst_msg: cosmwasm_std::BankMsg::Burn { amount: vec![] }.into(),
+            job_id: job_id.clone(),
+            code_id: 1,
+            nonce: 1,
+        },
+        PacketMsg::Migrate {
+            controller: controller.clone(),
+            migration_msg: cosmwasm_std::BankMsg::Burn { amount: vec![] }.into(),
+            new_code_id: 1,
+            job_id,
+            fail_on_error: true,
+            nonce: 1,
+        },
+        PacketMsg::WhoAmI {
+            controller,
+            nonce: 1,
+        },
+        PacketMsg::Forward {
+            hops: vec!["channel-0".to_string()],
+            visited: vec![],
+            packet: Box::new(dispatch.clone()),
+        },
+        chunk,
+        dispatch,
+    ]
+}
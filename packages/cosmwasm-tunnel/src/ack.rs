@@ -0,0 +1,69 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_slice, Binary, StdError};
+
+use thiserror::Error;
+
+use crate::{
+    ChunkResponse, DispatchMigrateResponse, ErrorResponse, ForwardResponse, InstantiateResponse,
+    PacketMsg, StdAck, TunnelOperation, WhoAmIResponse,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AckError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("Remote error {:?}: {}", .0.code, .0.message)]
+    Remote(ErrorResponse),
+}
+
+/// The decoded acknowledgement of a `PacketMsg`, each variant is the response to the packet of the same kind
+#[cw_serde]
+pub enum TunnelAck {
+    Instantiate(InstantiateResponse),
+    Migrate(DispatchMigrateResponse),
+    Dispatch(DispatchMigrateResponse),
+    WhoAmI(WhoAmIResponse),
+    Forward(ForwardResponse),
+    /// A chunk was received, its packet is not complete yet
+    Chunk(ChunkResponse),
+    Error(ErrorResponse),
+}
+
+impl TunnelAck {
+    /// Decodes the raw acknowledgement of the sent `packet`
+    pub fn decode<T>(packet: &PacketMsg<T>, ack: &Binary) -> Result<TunnelAck, StdError> {
+        let ack: StdAck = from_slice(ack)?;
+        TunnelAck::from_std_ack(packet, &ack)
+    }
+
+    /// Decodes the result data of `ack` into the response type of the sent `packet`
+    pub fn from_std_ack<T>(packet: &PacketMsg<T>, ack: &StdAck) -> Result<TunnelAck, StdError> {
+        let operation = match packet {
+            PacketMsg::Instantiate { .. } => TunnelOperation::Instantiate,
+            PacketMsg::Migrate { .. } => TunnelOperation::Migrate,
+            PacketMsg::Dispatch { .. } => TunnelOperation::Dispatch,
+            PacketMsg::WhoAmI { .. } => TunnelOperation::WhoAmI,
+            // a forward ack is acknowledged as the forward it returns along
+            PacketMsg::Forward { .. } | PacketMsg::ForwardAck { .. } => TunnelOperation::Forward,
+            // the part completing the packet is acknowledged with the response to the packet
+            PacketMsg::Chunk { operation, .. } => match ack.try_decode() {
+                Ok(res) => return Ok(TunnelAck::Chunk(res)),
+                Err(_) => *operation,
+            },
+        };
+        let decoded = match operation {
+            TunnelOperation::Instantiate => ack.try_decode().map(TunnelAck::Instantiate),
+            TunnelOperation::Migrate => ack.try_decode().map(TunnelAck::Migrate),
+            TunnelOperation::Dispatch => ack.try_decode().map(TunnelAck::Dispatch),
+            TunnelOperation::WhoAmI => ack.try_decode().map(TunnelAck::WhoAmI),
+            TunnelOperation::Forward => ack.try_decode().map(TunnelAck::Forward),
+            TunnelOperation::Chunk => ack.try_decode().map(TunnelAck::Chunk),
+        };
+        match decoded {
+            Ok(ack) => Ok(ack),
+            Err(AckError::Remote(err)) => Ok(TunnelAck::Error(err)),
+            Err(AckError::Std(err)) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! The custom messages (`CosmosMsg::Custom`) of chain bindings the tunnel can be built for.
+//! The tunnel of a chain is built with the cargo feature of its binding (see `TunnelCustomMsg` in `ic-tunnel`),
+//! and the controllers send `ExecuteMsg<T>` with the same type.
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{CustomMsg, Uint128};
+
+/// The token factory module of Juno and Osmosis based chains, as in `token-bindings`.
+/// Built with the `token-factory` feature of `ic-tunnel`
+#[cw_serde]
+pub enum TokenFactoryMsg {
+    Token(TokenMsg),
+}
+
+impl CustomMsg for TokenFactoryMsg {}
+
+#[cw_serde]
+pub enum TokenMsg {
+    /// Creates `factory/{proxy}/{subdenom}`, administered by the proxy
+    CreateDenom { subdenom: String },
+    ChangeAdmin {
+        denom: String,
+        new_admin_address: String,
+    },
+    MintTokens {
+        denom: String,
+        amount: Uint128,
+        mint_to_address: String,
+    },
+    BurnTokens {
+        denom: String,
+        amount: Uint128,
+        burn_from_address: String,
+    },
+}
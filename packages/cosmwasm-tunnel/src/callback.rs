@@ -2,7 +2,7 @@ use cosmwasm_schema::{cw_serde, schemars};
 
 use cosmwasm_std::{to_binary, Binary, CosmosMsg, StdResult, WasmMsg};
 
-use crate::StdAck;
+use crate::{ErrorResponse, StdAck, TunnelErrorCode};
 
 /// ReceiveIbcResponseMsg should be de/serialized under `Receive()` variant in a ExecuteMsg
 #[cw_serde]
@@ -10,9 +10,23 @@ pub struct ReceiveIcaResponseMsg {
     /// The ID chosen by the caller in the `job_id`
     pub id: String,
     pub msg: StdAck,
+    /// The reason the operation failed, if `msg` is `StdAck::Error`
+    pub error_code: Option<TunnelErrorCode>,
 }
 
 impl ReceiveIcaResponseMsg {
+    pub fn new(id: String, msg: StdAck) -> Self {
+        let error_code = match &msg {
+            StdAck::Result(_) => None,
+            StdAck::Error(err) => Some(ErrorResponse::from_ack_error(err).code),
+        };
+        ReceiveIcaResponseMsg {
+            id,
+            msg,
+            error_code,
+        }
+    }
+
     /// serializes the message
     pub fn into_binary(self) -> StdResult<Binary> {
         let msg = SimpleIcaReceiverExecuteMsg::ReceiveIcaResponse(self);
@@ -40,3 +54,18 @@ impl ReceiveIcaResponseMsg {
 enum SimpleIcaReceiverExecuteMsg {
     ReceiveIcaResponse(ReceiveIcaResponseMsg),
 }
+
+/// The callback of the tunnel as an execute message of the controller.
+/// Include the variant, with its alias, in the controller's ExecuteMsg enum
+/// or deserialize the message with this enum, e.g. from an untagged wrapper.
+#[cw_serde]
+pub enum TunnelReceiverMsg {
+    #[serde(alias = "receive_ica_response")]
+    ReceiveTunnelResponse(ReceiveIcaResponseMsg),
+}
+
+impl From<ReceiveIcaResponseMsg> for TunnelReceiverMsg {
+    fn from(msg: ReceiveIcaResponseMsg) -> Self {
+        TunnelReceiverMsg::ReceiveTunnelResponse(msg)
+    }
+}
@@ -1,4 +1,4 @@
-pub use crate::{APP_ORDER, IBC_APP_VERSION};
+use crate::APP_ORDER;
 use cosmwasm_std::IbcOrder;
 
 use thiserror::Error;
@@ -8,7 +8,7 @@ pub enum ChannelError {
     #[error("Only supports unordered channels")]
     InvalidChannelOrder,
 
-    #[error("Counterparty version must be '{0}'")]
+    #[error("Counterparty version must be '{0}' channel metadata or 'cw-tunnel-v1'")]
     InvalidChannelVersion(&'static str),
 }
 
@@ -19,11 +19,3 @@ pub fn check_order(order: &IbcOrder) -> Result<(), ChannelError> {
         Ok(())
     }
 }
-
-pub fn check_version(version: &str) -> Result<(), ChannelError> {
-    if version != IBC_APP_VERSION {
-        Err(ChannelError::InvalidChannelVersion(IBC_APP_VERSION))
-    } else {
-        Ok(())
-    }
-}
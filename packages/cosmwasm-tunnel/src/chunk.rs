@@ -0,0 +1,63 @@
+use cosmwasm_schema::serde::Serialize;
+use cosmwasm_std::{Binary, StdResult};
+use sha2::{Digest, Sha256};
+
+use crate::{PacketChunk, PacketCodec, PacketEncoding, PacketMsg, TunnelOperation, MAX_CHUNKS};
+
+/// What the data of a protobuf chunk adds besides itself: its tag and length, and the longer length
+/// of the chunk message around it. The lengths of chunks under 2 MiB take at most 3 bytes
+const PROTO_DATA_OVERHEAD: usize = 6;
+
+/// The sha256 hash of encoded packet data, identifies its chunks
+pub fn chunk_hash(data: &[u8]) -> Binary {
+    Sha256::digest(data).to_vec().into()
+}
+
+/// The size of the parts of the encoded `packet`, so that each encoded `Chunk` is at most `max_size`:
+/// the space the rest of the chunk leaves for its data, which grows by a third in base64 in JSON
+pub fn chunk_size<T: Serialize>(
+    codec: PacketCodec,
+    packet: &PacketMsg<T>,
+    max_size: usize,
+) -> StdResult<usize> {
+    // the index and total of the last chunk are the longest
+    let empty = chunk_of(packet, chunk_hash(&[]), MAX_CHUNKS, MAX_CHUNKS, &[]);
+    let available = max_size.saturating_sub(codec.encode_packet(&empty)?.len());
+    let size = match codec.encoding {
+        PacketEncoding::Json => available / 4 * 3,
+        PacketEncoding::Proto => available.saturating_sub(PROTO_DATA_OVERHEAD),
+    };
+    // a packet too large to chunk at all needs more than `MAX_CHUNKS` chunks of a byte
+    Ok(size.max(1))
+}
+
+/// Splits the encoded `packet` into chunks of `chunk_size` bytes, see `chunk_size`
+pub fn split_packet<T>(packet: &PacketMsg<T>, data: &[u8], chunk_size: usize) -> Vec<PacketMsg<T>> {
+    let hash = chunk_hash(data);
+    let total = data.len().div_ceil(chunk_size) as u32;
+    data.chunks(chunk_size)
+        .enumerate()
+        .map(|(index, part)| chunk_of(packet, hash.clone(), index as u32, total, part))
+        .collect()
+}
+
+fn chunk_of<T>(
+    packet: &PacketMsg<T>,
+    hash: Binary,
+    index: u32,
+    total: u32,
+    data: &[u8],
+) -> PacketMsg<T> {
+    PacketMsg::Chunk {
+        controller: packet.controller().to_string(),
+        job_id: packet.job_id().cloned(),
+        // forward acks are not sent with `send_packet`, so they are not split
+        operation: packet.operation().unwrap_or(TunnelOperation::Forward),
+        chunk: PacketChunk {
+            hash,
+            index,
+            total,
+            data: data.to_vec().into(),
+        },
+    }
+}
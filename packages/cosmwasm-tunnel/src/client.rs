@@ -0,0 +1,271 @@
+use cosmwasm_schema::{cw_serde, schemars, serde};
+use cosmwasm_std::{
+    to_binary, Addr, Binary, CosmosMsg, Empty, ReplyOn, StdResult, SubMsg, WasmMsg,
+};
+
+use crate::{ExecuteMsg, OperatorGrant, SessionKey, SigningKey, SpendLimit};
+
+/// A tunnel contract, with the operations a controller contract sends to it.
+/// Each operation returns a `TunnelCall` to set the optional parameters on.
+#[cw_serde]
+pub struct TunnelContract(pub Addr);
+
+impl TunnelContract {
+    pub fn new(addr: Addr) -> Self {
+        TunnelContract(addr)
+    }
+
+    pub fn addr(&self) -> Addr {
+        self.0.clone()
+    }
+
+    /// Instantiates a proxy with `inst_msg` (a `WasmMsg::Instantiate`) on the remote chain of `channel_id`
+    pub fn instantiate<T>(
+        &self,
+        channel_id: impl Into<String>,
+        code_id: u64,
+        inst_msg: CosmosMsg<T>,
+    ) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RemoteInstantiate {
+            inst_msg,
+            job_id: None,
+            code_id,
+            channel_id: channel_id.into(),
+            timeout: None,
+        })
+    }
+
+    /// Migrates the proxy on the remote chain of `channel_id` with `migrate_msg` (a `WasmMsg::Migrate`)
+    pub fn migrate<T>(
+        &self,
+        channel_id: impl Into<String>,
+        new_code_id: u64,
+        migrate_msg: CosmosMsg<T>,
+    ) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RemoteMigrate {
+            migrate_msg,
+            job_id: None,
+            new_code_id,
+            channel_id: channel_id.into(),
+            fail_on_error: false,
+            timeout: None,
+        })
+    }
+
+    /// Dispatches `msg` through the proxy (or interchain account) on the remote chain of `channel_id`
+    pub fn dispatch<T>(&self, channel_id: impl Into<String>, msg: CosmosMsg<T>) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RemoteDispatch {
+            dispatch_msg: msg,
+            job_id: None,
+            channel_id: channel_id.into(),
+            fail_on_error: false,
+            timeout: None,
+        })
+    }
+
+    /// Asks for the address of the proxy on the remote chain of `channel_id`
+    pub fn query_remote_addr<T>(&self, channel_id: impl Into<String>) -> TunnelCall<T> {
+        self.call(ExecuteMsg::QueryRemoteAddr {
+            channel_id: channel_id.into(),
+            timeout: None,
+        })
+    }
+
+    pub fn register_interchain_account<T>(
+        &self,
+        connection_id: impl Into<String>,
+        counterparty_connection_id: impl Into<String>,
+    ) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RegisterInterchainAccount {
+            connection_id: connection_id.into(),
+            counterparty_connection_id: counterparty_connection_id.into(),
+            job_id: None,
+        })
+    }
+
+    pub fn grant_operator<T>(
+        &self,
+        operator: impl Into<String>,
+        grant: OperatorGrant,
+    ) -> TunnelCall<T> {
+        self.call(ExecuteMsg::GrantOperator {
+            operator: operator.into(),
+            grant,
+        })
+    }
+
+    pub fn revoke_operator<T>(&self, operator: impl Into<String>) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RevokeOperator {
+            operator: operator.into(),
+        })
+    }
+
+    pub fn register_signing_key<T>(&self, key: SigningKey) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RegisterSigningKey { key })
+    }
+
+    pub fn revoke_signing_key<T>(&self) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RevokeSigningKey {})
+    }
+
+    pub fn register_session_key<T>(
+        &self,
+        name: impl Into<String>,
+        session: SessionKey,
+    ) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RegisterSessionKey {
+            name: name.into(),
+            session,
+        })
+    }
+
+    pub fn revoke_session_key<T>(&self, name: impl Into<String>) -> TunnelCall<T> {
+        self.call(ExecuteMsg::RevokeSessionKey { name: name.into() })
+    }
+
+    /// Submits the payload of a `SignedOperation` with its signature, on behalf of its controller
+    pub fn submit_signed<T>(&self, payload: Binary, signature: Binary) -> TunnelCall<T> {
+        self.call(ExecuteMsg::SubmitSigned { payload, signature })
+    }
+
+    /// Sets the spend limit of the proxy on the remote tunnel (this contract),
+    /// to dispatch through the proxy with `into_cosmos_msg`
+    pub fn set_spend_limit<T>(&self, limit: SpendLimit) -> TunnelCall<T> {
+        self.call(ExecuteMsg::SetSpendLimit { limit })
+    }
+
+    /// Any message to the tunnel
+    pub fn call<T>(&self, msg: ExecuteMsg<T>) -> TunnelCall<T> {
+        TunnelCall {
+            contract: self.addr(),
+            msg,
+        }
+    }
+}
+
+/// A message to the tunnel, built with `TunnelContract`.
+/// The tunnel does not take funds, the proxy on the remote chain holds the funds it dispatches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TunnelCall<T = Empty> {
+    contract: Addr,
+    msg: ExecuteMsg<T>,
+}
+
+impl<T> TunnelCall<T> {
+    /// Sets the job id of the operation.
+    /// The tunnel only calls the sender back (see `TunnelReceiverMsg`) if the job id is set.
+    pub fn job_id(mut self, id: impl Into<String>) -> Self {
+        if let Some(job_id) = self.job_id_mut() {
+            *job_id = Some(id.into());
+        }
+        self
+    }
+
+    /// Sets the packet timeout in seconds, the tunnel refuses a timeout of 0
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        if let Some(timeout) = self.timeout_mut() {
+            *timeout = Some(seconds);
+        }
+        self
+    }
+
+    /// Acknowledges with an error if the operation fails on the remote chain
+    pub fn fail_on_error(mut self) -> Self {
+        if let Some(fail_on_error) = self.fail_on_error_mut() {
+            *fail_on_error = true;
+        }
+        self
+    }
+
+    /// Sends the operation through intermediate tunnels along `hops`
+    pub fn forward(mut self, hops: Vec<String>) -> Self {
+        self.msg = ExecuteMsg::RemoteForward {
+            msg: Box::new(self.msg),
+            hops,
+        };
+        self
+    }
+
+    /// Sends the operation as an operator on behalf of `controller`
+    pub fn execute_as(mut self, controller: impl Into<String>) -> Self {
+        self.msg = ExecuteMsg::ExecuteAs {
+            controller: controller.into(),
+            msg: Box::new(self.msg),
+        };
+        self
+    }
+
+    pub fn msg(&self) -> &ExecuteMsg<T> {
+        &self.msg
+    }
+
+    /// The operation that is sent, i.e. without `RemoteForward` and `ExecuteAs`
+    fn operation_mut(&mut self) -> &mut ExecuteMsg<T> {
+        let mut msg = &mut self.msg;
+        while let ExecuteMsg::RemoteForward { msg: inner, .. }
+        | ExecuteMsg::ExecuteAs { msg: inner, .. } = msg
+        {
+            msg = inner;
+        }
+        msg
+    }
+
+    fn job_id_mut(&mut self) -> Option<&mut Option<String>> {
+        match self.operation_mut() {
+            ExecuteMsg::RemoteInstantiate { job_id, .. }
+            | ExecuteMsg::RemoteMigrate { job_id, .. }
+            | ExecuteMsg::RemoteDispatch { job_id, .. }
+            | ExecuteMsg::RegisterInterchainAccount { job_id, .. } => Some(job_id),
+            _ => None,
+        }
+    }
+
+    fn timeout_mut(&mut self) -> Option<&mut Option<u64>> {
+        match self.operation_mut() {
+            ExecuteMsg::RemoteInstantiate { timeout, .. }
+            | ExecuteMsg::RemoteMigrate { timeout, .. }
+            | ExecuteMsg::RemoteDispatch { timeout, .. }
+            | ExecuteMsg::QueryRemoteAddr { timeout, .. } => Some(timeout),
+            _ => None,
+        }
+    }
+
+    fn fail_on_error_mut(&mut self) -> Option<&mut bool> {
+        match self.operation_mut() {
+            ExecuteMsg::RemoteMigrate { fail_on_error, .. }
+            | ExecuteMsg::RemoteDispatch { fail_on_error, .. } => Some(fail_on_error),
+            _ => None,
+        }
+    }
+}
+
+impl<T> TunnelCall<T>
+where
+    T: Clone + std::fmt::Debug + PartialEq + schemars::JsonSchema + serde::Serialize,
+{
+    /// creates a cosmos_msg executing the call on the tunnel
+    pub fn into_cosmos_msg<C>(self) -> StdResult<CosmosMsg<C>>
+    where
+        C: Clone + std::fmt::Debug + PartialEq + schemars::JsonSchema,
+    {
+        Ok(WasmMsg::Execute {
+            contract_addr: self.contract.into(),
+            msg: to_binary(&self.msg)?,
+            funds: vec![],
+        }
+        .into())
+    }
+
+    /// creates a submessage executing the call on the tunnel, replied to the caller as `reply_on`
+    pub fn into_sub_msg<C>(self, id: u64, reply_on: ReplyOn) -> StdResult<SubMsg<C>>
+    where
+        C: Clone + std::fmt::Debug + PartialEq + schemars::JsonSchema,
+    {
+        Ok(SubMsg {
+            id,
+            msg: self.into_cosmos_msg()?,
+            gas_limit: None,
+            reply_on,
+        })
+    }
+}
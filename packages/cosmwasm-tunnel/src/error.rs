@@ -0,0 +1,65 @@
+use cosmwasm_std::StdError;
+use cw_utils::ParseReplyError;
+
+use thiserror::Error;
+
+use crate::{TunnelErrorCode, TunnelOperation, MAX_CHUNKS};
+
+/// The errors of the default `TunnelHost` and `TunnelRemote` handlers,
+/// contracts convert them into their own error with `From<TunnelError>`
+#[derive(Error, Debug, PartialEq)]
+pub enum TunnelError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    ParseReply(#[from] ParseReplyError),
+
+    #[error("Account not found")]
+    AccountNotFound,
+
+    #[error("Account already exists")]
+    AccountAlreadyExists,
+
+    #[error("Invalid Connection Id")]
+    InvalidConnectionId,
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Invalid reply id")]
+    InvalidReplyId,
+
+    #[error("Invalid forward")]
+    InvalidForward,
+
+    #[error("The counterparty of the channel does not support {0:?}")]
+    UnsupportedOperation(TunnelOperation),
+
+    #[error("Message policy violation: {0}")]
+    PolicyViolation(String),
+
+    #[error("The packet timeout must be more than 0 seconds")]
+    InvalidTimeout,
+
+    #[error("The packet needs {0} chunks, more than the maximum of {MAX_CHUNKS}")]
+    PacketTooLarge(usize),
+}
+
+impl TunnelError {
+    /// The code returned to the host chain in the error ack
+    pub fn code(&self) -> TunnelErrorCode {
+        match self {
+            TunnelError::Std(StdError::ParseErr { .. }) => TunnelErrorCode::InvalidPacket,
+            TunnelError::AccountNotFound => TunnelErrorCode::AccountNotFound,
+            TunnelError::AccountAlreadyExists => TunnelErrorCode::AccountAlreadyExists,
+            TunnelError::InvalidConnectionId => TunnelErrorCode::InvalidConnection,
+            TunnelError::Unauthorized => TunnelErrorCode::Unauthorized,
+            TunnelError::InvalidForward | TunnelError::UnsupportedOperation(_) => {
+                TunnelErrorCode::InvalidRoute
+            }
+            TunnelError::PolicyViolation(_) => TunnelErrorCode::PolicyViolation,
+            _ => TunnelErrorCode::Unknown,
+        }
+    }
+}
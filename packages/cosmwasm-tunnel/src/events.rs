@@ -0,0 +1,264 @@
+//! The events emitted by the tunnel.
+//! Each event converts into an `Event` and parses back from one, including the `wasm-` prefixed
+//! events of a transaction result, so indexers and tests share one schema.
+use cosmwasm_std::{Event, StdError, StdResult};
+
+/// An event of the tunnel with a fixed type and attributes
+pub trait TunnelEvent: Sized {
+    /// The type of the event, without the `wasm-` prefix added by the chain
+    const EVENT_TYPE: &'static str;
+
+    fn into_event(self) -> Event;
+
+    fn from_event(event: &Event) -> StdResult<Self>;
+
+    /// Whether `event` has this type, as emitted by the contract or as found in a transaction result
+    fn matches(event: &Event) -> bool {
+        event.ty == Self::EVENT_TYPE || event.ty.strip_prefix("wasm-") == Some(Self::EVENT_TYPE)
+    }
+
+    /// Parses the first event of this type in `events`
+    fn find(events: &[Event]) -> Option<Self> {
+        events
+            .iter()
+            .find(|event| Self::matches(event))
+            .and_then(|event| Self::from_event(event).ok())
+    }
+}
+
+/// How a field of an event is stored in an attribute, `None` values are left out
+trait AttributeValue: Sized {
+    fn to_value(&self) -> Option<String>;
+
+    fn from_value(key: &str, value: Option<&str>) -> StdResult<Self>;
+}
+
+impl AttributeValue for String {
+    fn to_value(&self) -> Option<String> {
+        Some(self.clone())
+    }
+
+    fn from_value(key: &str, value: Option<&str>) -> StdResult<Self> {
+        value
+            .map(String::from)
+            .ok_or_else(|| StdError::generic_err(format!("missing event attribute {key}")))
+    }
+}
+
+impl AttributeValue for Option<String> {
+    fn to_value(&self) -> Option<String> {
+        self.clone()
+    }
+
+    fn from_value(_key: &str, value: Option<&str>) -> StdResult<Self> {
+        Ok(value.map(String::from))
+    }
+}
+
+/// Comma separated, as channel ids, versions and chain ids do not contain commas.
+/// An empty list is left out, as the chain rejects attributes with an empty value
+impl AttributeValue for Vec<String> {
+    fn to_value(&self) -> Option<String> {
+        (!self.is_empty()).then(|| self.join(","))
+    }
+
+    fn from_value(_key: &str, value: Option<&str>) -> StdResult<Self> {
+        match value {
+            None | Some("") => Ok(vec![]),
+            Some(value) => Ok(value.split(',').map(String::from).collect()),
+        }
+    }
+}
+
+macro_rules! tunnel_events {
+    ($(
+        $(#[$meta:meta])*
+        $name:ident => $ty:literal {
+            $($field:ident: $field_ty:ty),* $(,)?
+        }
+    )*) => {$(
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name {
+            $(pub $field: $field_ty,)*
+        }
+
+        impl TunnelEvent for $name {
+            const EVENT_TYPE: &'static str = $ty;
+
+            #[allow(unused_mut)]
+            fn into_event(self) -> Event {
+                let mut event = Event::new(Self::EVENT_TYPE);
+                $(
+                    if let Some(value) = AttributeValue::to_value(&self.$field) {
+                        event = event.add_attribute(stringify!($field), value);
+                    }
+                )*
+                event
+            }
+
+            #[allow(unused_variables)]
+            fn from_event(event: &Event) -> StdResult<Self> {
+                if !Self::matches(event) {
+                    return Err(StdError::generic_err(format!(
+                        "expected event {}, got {}",
+                        Self::EVENT_TYPE,
+                        event.ty
+                    )));
+                }
+                let attribute = |key: &str| {
+                    event
+                        .attributes
+                        .iter()
+                        .find(|attr| attr.key == key)
+                        .map(|attr| attr.value.as_str())
+                };
+                Ok($name {
+                    $($field: AttributeValue::from_value(
+                        stringify!($field),
+                        attribute(stringify!($field)),
+                    )?,)*
+                })
+            }
+        }
+
+        impl From<$name> for Event {
+            fn from(event: $name) -> Self {
+                event.into_event()
+            }
+        }
+
+        impl TryFrom<&Event> for $name {
+            type Error = StdError;
+
+            fn try_from(event: &Event) -> StdResult<Self> {
+                <$name as TunnelEvent>::from_event(event)
+            }
+        }
+    )*};
+}
+
+tunnel_events! {
+    /// The tunnel contract was instantiated, with the roles it is built with (`host`, `remote`)
+    TunnelInstantiated => "ica-tunnel.V1.MsgInstantiated" {
+        roles: Vec<String>,
+    }
+
+    /// The tunnel contract was migrated, with the names of the applied state migrations
+    TunnelMigrated => "ica-tunnel.V1.MsgMigrated" {
+        from_version: String,
+        to_version: String,
+        migrations: Vec<String>,
+    }
+
+    /// A controller asked for a proxy to be instantiated on the remote chain
+    InstantiationRequested => "ica-tunnel.V1.HostMsg.InstantiationRequested" {
+        channel_id: String,
+        controller: String,
+        job_id: Option<String>,
+    }
+
+    /// A controller asked for its proxy to be migrated on the remote chain
+    MigrationRequested => "ica-tunnel.V1.HostMsg.MigrationRequested" {
+        channel_id: String,
+        controller: String,
+        job_id: Option<String>,
+    }
+
+    /// A controller dispatched messages to its proxy or interchain account
+    DispatchRequested => "ica-tunnel.V1.HostMsg.DispatchRequested" {
+        channel_id: String,
+        controller: String,
+        job_id: Option<String>,
+    }
+
+    /// A controller asked for the address of its proxy
+    RemoteAddrRequested => "ica-tunnel.V1.HostMsg.RemoteAddrRequested" {
+        channel_id: String,
+        controller: String,
+    }
+
+    /// A controller registered an ICS-27 interchain account
+    InterchainAccountRequested => "ica-tunnel.V1.HostMsg.InterchainAccountRequested" {
+        connection_id: String,
+        controller: String,
+        job_id: Option<String>,
+    }
+
+    /// A controller sent an operation through intermediate tunnels
+    ForwardRequested => "ica-tunnel.V1.HostMsg.ForwardRequested" {
+        channel_id: String,
+        controller: String,
+        hops: Vec<String>,
+        job_id: Option<String>,
+    }
+
+    /// A controller granted an operator
+    OperatorGranted => "ica-tunnel.V1.HostMsg.OperatorGranted" {
+        controller: String,
+        operator: String,
+    }
+
+    /// A controller revoked an operator
+    OperatorRevoked => "ica-tunnel.V1.HostMsg.OperatorRevoked" {
+        controller: String,
+        operator: String,
+    }
+
+    /// A controller registered the key it signs operations with
+    SigningKeyRegistered => "ica-tunnel.V1.HostMsg.SigningKeyRegistered" {
+        controller: String,
+    }
+
+    /// A controller revoked its signing key
+    SigningKeyRevoked => "ica-tunnel.V1.HostMsg.SigningKeyRevoked" {
+        controller: String,
+    }
+
+    /// A controller registered a session key
+    SessionKeyRegistered => "ica-tunnel.V1.HostMsg.SessionKeyRegistered" {
+        controller: String,
+        name: String,
+    }
+
+    /// A controller revoked a session key
+    SessionKeyRevoked => "ica-tunnel.V1.HostMsg.SessionKeyRevoked" {
+        controller: String,
+        name: String,
+    }
+
+    /// The channel of an ICS-27 interchain account was opened
+    InterchainAccountOpened => "ica-tunnel.V1.MsgInterchainAccountOpened" {
+        channel_id: String,
+        controller: String,
+        address: String,
+    }
+
+    /// Governance set the message policy of a proxy, or the global policy without `account`
+    MsgPolicySet => "ica-tunnel.V1.MsgPolicySet" {
+        account: Option<String>,
+    }
+
+    /// Governance removed the message policy of a proxy, or the global policy without `account`
+    MsgPolicyRemoved => "ica-tunnel.V1.MsgPolicyRemoved" {
+        account: Option<String>,
+    }
+
+    /// Governance or the controller set the spend limit of a proxy
+    SpendLimitSet => "ica-tunnel.V1.MsgSpendLimitSet" {
+        account: String,
+    }
+
+    /// Governance removed the spend limit of a proxy
+    SpendLimitRemoved => "ica-tunnel.V1.MsgSpendLimitRemoved" {
+        account: String,
+    }
+
+    /// A proxy was instantiated on this (remote) chain for the controller on the other end of the connection
+    ProxyInstantiated => "ica-tunnel.V1.MsgICAInstantiated" {
+        contract_addr: String,
+        connection_id: String,
+        port_id: String,
+        controller: String,
+    }
+}
@@ -0,0 +1,339 @@
+use std::fmt::Display;
+
+use cosmwasm_schema::serde::{de::DeserializeOwned, Serialize};
+use cosmwasm_std::{
+    from_slice, Binary, Deps, DepsMut, Env, IbcBasicResponse, IbcMsg, IbcPacketAckMsg,
+    IbcPacketTimeoutMsg, MessageInfo, Response, StdError, StdResult, Storage, SubMsg,
+};
+
+use crate::{
+    chunk_size, split_packet, ChannelMetadata, ChunkResponse, ErrorResponse, ExecuteMsg,
+    PacketCodec, PacketMsg, ReceiveIcaResponseMsg, StdAck, TunnelError, TunnelErrorCode,
+    TunnelOperation, MAX_CHUNKS, MAX_PACKET_SIZE, PACKET_LIFETIME,
+};
+
+pub const ACK_CALLBACK_ID: u64 = 4567;
+
+/// A packet to send, created from an `ExecuteMsg` of a controller
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutgoingPacket<T> {
+    pub channel_id: String,
+    pub packet: PacketMsg<T>,
+    /// In seconds, `PACKET_LIFETIME` if not set
+    pub timeout: Option<u64>,
+}
+
+/// The host side of a tunnel: sends the operations of controllers and returns the acks to them.
+/// Contracts embedding the tunnel implement the hooks and call the handlers from their entry points.
+pub trait TunnelHost<T: Serialize + DeserializeOwned = cosmwasm_std::Empty> {
+    type Error: From<StdError> + From<TunnelError> + Display;
+
+    /// The reply id of the controller callbacks
+    const ACK_CALLBACK_ID: u64 = ACK_CALLBACK_ID;
+    /// Larger packets are sent in chunks if the counterparty supports it
+    const MAX_PACKET_SIZE: usize = MAX_PACKET_SIZE;
+
+    /// The nonce of the next packet of `controller` on `channel_id`
+    fn next_nonce(
+        &self,
+        deps: DepsMut,
+        channel_id: &str,
+        controller: &str,
+    ) -> Result<u64, Self::Error>;
+
+    /// The protocol version and packet encoding negotiated for the channel,
+    /// the latest version in JSON unless the channel was opened with others
+    fn codec(&self, _storage: &dyn Storage, _channel_id: &str) -> Result<PacketCodec, Self::Error> {
+        Ok(PacketCodec::default())
+    }
+
+    /// The metadata the counterparty sent in the channel handshake, if it is known
+    fn counterparty(
+        &self,
+        _deps: Deps,
+        _channel_id: &str,
+    ) -> Result<Option<ChannelMetadata>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Checks the sender may send `packet`, which is sent as the sender
+    fn authorize(
+        &self,
+        _deps: Deps,
+        _env: &Env,
+        _info: &MessageInfo,
+        _packet: &OutgoingPacket<T>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The packet of a `Remote*` operation of `controller`, `None` for the other messages
+    fn create_packet(&self, controller: String, msg: ExecuteMsg<T>) -> Option<OutgoingPacket<T>> {
+        let (packet, channel_id, timeout) = match msg {
+            ExecuteMsg::RemoteInstantiate {
+                inst_msg,
+                job_id,
+                code_id,
+                channel_id,
+                timeout,
+            } => (
+                PacketMsg::Instantiate {
+                    controller,
+                    inst_msg,
+                    job_id,
+                    code_id,
+                    nonce: 0,
+                },
+                channel_id,
+                timeout,
+            ),
+            ExecuteMsg::RemoteMigrate {
+                migrate_msg,
+                job_id,
+                new_code_id,
+                channel_id,
+                fail_on_error,
+                timeout,
+            } => (
+                PacketMsg::Migrate {
+                    controller,
+                    migration_msg: migrate_msg,
+                    new_code_id,
+                    job_id,
+                    fail_on_error,
+                    nonce: 0,
+                },
+                channel_id,
+                timeout,
+            ),
+            ExecuteMsg::RemoteDispatch {
+                dispatch_msg,
+                job_id,
+                channel_id,
+                fail_on_error,
+                timeout,
+            } => (
+                PacketMsg::Dispatch {
+                    controller,
+                    msg: dispatch_msg,
+                    job_id,
+                    fail_on_error,
+                    nonce: 0,
+                },
+                channel_id,
+                timeout,
+            ),
+            ExecuteMsg::QueryRemoteAddr {
+                channel_id,
+                timeout,
+            } => (
+                PacketMsg::WhoAmI {
+                    controller,
+                    nonce: 0,
+                },
+                channel_id,
+                timeout,
+            ),
+            _ => return None,
+        };
+        Some(OutgoingPacket {
+            channel_id,
+            packet,
+            timeout,
+        })
+    }
+
+    /// Sends a `Remote*` operation as the sender
+    fn execute_remote(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg<T>,
+    ) -> Result<Response, Self::Error> {
+        let packet = self
+            .create_packet(info.sender.to_string(), msg)
+            .ok_or(TunnelError::Unauthorized)?;
+        self.authorize(deps.as_ref(), &env, &info, &packet)?;
+        let msgs = self.send_packet(deps, &env, packet)?;
+        Ok(Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "execute_remote"))
+    }
+
+    /// Assigns the next nonce of the controller to the packet, on channels with nonces, and sends it,
+    /// if the counterparty of the channel supports its operation.
+    /// A packet larger than `MAX_PACKET_SIZE` is sent in chunks if the counterparty supports them.
+    fn send_packet(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        mut packet: OutgoingPacket<T>,
+    ) -> Result<Vec<IbcMsg>, Self::Error> {
+        if packet.timeout == Some(0) {
+            return Err(TunnelError::InvalidTimeout.into());
+        }
+        let codec = self.codec(deps.storage, &packet.channel_id)?;
+        let counterparty = self.counterparty(deps.as_ref(), &packet.channel_id)?;
+        let supports = |operation| counterparty.as_ref().map(|c| c.supports(operation));
+        if let Some(operation) = packet.packet.operation() {
+            if !codec.version.supports(operation) || supports(operation) == Some(false) {
+                return Err(TunnelError::UnsupportedOperation(operation).into());
+            }
+        }
+        // the packets of `cw-tunnel-v1` channels are sent without nonces, they do not take one
+        let controller = packet.packet.controller().to_string();
+        match packet.packet.nonce_mut() {
+            Some(nonce) if codec.has_nonces() => {
+                *nonce = self.next_nonce(deps, &packet.channel_id, &controller)?;
+            }
+            _ => {}
+        }
+
+        let data = codec.encode_packet(&packet.packet)?;
+        if data.len() <= Self::MAX_PACKET_SIZE || supports(TunnelOperation::Chunk) != Some(true) {
+            return Ok(vec![send_packet_msg(
+                env,
+                packet.channel_id,
+                data,
+                packet.timeout,
+            )]);
+        }
+        let chunk_size = chunk_size(codec, &packet.packet, Self::MAX_PACKET_SIZE)?;
+        let chunks = data.len().div_ceil(chunk_size);
+        if chunks > MAX_CHUNKS as usize {
+            return Err(TunnelError::PacketTooLarge(chunks).into());
+        }
+        split_packet(&packet.packet, &data, chunk_size)
+            .into_iter()
+            .map(|chunk| {
+                let data = codec.encode_packet(&chunk)?;
+                let channel_id = packet.channel_id.clone();
+                Ok(send_packet_msg(env, channel_id, data, packet.timeout))
+            })
+            .collect()
+    }
+
+    /// The `IbcMsg` of the packet as it is, encoded for the channel
+    fn ibc_msg(
+        &self,
+        env: &Env,
+        codec: PacketCodec,
+        packet: OutgoingPacket<T>,
+    ) -> StdResult<IbcMsg> {
+        let data = codec.encode_packet(&packet.packet)?;
+        Ok(send_packet_msg(
+            env,
+            packet.channel_id,
+            data,
+            packet.timeout,
+        ))
+    }
+
+    /// The sent packet and its ack, a malformed ack is returned as a `TunnelErrorCode::InvalidAck` error
+    fn decode_ack(
+        &self,
+        deps: Deps,
+        msg: &IbcPacketAckMsg,
+    ) -> Result<(PacketMsg<T>, StdAck), Self::Error> {
+        let codec = self.codec(deps.storage, &msg.original_packet.src.channel_id)?;
+        let packet = codec.decode_packet(&msg.original_packet.data)?;
+        let ack = match codec.decode_ack(&msg.acknowledgement.data) {
+            Ok(ack) => ack,
+            Err(e) => StdAck::error_with(&ErrorResponse {
+                code: TunnelErrorCode::InvalidAck,
+                message: e.to_string(),
+                job_id: packet.job_id().cloned(),
+            })?,
+        };
+        Ok((packet, ack))
+    }
+
+    /// Handles `ibc_packet_ack`
+    fn packet_ack(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketAckMsg,
+    ) -> Result<IbcBasicResponse, Self::Error> {
+        let (packet, ack) = self.decode_ack(deps.as_ref(), &msg)?;
+        // the controller is called back once the part completing the packet is acknowledged
+        if let (PacketMsg::Chunk { .. }, StdAck::Result(data)) = (&packet, &ack) {
+            if let Ok(res) = from_slice::<ChunkResponse>(data) {
+                return Ok(IbcBasicResponse::new()
+                    .add_attribute("action", "ibc_packet_ack")
+                    .add_attribute("chunks_received", res.received.to_string()));
+            }
+        }
+        Ok(self
+            .deliver_ack(
+                deps,
+                &env,
+                &msg.original_packet.src.channel_id,
+                &packet,
+                ack,
+            )?
+            .add_attribute("action", "ibc_packet_ack"))
+    }
+
+    /// Handles `ibc_packet_timeout`, delivered as a `TunnelErrorCode::Timeout` error
+    fn packet_timeout(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketTimeoutMsg,
+    ) -> Result<IbcBasicResponse, Self::Error> {
+        let codec = self.codec(deps.storage, &msg.packet.src.channel_id)?;
+        let packet: PacketMsg<T> = codec.decode_packet(&msg.packet.data)?;
+        let ack = StdAck::error_with(&ErrorResponse {
+            code: TunnelErrorCode::Timeout,
+            message: "IBC Packet Timeout".to_string(),
+            job_id: packet.job_id().cloned(),
+        })?;
+        Ok(self
+            .deliver_ack(deps, &env, &msg.packet.src.channel_id, &packet, ack)?
+            .add_attribute("action", "ibc_packet_timeout"))
+    }
+
+    /// Returns the ack of `packet`, sent on `channel_id`, to its controller
+    fn deliver_ack(
+        &self,
+        _deps: DepsMut,
+        _env: &Env,
+        _channel_id: &str,
+        packet: &PacketMsg<T>,
+        ack: StdAck,
+    ) -> Result<IbcBasicResponse, Self::Error> {
+        Ok(IbcBasicResponse::new().add_submessages(self.callback_msg(
+            packet.controller(),
+            packet.job_id(),
+            ack,
+        )?))
+    }
+
+    /// The controller is only called back if it has set a `job_id`, i.e. it expects the callback.
+    /// A failing callback does not revert the ack.
+    fn callback_msg(
+        &self,
+        controller: &str,
+        job_id: Option<&String>,
+        ack: StdAck,
+    ) -> StdResult<Option<SubMsg>> {
+        job_id
+            .map(|id| {
+                let msg =
+                    ReceiveIcaResponseMsg::new(id.clone(), ack).into_cosmos_msg(controller)?;
+                Ok(SubMsg::reply_on_error(msg, Self::ACK_CALLBACK_ID))
+            })
+            .transpose()
+    }
+}
+
+fn send_packet_msg(env: &Env, channel_id: String, data: Binary, timeout: Option<u64>) -> IbcMsg {
+    let timeout = timeout.unwrap_or(PACKET_LIFETIME);
+    IbcMsg::SendPacket {
+        data,
+        channel_id,
+        timeout: env.block.time.plus_seconds(timeout).into(),
+    }
+}
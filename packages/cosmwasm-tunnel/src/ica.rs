@@ -0,0 +1,293 @@
+//! Native interchain accounts (ICS-27) for remote chains that do not run CosmWasm.
+//! The tunnel is the owner of the interchain account on the controller module,
+//! messages are sent as stargate `MsgSendTx` and the packet lifecycle is delivered to the tunnel through `sudo`.
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_slice, to_vec, Binary, CosmosMsg, StdError, StdResult};
+use prost::Message;
+use sha2::{Digest, Sha256};
+
+use crate::StdAck;
+
+pub const ICA_VERSION: &str = "ics27-1";
+pub const ICA_ENCODING: &str = "proto3";
+pub const ICA_TX_TYPE: &str = "sdk_multi_msg";
+pub const ICA_CONTROLLER_PORT_PREFIX: &str = "icacontroller-";
+
+pub const MSG_REGISTER_INTERCHAIN_ACCOUNT_TYPE_URL: &str =
+    "/ibc.applications.interchain_accounts.controller.v1.MsgRegisterInterchainAccount";
+pub const MSG_SEND_TX_TYPE_URL: &str =
+    "/ibc.applications.interchain_accounts.controller.v1.MsgSendTx";
+
+/// `InterchainAccountPacketData.type` for executing a tx on the host chain
+const TYPE_EXECUTE_TX: i32 = 1;
+
+/// ICS-27 channel version metadata
+#[cw_serde]
+pub struct IcaMetadata {
+    pub version: String,
+    pub controller_connection_id: String,
+    pub host_connection_id: String,
+    pub address: String,
+    pub encoding: String,
+    pub tx_type: String,
+}
+
+impl IcaMetadata {
+    pub fn new(controller_connection_id: String, host_connection_id: String) -> Self {
+        IcaMetadata {
+            version: ICA_VERSION.to_string(),
+            controller_connection_id,
+            host_connection_id,
+            address: String::new(),
+            encoding: ICA_ENCODING.to_string(),
+            tx_type: ICA_TX_TYPE.to_string(),
+        }
+    }
+}
+
+/// The json we put in the memo of the ICS-27 packet, to find the controller and job on ack
+#[cw_serde]
+pub struct IcaMemo {
+    pub controller: String,
+    pub job_id: Option<String>,
+}
+
+/// The ICS-27 packet data as relayed, the json encoding of `InterchainAccountPacketData`
+#[cw_serde]
+pub struct IcaPacketData {
+    #[serde(rename = "type")]
+    pub tx_type: String,
+    pub data: Binary,
+    pub memo: String,
+}
+
+/// A message response of the executed tx on the host chain (protobuf `Any`)
+#[cw_serde]
+pub struct IcaMsgResponse {
+    pub type_url: String,
+    pub value: Binary,
+}
+
+/// This is the success response we send to the controller on ack of an ICS-27 tx
+#[cw_serde]
+pub struct IcaTxResponse {
+    pub msg_responses: Vec<IcaMsgResponse>,
+    pub job_id: Option<String>,
+}
+
+/// The sent packet as delivered by the ICA controller middleware in `IcaSudoMsg`
+#[cw_serde]
+pub struct RequestPacket {
+    pub sequence: Option<u64>,
+    pub source_port: Option<String>,
+    pub source_channel: Option<String>,
+    pub destination_port: Option<String>,
+    pub destination_channel: Option<String>,
+    pub data: Option<Binary>,
+    pub timeout_height: Option<RequestPacketTimeoutHeight>,
+    pub timeout_timestamp: Option<u64>,
+}
+
+#[cw_serde]
+pub struct RequestPacketTimeoutHeight {
+    pub revision_number: Option<u64>,
+    pub revision_height: Option<u64>,
+}
+
+impl RequestPacket {
+    /// The memo of the sent ICS-27 packet
+    pub fn ica_memo(&self) -> StdResult<IcaMemo> {
+        let data = self
+            .data
+            .as_ref()
+            .ok_or_else(|| StdError::generic_err("missing packet data"))?;
+        let packet: IcaPacketData = from_slice(data)?;
+        from_slice(packet.memo.as_bytes())
+    }
+}
+
+/// The ICS-27 packet lifecycle, delivered to the owner contract by the chain's ICA controller middleware
+#[cw_serde]
+pub enum IcaSudoMsg {
+    Response {
+        request: RequestPacket,
+        data: Binary,
+    },
+    Error {
+        request: RequestPacket,
+        details: String,
+    },
+    Timeout {
+        request: RequestPacket,
+    },
+    OpenAck {
+        port_id: String,
+        channel_id: String,
+        counterparty_channel_id: String,
+        counterparty_version: String,
+    },
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoAny {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes, tag = "2")]
+    value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MsgRegisterInterchainAccount {
+    #[prost(string, tag = "1")]
+    owner: String,
+    #[prost(string, tag = "2")]
+    connection_id: String,
+    #[prost(string, tag = "3")]
+    version: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct InterchainAccountPacketData {
+    #[prost(int32, tag = "1")]
+    r#type: i32,
+    #[prost(bytes, tag = "2")]
+    data: Vec<u8>,
+    #[prost(string, tag = "3")]
+    memo: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct CosmosTx {
+    #[prost(message, repeated, tag = "1")]
+    messages: Vec<ProtoAny>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MsgSendTx {
+    #[prost(string, tag = "1")]
+    owner: String,
+    #[prost(string, tag = "2")]
+    connection_id: String,
+    #[prost(message, optional, tag = "3")]
+    packet_data: Option<InterchainAccountPacketData>,
+    #[prost(uint64, tag = "4")]
+    relative_timeout: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MsgData {
+    #[prost(string, tag = "1")]
+    msg_type: String,
+    #[prost(bytes, tag = "2")]
+    data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TxMsgData {
+    #[prost(message, repeated, tag = "1")]
+    data: Vec<MsgData>,
+    #[prost(message, repeated, tag = "2")]
+    msg_responses: Vec<ProtoAny>,
+}
+
+/// The port the ICA controller module binds for `owner`
+pub fn ica_controller_port(owner: &str) -> String {
+    format!("{ICA_CONTROLLER_PORT_PREFIX}{owner}")
+}
+
+/// The owner the tunnel registers the interchain account of `controller` with:
+/// the tunnel followed by a short hash of the controller, as port ids are limited to 128 characters
+pub fn ica_owner(tunnel: &str, controller: &str) -> String {
+    let hash = Sha256::digest(controller.as_bytes());
+    let id: String = hash[..8].iter().map(|b| format!("{b:02x}")).collect();
+    format!("{tunnel}.{id}")
+}
+
+/// Creates the stargate message registering an interchain account owned by `owner` on `connection_id`
+pub fn register_interchain_account_msg<T>(
+    owner: String,
+    connection_id: String,
+    counterparty_connection_id: String,
+) -> StdResult<CosmosMsg<T>> {
+    let metadata = IcaMetadata::new(connection_id.clone(), counterparty_connection_id);
+    let version = String::from_utf8(to_vec(&metadata)?).map_err(StdError::invalid_utf8)?;
+    let msg = MsgRegisterInterchainAccount {
+        owner,
+        connection_id,
+        version,
+    };
+    Ok(CosmosMsg::Stargate {
+        type_url: MSG_REGISTER_INTERCHAIN_ACCOUNT_TYPE_URL.to_string(),
+        value: msg.encode_to_vec().into(),
+    })
+}
+
+/// Creates the stargate message executing `msgs` on the interchain account of `owner`.
+/// Each message must be a `CosmosMsg::Stargate`, i.e. a protobuf `Any` of the host chain.
+pub fn send_tx_msg<T, U>(
+    owner: String,
+    connection_id: String,
+    msgs: Vec<CosmosMsg<U>>,
+    memo: &IcaMemo,
+    relative_timeout_seconds: u64,
+) -> StdResult<CosmosMsg<T>> {
+    let messages = msgs
+        .into_iter()
+        .map(|msg| match msg {
+            CosmosMsg::Stargate { type_url, value } => Ok(ProtoAny {
+                type_url,
+                value: value.into(),
+            }),
+            _ => Err(StdError::generic_err(
+                "interchain account messages must be CosmosMsg::Stargate",
+            )),
+        })
+        .collect::<StdResult<_>>()?;
+    let memo = String::from_utf8(to_vec(memo)?).map_err(StdError::invalid_utf8)?;
+    let msg = MsgSendTx {
+        owner,
+        connection_id,
+        packet_data: Some(InterchainAccountPacketData {
+            r#type: TYPE_EXECUTE_TX,
+            data: CosmosTx { messages }.encode_to_vec(),
+            memo,
+        }),
+        relative_timeout: relative_timeout_seconds * 1_000_000_000,
+    };
+    Ok(CosmosMsg::Stargate {
+        type_url: MSG_SEND_TX_TYPE_URL.to_string(),
+        value: msg.encode_to_vec().into(),
+    })
+}
+
+/// Decodes the result of an ICS-27 ack (protobuf `TxMsgData`) into the message responses.
+/// `data` is either the raw `TxMsgData` or the full json channel acknowledgement.
+pub fn decode_ica_ack(data: &Binary) -> StdResult<Vec<IcaMsgResponse>> {
+    let raw = match from_slice::<StdAck>(data) {
+        Ok(ack) => ack.into_result().map_err(StdError::generic_err)?,
+        Err(_) => data.clone(),
+    };
+    let tx_data = TxMsgData::decode(raw.as_slice())
+        .map_err(|e| StdError::parse_err("TxMsgData", e.to_string()))?;
+    // chains before cosmos-sdk v0.46 only set the deprecated `data` field
+    let responses = if tx_data.msg_responses.is_empty() {
+        tx_data
+            .data
+            .into_iter()
+            .map(|data| IcaMsgResponse {
+                type_url: data.msg_type,
+                value: data.data.into(),
+            })
+            .collect()
+    } else {
+        tx_data
+            .msg_responses
+            .into_iter()
+            .map(|any| IcaMsgResponse {
+                type_url: any.type_url,
+                value: any.value.into(),
+            })
+            .collect()
+    };
+    Ok(responses)
+}
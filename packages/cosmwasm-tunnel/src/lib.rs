@@ -1,12 +1,58 @@
+mod ack;
+pub mod bindings;
+mod callback;
 mod checks;
+mod chunk;
+mod client;
+mod error;
+pub mod events;
+mod host;
+pub mod ica;
+mod metadata;
 mod msg;
+mod proto;
+mod protocol;
+mod remote;
+pub mod v1;
 
 use cosmwasm_std::IbcOrder;
 
-pub use crate::checks::{check_order, check_version, ChannelError};
+pub use crate::ack::{AckError, TunnelAck};
+pub use crate::callback::{ReceiveIcaResponseMsg, TunnelReceiverMsg};
+pub use crate::checks::{check_order, ChannelError};
+pub use crate::chunk::{chunk_hash, chunk_size, split_packet};
+pub use crate::client::{TunnelCall, TunnelContract};
+pub use crate::error::TunnelError;
+pub use crate::host::{OutgoingPacket, TunnelHost, ACK_CALLBACK_ID};
+pub use crate::metadata::{ChannelMetadata, PacketEncoding, TunnelOperation};
 pub use crate::msg::*;
+pub use crate::protocol::{
+    channel_version, negotiate_version, Negotiated, PacketCodec, ProtocolVersion,
+    SUPPORTED_VERSIONS,
+};
+pub use crate::remote::{
+    PacketSource, SenderInfo, TunnelRemote, INIT_CALLBACK_ID, MIGRATE_CALLBACK_ID,
+    RECEIVE_DISPATCH_ID,
+};
 
+/// The version of the first protocol, still spoken on the channels opened with it, see `ProtocolVersion`
 pub const IBC_APP_VERSION: &str = "cw-tunnel-v1";
+/// The latest protocol version, see `ProtocolVersion`
+pub const LATEST_APP_VERSION: &str = "cw-tunnel-v2";
 pub const APP_ORDER: IbcOrder = IbcOrder::Unordered;
 pub const BAD_APP_ORDER: IbcOrder = IbcOrder::Ordered;
 pub const PACKET_LIFETIME: u64 = 60 * 60;
+/// The maximum number of chains a packet can be forwarded through
+pub const MAX_FORWARD_HOPS: usize = 4;
+/// The maximum nesting of objects and arrays in received packet data
+pub const MAX_PACKET_DEPTH: usize = 64;
+/// The maximum size of sent packet data, larger packets are sent in chunks that are encoded within this size
+pub const MAX_PACKET_SIZE: usize = 32 * 1024;
+/// The maximum number of chunks of a packet
+pub const MAX_CHUNKS: u32 = 64;
+/// The maximum number of incomplete packets a controller can have on the remote tunnel at once
+pub const MAX_UPLOADS: u32 = 8;
+/// How long the chunks of an incomplete packet are kept, in seconds
+pub const CHUNK_UPLOAD_LIFETIME: u64 = 24 * 60 * 60;
+/// The maximum (and default) number of nonces below the highest received nonce that are accepted out of order
+pub const MAX_NONCE_WINDOW: u64 = 64;
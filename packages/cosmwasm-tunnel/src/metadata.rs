@@ -0,0 +1,110 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_slice, to_vec, StdError, StdResult};
+
+use crate::{ChannelError, PacketMsg, ProtocolVersion, TunnelRole, LATEST_APP_VERSION};
+
+/// The operations a tunnel runs when it receives them
+#[cw_serde]
+#[derive(Copy)]
+pub enum TunnelOperation {
+    Instantiate,
+    Migrate,
+    Dispatch,
+    WhoAmI,
+    /// Sending packets on to another chain, needs both roles
+    Forward,
+    /// Receiving packets larger than `MAX_PACKET_SIZE` in parts
+    Chunk,
+}
+
+/// The encoding of the packets and acks on a channel, proposed by the tunnel opening it
+#[cw_serde]
+#[derive(Copy, Default)]
+pub enum PacketEncoding {
+    #[default]
+    Json,
+    /// `proto/cw_tunnel/v2/packet.proto`, needs `cw-tunnel-v2`
+    Proto,
+}
+
+/// The channel version of a tunnel, JSON encoded as ICS-27's metadata.
+/// Each end sends its own in the handshake and keeps the one of the counterparty per channel.
+#[cw_serde]
+pub struct ChannelMetadata {
+    /// The protocol version, `LATEST_APP_VERSION` when sent by this tunnel
+    pub version: String,
+    /// The roles the tunnel is built with
+    pub roles: Vec<TunnelRole>,
+    pub chain_id: String,
+    /// The operations the tunnel runs when it receives them
+    pub operations: Vec<TunnelOperation>,
+    pub encoding: PacketEncoding,
+}
+
+impl ChannelMetadata {
+    /// The metadata of a tunnel with `roles`, which runs the operations of its roles
+    pub fn new(chain_id: impl Into<String>, roles: Vec<TunnelRole>) -> Self {
+        let mut operations = vec![];
+        if roles.contains(&TunnelRole::Remote) {
+            operations.extend([
+                TunnelOperation::Instantiate,
+                TunnelOperation::Migrate,
+                TunnelOperation::Dispatch,
+                TunnelOperation::WhoAmI,
+                TunnelOperation::Chunk,
+            ]);
+            if roles.contains(&TunnelRole::Host) {
+                operations.push(TunnelOperation::Forward);
+            }
+        }
+        ChannelMetadata {
+            version: LATEST_APP_VERSION.to_string(),
+            roles,
+            chain_id: chain_id.into(),
+            operations,
+            encoding: PacketEncoding::Json,
+        }
+    }
+
+    /// Parses the channel version sent by the counterparty
+    pub fn parse(version: &str) -> Result<Self, ChannelError> {
+        let metadata: ChannelMetadata = from_slice(version.as_bytes())
+            .map_err(|_| ChannelError::InvalidChannelVersion(LATEST_APP_VERSION))?;
+        metadata.protocol_version()?;
+        Ok(metadata)
+    }
+
+    /// The protocol version of the metadata, `cw-tunnel-v1` channels have none
+    pub fn protocol_version(&self) -> Result<ProtocolVersion, ChannelError> {
+        match ProtocolVersion::parse(&self.version) {
+            Some(ProtocolVersion::V1) | None => {
+                Err(ChannelError::InvalidChannelVersion(LATEST_APP_VERSION))
+            }
+            Some(version) => Ok(version),
+        }
+    }
+
+    /// The channel version string
+    pub fn to_version(&self) -> StdResult<String> {
+        String::from_utf8(to_vec(self)?).map_err(StdError::from)
+    }
+
+    pub fn supports(&self, operation: TunnelOperation) -> bool {
+        self.operations.contains(&operation)
+    }
+}
+
+impl<T> PacketMsg<T> {
+    /// The operation the receiving tunnel runs, `None` for the acks of forwarded packets
+    pub fn operation(&self) -> Option<TunnelOperation> {
+        match self {
+            PacketMsg::Instantiate { .. } => Some(TunnelOperation::Instantiate),
+            PacketMsg::Migrate { .. } => Some(TunnelOperation::Migrate),
+            PacketMsg::Dispatch { .. } => Some(TunnelOperation::Dispatch),
+            PacketMsg::WhoAmI { .. } => Some(TunnelOperation::WhoAmI),
+            PacketMsg::Forward { .. } => Some(TunnelOperation::Forward),
+            PacketMsg::ForwardAck { .. } => None,
+            PacketMsg::Chunk { .. } => Some(TunnelOperation::Chunk),
+        }
+    }
+}
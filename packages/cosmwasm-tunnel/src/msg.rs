@@ -1,34 +1,448 @@
+use cosmwasm_schema::schemars::JsonSchema;
 use cosmwasm_schema::{cw_serde, serde, QueryResponses};
-use cosmwasm_std::{from_slice, to_binary, Binary, CosmosMsg, SubMsgResult};
+
+use crate::ica::IcaSudoMsg;
+use crate::{
+    AckError, ChannelMetadata, PacketEncoding, ProtocolVersion, TunnelError, TunnelOperation,
+    MAX_PACKET_DEPTH,
+};
+use cosmwasm_std::{
+    from_slice, to_binary, to_vec, Api, BankMsg, Binary, Coin, CosmosMsg, Empty, IbcMsg, StdError,
+    StdResult, SubMsgResult, WasmMsg,
+};
+use cw_utils::Expiration;
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub init: u64,
+    /// How far below the highest received nonce an out of order packet is accepted,
+    /// defaults to `MAX_NONCE_WINDOW`
+    pub nonce_window: Option<u64>,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {
+    /// Updates the nonce window if set
+    pub nonce_window: Option<u64>,
 }
 
+/// The messages the chain sends to the tunnel through `sudo`
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(crate = "cosmwasm_schema::serde", untagged)]
+#[schemars(crate = "cosmwasm_schema::schemars")]
+pub enum SudoMsg {
+    /// The ICS-27 packet lifecycle of the native interchain accounts, on a host tunnel
+    Ica(IcaSudoMsg),
+    /// The settings of a remote tunnel changed by governance
+    Governance(GovernanceMsg),
+}
+
+/// The settings of a remote tunnel only the governance of its chain can change
 #[cw_serde]
-pub enum ExecuteMsg {
+pub enum GovernanceMsg {
+    /// Sets the message policy of the proxy `account`,
+    /// or the global policy of the proxies without one if `account` is not set
+    SetMsgPolicy {
+        account: Option<String>,
+        policy: MsgPolicy,
+    },
+    /// Removes the message policy of the proxy `account`, or the global policy
+    RemoveMsgPolicy {
+        account: Option<String>,
+    },
+    /// Sets the spend limit of the proxy `account`, keeping what it spent in the current period
+    SetSpendLimit {
+        account: String,
+        limit: SpendLimit,
+    },
+    RemoveSpendLimit {
+        account: String,
+    },
+}
+
+/// `T` is the custom message type (`CosmosMsg::Custom`) of the remote chain
+#[cw_serde]
+pub enum ExecuteMsg<T = Empty> {
     RemoteInstantiate {
-        inst_msg: CosmosMsg,
+        inst_msg: CosmosMsg<T>,
         job_id: Option<String>,
         code_id: u64,
         channel_id: String,
+        /// The packet timeout in seconds, more than 0, `PACKET_LIFETIME` if not set
+        timeout: Option<u64>,
     },
     RemoteMigrate {
-        migrate_msg: CosmosMsg,
+        migrate_msg: CosmosMsg<T>,
         job_id: Option<String>,
         new_code_id: u64,
         channel_id: String,
+        /// Acknowledge with `StdAck::Error` if the migration fails on the remote chain
+        #[serde(default)]
+        fail_on_error: bool,
+        /// The packet timeout in seconds, more than 0, `PACKET_LIFETIME` if not set
+        timeout: Option<u64>,
     },
     RemoteDispatch {
-        dispatch_msg: CosmosMsg,
+        dispatch_msg: CosmosMsg<T>,
         job_id: Option<String>,
 
         channel_id: String,
+        /// Acknowledge with `StdAck::Error` if the dispatched message fails on the remote chain
+        #[serde(default)]
+        fail_on_error: bool,
+        /// The packet timeout in seconds, more than 0, `PACKET_LIFETIME` if not set
+        timeout: Option<u64>,
     },
     QueryRemoteAddr {
         channel_id: String,
+        /// The packet timeout in seconds, more than 0, `PACKET_LIFETIME` if not set
+        timeout: Option<u64>,
     },
+    /// Registers a native interchain account (ICS-27) for the sender on `connection_id`.
+    /// Once the channel is open, messages are sent with `RemoteDispatch` on that channel
+    /// and must be `CosmosMsg::Stargate`.
+    RegisterInterchainAccount {
+        connection_id: String,
+        counterparty_connection_id: String,
+        job_id: Option<String>,
+    },
+    /// Sends `msg` on its `channel_id` to a remote tunnel which forwards it on along `hops`,
+    /// the channel ids on each intermediate chain. The final ack is returned in the callback.
+    RemoteForward {
+        msg: Box<ExecuteMsg<T>>,
+        hops: Vec<String>,
+    },
+    /// Allows `operator` to send operations on behalf of the sender within the grant,
+    /// replaces any existing grant
+    GrantOperator {
+        operator: String,
+        grant: OperatorGrant,
+    },
+    RevokeOperator {
+        operator: String,
+    },
+    /// Sends `msg` on behalf of `controller`, the sender must be an operator of the controller
+    ExecuteAs {
+        controller: String,
+        msg: Box<ExecuteMsg<T>>,
+    },
+    /// Registers the key the sender signs `SubmitSigned` operations with, replaces any existing key
+    RegisterSigningKey {
+        key: SigningKey,
+    },
+    RevokeSigningKey {},
+    /// Sends the operation in `payload`, the JSON of a `SignedOperation`, on behalf of its controller.
+    /// Any address can submit it, `signature` must be made with the key registered to the controller.
+    SubmitSigned {
+        payload: Binary,
+        signature: Binary,
+    },
+    /// Registers a session key of the sender under `name`, replaces any existing session of that name
+    RegisterSessionKey {
+        name: String,
+        session: SessionKey,
+    },
+    RevokeSessionKey {
+        name: String,
+    },
+    /// Sets the spend limit of the sending proxy on this (remote) tunnel,
+    /// dispatched by its controller. It can only tighten the existing limit, governance can set any
+    SetSpendLimit {
+        limit: SpendLimit,
+    },
+}
+
+/// A key a controller lets sign `RemoteDispatch` operations for it within limits, e.g. held by a wallet UI
+#[cw_serde]
+pub struct SessionKey {
+    pub key: SigningKey,
+    pub expires: Expiration,
+    /// The channels the session can dispatch on
+    pub channels: Vec<String>,
+    /// The kinds of messages the session can dispatch, only kinds whose spend is known, see `CosmosMsgKind::has_known_spend`
+    pub allowed_msgs: Vec<CosmosMsgKind>,
+    /// The most the dispatched messages can spend over the session, see `spent_funds`.
+    /// Denoms that are not listed cannot be spent. Funds count as spent when the dispatch is sent,
+    /// they are not credited back if it fails on the remote chain.
+    pub spend_limit: Vec<Coin>,
+}
+
+/// The most a proxy on a remote tunnel can spend per denom in any rolling period, see `spent_funds`
+#[cw_serde]
+pub struct SpendLimit {
+    /// The maximum amount of each limited denom, other denoms are not limited
+    pub max: Vec<Coin>,
+    /// The length of the rolling period, in seconds
+    pub period: u64,
+}
+
+/// A public key registered to a controller for `SubmitSigned`
+#[cw_serde]
+pub enum SigningKey {
+    /// A compressed or uncompressed secp256k1 key, signing the sha256 hash of the payload
+    Secp256k1(Binary),
+    /// An ed25519 key, signing the payload
+    Ed25519(Binary),
+}
+
+/// An operation signed off-chain by a controller, see `ExecuteMsg::SubmitSigned`
+#[cw_serde]
+pub struct SignedOperation<T = Empty> {
+    pub controller: String,
+    /// The chain id and address of the host tunnel, the operation cannot be submitted to another
+    pub chain_id: String,
+    pub tunnel: String,
+    /// The next signed nonce of the controller, one above `QueryMsg::SignedNonce`,
+    /// or of the session if it is signed with a session key, one above its `SessionKeyResponse::nonce`
+    pub nonce: u64,
+    pub expires: Expiration,
+    pub msg: ExecuteMsg<T>,
+    /// Signed with the session key of this name instead of the signing key of the controller
+    #[serde(default)]
+    pub session: Option<String>,
+}
+
+impl<T: serde::Serialize> SignedOperation<T> {
+    /// The payload to sign and submit
+    pub fn payload(&self) -> StdResult<Binary> {
+        to_binary(self)
+    }
+}
+
+/// The operations an operator can send on behalf of a controller
+#[cw_serde]
+pub enum OperatorScope {
+    /// `RemoteDispatch`
+    Dispatch,
+    /// `RemoteMigrate`
+    Migrate,
+    /// `QueryRemoteAddr`
+    Query,
+}
+
+#[cw_serde]
+pub struct OperatorGrant {
+    pub scopes: Vec<OperatorScope>,
+    pub expires: Option<Expiration>,
+    /// The kinds of messages the operator can dispatch, any kind if `None`
+    pub allowed_msgs: Option<Vec<CosmosMsgKind>>,
+}
+
+/// The kind of a `CosmosMsg`, i.e. its variant
+#[cw_serde]
+#[derive(Copy)]
+pub enum CosmosMsgKind {
+    Bank,
+    Custom,
+    Staking,
+    Distribution,
+    Stargate,
+    Ibc,
+    Wasm,
+    Gov,
+    Other,
+}
+
+impl CosmosMsgKind {
+    /// The kind of `msg`, i.e. its variant
+    pub fn of<T: serde::Serialize>(msg: &CosmosMsg<T>) -> StdResult<Self> {
+        Ok(match msg {
+            CosmosMsg::Bank(_) => CosmosMsgKind::Bank,
+            CosmosMsg::Custom(_) => CosmosMsgKind::Custom,
+            #[cfg(feature = "staking")]
+            CosmosMsg::Staking(_) => CosmosMsgKind::Staking,
+            #[cfg(feature = "staking")]
+            CosmosMsg::Distribution(_) => CosmosMsgKind::Distribution,
+            CosmosMsg::Stargate { .. } => CosmosMsgKind::Stargate,
+            CosmosMsg::Ibc(_) => CosmosMsgKind::Ibc,
+            CosmosMsg::Wasm(_) => CosmosMsgKind::Wasm,
+            CosmosMsg::Gov(_) => CosmosMsgKind::Gov,
+            // another crate of the build may enable the staking variants of cosmwasm-std
+            #[cfg(not(feature = "staking"))]
+            _ => match from_slice::<StakingKind>(&to_vec(msg)?) {
+                Ok(StakingKind::Staking(_)) => CosmosMsgKind::Staking,
+                Ok(StakingKind::Distribution(_)) => CosmosMsgKind::Distribution,
+                Err(_) => CosmosMsgKind::Other,
+            },
+            #[cfg(feature = "staking")]
+            _ => CosmosMsgKind::Other,
+        })
+    }
+
+    /// Whether `spent_funds` knows all the funds a message of this kind can spend,
+    /// it cannot know what `Stargate`, `Custom` and unknown messages spend
+    pub fn has_known_spend(&self) -> bool {
+        !matches!(
+            self,
+            CosmosMsgKind::Stargate | CosmosMsgKind::Custom | CosmosMsgKind::Other
+        )
+    }
+}
+
+#[cfg(not(feature = "staking"))]
+#[derive(serde::Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde", rename_all = "snake_case")]
+enum StakingKind {
+    Staking(serde::de::IgnoredAny),
+    Distribution(serde::de::IgnoredAny),
+}
+
+/// The funds `msg` sends or delegates from the account executing it
+pub fn spent_funds<T: serde::Serialize>(msg: &CosmosMsg<T>) -> StdResult<Vec<Coin>> {
+    let coins = match msg {
+        CosmosMsg::Bank(BankMsg::Send { amount, .. })
+        | CosmosMsg::Bank(BankMsg::Burn { amount }) => amount.clone(),
+        CosmosMsg::Wasm(WasmMsg::Execute { funds, .. })
+        | CosmosMsg::Wasm(WasmMsg::Instantiate { funds, .. }) => funds.clone(),
+        CosmosMsg::Ibc(IbcMsg::Transfer { amount, .. }) => vec![amount.clone()],
+        // staking messages are only variants with the `staking` feature, so they are matched in JSON
+        _ => match from_slice::<StakingSpend>(&to_vec(msg)?) {
+            Ok(StakingSpend::Staking(StakingDelegate::Delegate { amount })) => vec![amount],
+            Err(_) => vec![],
+        },
+    };
+    let mut total: Vec<Coin> = vec![];
+    for coin in coins {
+        match total.iter_mut().find(|c| c.denom == coin.denom) {
+            Some(c) => c.amount += coin.amount,
+            None => total.push(coin),
+        }
+    }
+    Ok(total)
+}
+
+#[derive(serde::Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde", rename_all = "snake_case")]
+enum StakingSpend {
+    Staking(StakingDelegate),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde", rename_all = "snake_case")]
+enum StakingDelegate {
+    Delegate { amount: Coin },
+}
+
+/// The messages a remote tunnel lets the controllers dispatch through their proxies, see `MsgPolicy::check`
+#[cw_serde]
+pub struct MsgPolicy {
+    /// The categories of messages that can be dispatched, any other message is rejected
+    pub allow: Vec<MsgCategory>,
+    /// Rejects the allowed messages matching any of the rules
+    #[serde(default)]
+    pub deny: Vec<DenyRule>,
+}
+
+/// A category of `CosmosMsg` allowed by a `MsgPolicy`
+#[cw_serde]
+pub enum MsgCategory {
+    /// Any `BankMsg`
+    Bank,
+    /// Staking and distribution messages
+    Staking,
+    /// `WasmMsg::Execute` of the listed contracts
+    WasmExecute { contracts: Vec<String> },
+    /// `IbcMsg::Transfer`
+    IbcTransfer,
+}
+
+/// A `MsgPolicy` rule rejecting some of the allowed messages
+#[cw_serde]
+pub enum DenyRule {
+    /// Messages sending funds to or executing the address
+    Recipient { address: String },
+    /// Messages spending the denom, see `spent_funds`
+    Denom { denom: String },
+}
+
+impl MsgPolicy {
+    /// Checks the addresses of the policy are valid, in the normalized form `addr_validate` accepts
+    pub fn validate(&self, api: &dyn Api) -> StdResult<()> {
+        for category in &self.allow {
+            if let MsgCategory::WasmExecute { contracts } = category {
+                for contract in contracts {
+                    api.addr_validate(contract)?;
+                }
+            }
+        }
+        for rule in &self.deny {
+            if let DenyRule::Recipient { address } = rule {
+                api.addr_validate(address)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails with `TunnelError::PolicyViolation` if `msg` is not in an allowed category or matches a deny rule.
+    /// Addresses are compared as accounts, so another casing of a bech32 address matches
+    pub fn check<T: serde::Serialize>(
+        &self,
+        api: &dyn Api,
+        msg: &CosmosMsg<T>,
+    ) -> Result<(), TunnelError> {
+        let kind = CosmosMsgKind::of(msg)?;
+        if !self
+            .allow
+            .iter()
+            .any(|category| category.matches(api, msg, kind))
+        {
+            return Err(TunnelError::PolicyViolation(format!(
+                "{kind:?} message is not allowed"
+            )));
+        }
+        for rule in &self.deny {
+            if rule.matches(api, msg)? {
+                return Err(TunnelError::PolicyViolation(format!(
+                    "{kind:?} message is denied by {rule:?}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the addresses are of the same account, addresses that are not valid are compared as they are
+fn same_account(api: &dyn Api, a: &str, b: &str) -> bool {
+    match (api.addr_canonicalize(a), api.addr_canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+impl MsgCategory {
+    fn matches<T>(&self, api: &dyn Api, msg: &CosmosMsg<T>, kind: CosmosMsgKind) -> bool {
+        match self {
+            MsgCategory::Bank => kind == CosmosMsgKind::Bank,
+            MsgCategory::Staking => {
+                matches!(kind, CosmosMsgKind::Staking | CosmosMsgKind::Distribution)
+            }
+            MsgCategory::WasmExecute { contracts } => match msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) => contracts
+                    .iter()
+                    .any(|contract| same_account(api, contract, contract_addr)),
+                _ => false,
+            },
+            MsgCategory::IbcTransfer => matches!(msg, CosmosMsg::Ibc(IbcMsg::Transfer { .. })),
+        }
+    }
+}
+
+impl DenyRule {
+    fn matches<T: serde::Serialize>(&self, api: &dyn Api, msg: &CosmosMsg<T>) -> StdResult<bool> {
+        Ok(match self {
+            DenyRule::Recipient { address } => match msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, .. })
+                | CosmosMsg::Ibc(IbcMsg::Transfer { to_address, .. }) => {
+                    same_account(api, address, to_address)
+                }
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) => {
+                    same_account(api, address, contract_addr)
+                }
+                _ => false,
+            },
+            DenyRule::Denom { denom } => spent_funds(msg)?.iter().any(|c| &c.denom == denom),
+        })
+    }
 }
 
 #[cw_serde]
@@ -49,6 +463,57 @@ pub enum QueryMsg {
         start_after: Option<(String, String, String)>,
         limit: Option<u64>,
     },
+    /// Returns the native interchain account (ICS-27) of the controller on the connection
+    #[returns(InterchainAccountResponse)]
+    InterchainAccount {
+        controller: String,
+        connection_id: String,
+    },
+    /// Returns the highest nonce received on this (remote) tunnel from the controller
+    #[returns(NonceResponse)]
+    ReceivedNonce {
+        connection_id: String,
+        port_id: String,
+        controller: String,
+    },
+    /// Returns the last nonce sent by this (host) tunnel for the controller on the connection
+    #[returns(NonceResponse)]
+    SentNonce {
+        connection_id: String,
+        controller: String,
+    },
+    /// Returns the grant of the operator for the controller on this (host) tunnel, or none
+    #[returns(OperatorResponse)]
+    Operator {
+        controller: String,
+        operator: String,
+    },
+    #[returns(ListOperatorsResponse)]
+    ListOperators {
+        controller: String,
+        start_after: Option<String>,
+        limit: Option<u64>,
+    },
+    /// Returns the key the controller signs operations with on this (host) tunnel, or none
+    #[returns(SigningKeyResponse)]
+    SigningKey { controller: String },
+    /// Returns the session key of the controller registered under `name` on this (host) tunnel, or none
+    #[returns(SessionKeyResponse)]
+    SessionKey { controller: String, name: String },
+    /// Returns the nonce of the last signed operation of the controller submitted to this (host) tunnel
+    #[returns(NonceResponse)]
+    SignedNonce { controller: String },
+    /// Returns the message policy the dispatches of the proxy `account` are checked against on this (remote) tunnel,
+    /// its own or the global one, or the global policy if `account` is not set
+    #[returns(MsgPolicyResponse)]
+    MsgPolicy { account: Option<String> },
+    /// Returns the spend limit of the proxy `account` on this (remote) tunnel, or none,
+    /// and what it spent in the current period
+    #[returns(SpendLimitResponse)]
+    SpendLimit { account: String },
+    /// Returns the protocol version of the channel and the metadata the counterparty tunnel sent in its handshake
+    #[returns(ChannelMetadataResponse)]
+    ChannelMetadata { channel_id: String },
 }
 
 #[cw_serde]
@@ -61,6 +526,76 @@ pub struct ListAccountsResponse {
     pub accounts: Vec<AccountInfo>,
 }
 
+#[cw_serde]
+pub struct InterchainAccountResponse {
+    /// The controller the interchain account is registered for
+    pub controller: String,
+    /// The owner of the interchain account on the ICA controller module, its port is `icacontroller-{owner}`
+    pub owner: String,
+    /// The ICS-27 channel, once open
+    pub channel_id: Option<String>,
+    /// The interchain account address on the remote chain, once open
+    pub address: Option<String>,
+}
+
+#[cw_serde]
+pub struct NonceResponse {
+    pub nonce: u64,
+}
+
+#[cw_serde]
+pub struct OperatorResponse {
+    pub grant: Option<OperatorGrant>,
+}
+
+#[cw_serde]
+pub struct SigningKeyResponse {
+    pub key: Option<SigningKey>,
+}
+
+#[cw_serde]
+pub struct SessionKeyResponse {
+    pub session: Option<SessionKey>,
+    /// The funds the session has spent
+    pub spent: Vec<Coin>,
+    /// The nonce of the last operation signed with the session key of this name,
+    /// it is not reset when the session is registered again
+    pub nonce: u64,
+}
+
+#[cw_serde]
+pub struct MsgPolicyResponse {
+    /// Any message can be dispatched without a policy
+    pub policy: Option<MsgPolicy>,
+}
+
+#[cw_serde]
+pub struct SpendLimitResponse {
+    pub limit: Option<SpendLimit>,
+    /// The funds spent in the limited denoms over the last period
+    pub spent: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct ListOperatorsResponse {
+    pub operators: Vec<OperatorInfo>,
+}
+
+#[cw_serde]
+pub struct ChannelMetadataResponse {
+    /// The negotiated protocol version
+    pub version: ProtocolVersion,
+    /// The negotiated encoding of packets and acks
+    pub encoding: PacketEncoding,
+    pub metadata: Option<ChannelMetadata>,
+}
+
+#[cw_serde]
+pub struct OperatorInfo {
+    pub operator: String,
+    pub grant: OperatorGrant,
+}
+
 #[cw_serde]
 pub struct AccountInfo {
     pub account: String,
@@ -69,31 +604,222 @@ pub struct AccountInfo {
     pub controller: String,
 }
 
-/// This is the message we send over the IBC channel
+/// The side of a tunnel channel a tunnel plays, a tunnel can be built with either or both
 #[cw_serde]
-pub enum PacketMsg {
+#[derive(Copy)]
+pub enum TunnelRole {
+    /// Sends the operations of the controllers on its chain
+    Host,
+    /// Runs the proxies receiving the operations
+    Remote,
+}
+
+/// This is the message we send over the IBC channel.
+/// The `nonce` of an operation is assigned by the host tunnel, increasing per (connection, controller),
+/// and checked by the remote tunnel against replays.
+#[cw_serde]
+pub enum PacketMsg<T = Empty> {
     Instantiate {
         controller: String,
-        inst_msg: CosmosMsg,
+        inst_msg: CosmosMsg<T>,
         job_id: Option<String>,
         code_id: u64,
+        nonce: u64,
     },
     Migrate {
         controller: String,
-        migration_msg: CosmosMsg,
+        migration_msg: CosmosMsg<T>,
         new_code_id: u64,
         job_id: Option<String>,
+        #[serde(default)]
+        fail_on_error: bool,
+        nonce: u64,
     },
     Dispatch {
         controller: String,
-        msg: CosmosMsg,
+        msg: CosmosMsg<T>,
         job_id: Option<String>,
+        #[serde(default)]
+        fail_on_error: bool,
+        nonce: u64,
     },
     WhoAmI {
         controller: String,
+        nonce: u64,
+    },
+    /// Sends `packet` on along `hops`, the channel ids on each intermediate chain
+    Forward {
+        hops: Vec<String>,
+        /// The chain ids the packet has passed, to detect loops
+        visited: Vec<String>,
+        packet: Box<PacketMsg<T>>,
+    },
+    /// Returns the ack of a forwarded packet towards its origin, one hop at a time
+    ForwardAck {
+        /// The origin path of the controller, see `forwarded_controller`
+        controller: String,
+        job_id: Option<String>,
+        /// The nonce the receiving tunnel sent the forwarded packet with, checked against its record of the forward
+        nonce: u64,
+        ack: StdAck,
+    },
+    /// A part of an encoded packet larger than `MAX_PACKET_SIZE`, the packet is run once all parts are received.
+    /// The ack of the part completing the packet is the ack of the packet.
+    Chunk {
+        controller: String,
+        job_id: Option<String>,
+        /// The operation of the packet
+        operation: TunnelOperation,
+        chunk: PacketChunk,
     },
 }
 
+/// A part of an encoded packet, see `PacketMsg::Chunk`
+#[cw_serde]
+pub struct PacketChunk {
+    /// The sha256 hash of the encoded packet, identifies the upload
+    pub hash: Binary,
+    /// The position of the part, from 0
+    pub index: u32,
+    /// The number of parts of the packet
+    pub total: u32,
+    pub data: Binary,
+}
+
+impl<T: serde::de::DeserializeOwned> PacketMsg<T> {
+    /// Decodes packet data received from the counterparty.
+    /// Packets nested deeper than `MAX_PACKET_DEPTH` are refused before decoding,
+    /// since the decoder recurses into each nested `Forward`
+    pub fn decode(data: &[u8]) -> StdResult<Self> {
+        check_depth(data)?;
+        from_slice(data)
+    }
+}
+
+/// Refuses packet data nested deeper than `MAX_PACKET_DEPTH`
+pub(crate) fn check_depth(data: &[u8]) -> StdResult<()> {
+    if json_depth(data) > MAX_PACKET_DEPTH {
+        return Err(StdError::parse_err(
+            "PacketMsg",
+            format!("nested deeper than {MAX_PACKET_DEPTH}"),
+        ));
+    }
+    Ok(())
+}
+
+impl<T> PacketMsg<T> {
+    /// The controller on the host chain that sent this packet
+    pub fn controller(&self) -> &str {
+        match self {
+            PacketMsg::Instantiate { controller, .. }
+            | PacketMsg::Migrate { controller, .. }
+            | PacketMsg::Dispatch { controller, .. }
+            | PacketMsg::WhoAmI { controller, .. }
+            | PacketMsg::ForwardAck { controller, .. }
+            | PacketMsg::Chunk { controller, .. } => controller,
+            PacketMsg::Forward { packet, .. } => packet.controller(),
+        }
+    }
+
+    /// The replay protection nonce of an operation, chunks do not have one.
+    /// The nonce of a `ForwardAck` is the one of the forwarded packet, not checked against replays
+    pub fn nonce(&self) -> Option<u64> {
+        match self {
+            PacketMsg::Instantiate { nonce, .. }
+            | PacketMsg::Migrate { nonce, .. }
+            | PacketMsg::Dispatch { nonce, .. }
+            | PacketMsg::WhoAmI { nonce, .. } => Some(*nonce),
+            PacketMsg::Forward { packet, .. } => packet.nonce(),
+            PacketMsg::ForwardAck { .. } | PacketMsg::Chunk { .. } => None,
+        }
+    }
+
+    pub fn nonce_mut(&mut self) -> Option<&mut u64> {
+        match self {
+            PacketMsg::Instantiate { nonce, .. }
+            | PacketMsg::Migrate { nonce, .. }
+            | PacketMsg::Dispatch { nonce, .. }
+            | PacketMsg::WhoAmI { nonce, .. } => Some(nonce),
+            PacketMsg::Forward { packet, .. } => packet.nonce_mut(),
+            PacketMsg::ForwardAck { .. } | PacketMsg::Chunk { .. } => None,
+        }
+    }
+
+    /// The job id chosen by the controller, `WhoAmI` does not have one
+    pub fn job_id(&self) -> Option<&String> {
+        match self {
+            PacketMsg::Instantiate { job_id, .. }
+            | PacketMsg::Migrate { job_id, .. }
+            | PacketMsg::Dispatch { job_id, .. }
+            | PacketMsg::ForwardAck { job_id, .. }
+            | PacketMsg::Chunk { job_id, .. } => job_id.as_ref(),
+            PacketMsg::WhoAmI { .. } => None,
+            PacketMsg::Forward { packet, .. } => packet.job_id(),
+        }
+    }
+
+    /// The role of the tunnel that receives this packet, forwarding it on also needs the `Host` role
+    pub fn receiver_role(&self) -> TunnelRole {
+        match self {
+            PacketMsg::ForwardAck { .. } => TunnelRole::Host,
+            _ => TunnelRole::Remote,
+        }
+    }
+
+    /// Replaces the controller of an operation, used to key forwarded packets by their origin
+    pub fn with_controller(mut self, controller: String) -> Self {
+        *self.controller_mut() = controller;
+        self
+    }
+
+    fn controller_mut(&mut self) -> &mut String {
+        match self {
+            PacketMsg::Instantiate { controller, .. }
+            | PacketMsg::Migrate { controller, .. }
+            | PacketMsg::Dispatch { controller, .. }
+            | PacketMsg::WhoAmI { controller, .. }
+            | PacketMsg::ForwardAck { controller, .. }
+            | PacketMsg::Chunk { controller, .. } => controller,
+            PacketMsg::Forward { packet, .. } => packet.controller_mut(),
+        }
+    }
+}
+
+/// The maximum nesting of objects and arrays in the JSON bytes, ignoring brackets in strings
+pub(crate) fn json_depth(data: &[u8]) -> usize {
+    let (mut depth, mut max) = (0usize, 0usize);
+    let (mut in_string, mut escaped) = (false, false);
+    for byte in data {
+        match byte {
+            _ if escaped => escaped = false,
+            b'\\' if in_string => escaped = true,
+            b'"' => in_string = !in_string,
+            b'{' | b'[' if !in_string => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            b'}' | b']' if !in_string => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max
+}
+
+/// The controller of a forwarded packet on the next chain: the trusted origin (connection, port)
+/// of the packet on this chain prefixed to its controller.
+/// Addresses cannot contain `/`, so this cannot collide with a controller on the host chain.
+pub fn forwarded_controller(connection_id: &str, port_id: &str, controller: &str) -> String {
+    format!("{connection_id}/{port_id}/{controller}")
+}
+
+/// Splits a forwarded controller into the origin (connection, port) on this chain and the controller there,
+/// `None` for controllers on this chain
+pub fn split_forwarded_controller(controller: &str) -> Option<(&str, &str, &str)> {
+    let (connection_id, rest) = controller.split_once('/')?;
+    let (port_id, controller) = rest.split_once('/')?;
+    Some((connection_id, port_id, controller))
+}
+
 /// This is a generic ICS acknowledgement format.
 /// Proto defined here: https://github.com/cosmos/cosmos-sdk/blob/v0.42.0/proto/ibc/core/channel/v1/channel.proto#L141-L147
 /// If ibc_receive_packet returns Err(), then x/wasm runtime will rollback the state and return an error message in this format
@@ -105,9 +831,9 @@ pub enum StdAck {
 
 impl StdAck {
     // create a serialized success message
-    pub fn success(data: impl serde::Serialize) -> Binary {
-        let res = to_binary(&data).unwrap();
-        StdAck::Result(res).ack()
+    pub fn success(data: impl serde::Serialize) -> StdResult<Binary> {
+        let res = to_binary(&data)?;
+        Ok(StdAck::Result(res).ack())
     }
 
     // create a serialized error message
@@ -115,10 +841,40 @@ impl StdAck {
         StdAck::Error(err).ack()
     }
 
+    // create an error with a structured reason (JSON encoded in the error string)
+    pub fn error_with(reason: &impl serde::Serialize) -> StdResult<StdAck> {
+        let err = String::from_utf8(to_vec(reason)?).map_err(StdError::invalid_utf8)?;
+        Ok(StdAck::Error(err))
+    }
+
+    // create a serialized error message with a structured reason
+    pub fn fail_with(reason: &impl serde::Serialize) -> StdResult<Binary> {
+        Ok(StdAck::error_with(reason)?.ack())
+    }
+
     pub fn ack(&self) -> Binary {
+        // serializing an enum of a binary or a string cannot fail
         to_binary(self).unwrap()
     }
 
+    /// Returns the result data or the error string, without panicking
+    pub fn into_result(self) -> Result<Binary, String> {
+        match self {
+            StdAck::Result(data) => Ok(data),
+            StdAck::Error(err) => Err(err),
+        }
+    }
+
+    /// Decodes the result data into `T`,
+    /// an error ack is returned as `AckError::Remote` with its structured reason
+    pub fn try_decode<T: serde::de::DeserializeOwned>(&self) -> Result<T, AckError> {
+        match self {
+            StdAck::Result(data) => Ok(from_slice(data)?),
+            StdAck::Error(err) => Err(AckError::Remote(ErrorResponse::from_ack_error(err))),
+        }
+    }
+
+    /// Panics on `StdAck::Error`, use `into_result` in contracts
     pub fn unwrap(self) -> Binary {
         match self {
             StdAck::Result(data) => data,
@@ -126,6 +882,7 @@ impl StdAck {
         }
     }
 
+    /// Panics on `StdAck::Error` or invalid data, use `try_decode` in contracts
     pub fn unwrap_into<T: serde::de::DeserializeOwned>(self) -> T {
         from_slice(&self.unwrap()).unwrap()
     }
@@ -152,6 +909,76 @@ pub struct DispatchMigrateResponse {
     pub job_id: Option<String>,
 }
 
+/// This is the success response we send on ack for PacketMsg::Forward and PacketMsg::ForwardAck,
+/// the ack of the forwarded operation is returned later
+#[cw_serde]
+pub struct ForwardResponse {
+    pub job_id: Option<String>,
+}
+
+/// This is the success response we send on ack for a PacketMsg::Chunk that does not complete its packet
+#[cw_serde]
+pub struct ChunkResponse {
+    /// The number of parts received so far
+    pub received: u32,
+    pub total: u32,
+    pub job_id: Option<String>,
+}
+
+/// This is the reason we send in `StdAck::Error` when a packet fails on the remote chain
+#[cw_serde]
+pub struct ErrorResponse {
+    pub code: TunnelErrorCode,
+    pub message: String,
+    pub job_id: Option<String>,
+}
+
+impl ErrorResponse {
+    /// Parses the reason of a `StdAck::Error`,
+    /// errors that are not structured (i.e. from the runtime) are returned as `TunnelErrorCode::Unknown`
+    pub fn from_ack_error(err: &str) -> ErrorResponse {
+        from_slice(err.as_bytes()).unwrap_or_else(|_| ErrorResponse {
+            code: TunnelErrorCode::Unknown,
+            message: err.to_string(),
+            job_id: None,
+        })
+    }
+}
+
+/// The reason a packet failed, returned to the host in `ErrorResponse`
+#[cw_serde]
+#[derive(Copy)]
+pub enum TunnelErrorCode {
+    /// The packet data could not be decoded
+    InvalidPacket,
+    /// There is no proxy account for the controller
+    AccountNotFound,
+    /// There is already a proxy account for the controller
+    AccountAlreadyExists,
+    /// The connection of the receiving channel could not be found
+    InvalidConnection,
+    /// The controller is not allowed to perform this operation
+    Unauthorized,
+    /// The dispatched or migrated submessage failed on the remote chain
+    ExecutionFailed,
+    /// The packet timed out before it was received on the remote chain
+    Timeout,
+    /// The acknowledgement could not be decoded on the host chain
+    InvalidAck,
+    /// The forward route is invalid, too long or has a loop
+    InvalidRoute,
+    /// The nonce has already been received or is too old
+    InvalidNonce,
+    /// The receiving tunnel is not built with the role the packet is meant for
+    WrongRole,
+    /// The dispatched message is not allowed by the message policy of the remote tunnel
+    PolicyViolation,
+    /// The dispatched message would exceed the spend limit of the proxy
+    SpendLimitExceeded,
+    /// Any other error
+    Unknown,
+}
+
 /// This is the success response we send on ack for PacketMsg::WhoAmI.
 /// Return the caller's account address on the remote chain
 #[cw_serde]
@@ -0,0 +1,376 @@
+//! The protobuf encoding of packets and acks, see `proto/cw_tunnel/v2/packet.proto`
+use cosmwasm_schema::serde::{de::DeserializeOwned, Serialize};
+use cosmwasm_std::{from_slice, to_vec, Binary, CosmosMsg, StdError, StdResult};
+use prost::{Message, Oneof};
+
+use crate::msg::json_depth;
+use crate::{PacketChunk, PacketMsg, StdAck, TunnelOperation, MAX_PACKET_DEPTH};
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoPacketMsg {
+    #[prost(oneof = "Packet", tags = "1, 2, 3, 4, 5, 6, 7")]
+    packet: Option<Packet>,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+enum Packet {
+    #[prost(message, tag = "1")]
+    Instantiate(Instantiate),
+    #[prost(message, tag = "2")]
+    Migrate(Migrate),
+    #[prost(message, tag = "3")]
+    Dispatch(Dispatch),
+    #[prost(message, tag = "4")]
+    WhoAmI(WhoAmI),
+    #[prost(message, tag = "5")]
+    Forward(Box<Forward>),
+    #[prost(message, tag = "6")]
+    ForwardAck(ForwardAck),
+    #[prost(message, tag = "7")]
+    Chunk(Chunk),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Instantiate {
+    #[prost(string, tag = "1")]
+    controller: String,
+    #[prost(bytes, tag = "2")]
+    inst_msg: Vec<u8>,
+    #[prost(string, optional, tag = "3")]
+    job_id: Option<String>,
+    #[prost(uint64, tag = "4")]
+    code_id: u64,
+    #[prost(uint64, tag = "5")]
+    nonce: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Migrate {
+    #[prost(string, tag = "1")]
+    controller: String,
+    #[prost(bytes, tag = "2")]
+    migration_msg: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    new_code_id: u64,
+    #[prost(string, optional, tag = "4")]
+    job_id: Option<String>,
+    #[prost(bool, tag = "5")]
+    fail_on_error: bool,
+    #[prost(uint64, tag = "6")]
+    nonce: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Dispatch {
+    #[prost(string, tag = "1")]
+    controller: String,
+    #[prost(bytes, tag = "2")]
+    msg: Vec<u8>,
+    #[prost(string, optional, tag = "3")]
+    job_id: Option<String>,
+    #[prost(bool, tag = "4")]
+    fail_on_error: bool,
+    #[prost(uint64, tag = "5")]
+    nonce: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct WhoAmI {
+    #[prost(string, tag = "1")]
+    controller: String,
+    #[prost(uint64, tag = "2")]
+    nonce: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Forward {
+    #[prost(string, repeated, tag = "1")]
+    hops: Vec<String>,
+    #[prost(string, repeated, tag = "2")]
+    visited: Vec<String>,
+    #[prost(message, optional, tag = "3")]
+    packet: Option<ProtoPacketMsg>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ForwardAck {
+    #[prost(string, tag = "1")]
+    controller: String,
+    #[prost(string, optional, tag = "2")]
+    job_id: Option<String>,
+    #[prost(message, optional, tag = "3")]
+    ack: Option<ProtoAck>,
+    #[prost(uint64, tag = "4")]
+    nonce: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Chunk {
+    #[prost(string, tag = "1")]
+    controller: String,
+    #[prost(string, optional, tag = "2")]
+    job_id: Option<String>,
+    #[prost(int32, tag = "3")]
+    operation: i32,
+    #[prost(bytes, tag = "4")]
+    hash: Vec<u8>,
+    #[prost(uint32, tag = "5")]
+    index: u32,
+    #[prost(uint32, tag = "6")]
+    total: u32,
+    #[prost(bytes, tag = "7")]
+    data: Vec<u8>,
+}
+
+/// The `Operation` enum of the proto file, in the order of `TunnelOperation`
+const OPERATIONS: [TunnelOperation; 6] = [
+    TunnelOperation::Instantiate,
+    TunnelOperation::Migrate,
+    TunnelOperation::Dispatch,
+    TunnelOperation::WhoAmI,
+    TunnelOperation::Forward,
+    TunnelOperation::Chunk,
+];
+
+fn operation_to_proto(operation: TunnelOperation) -> i32 {
+    OPERATIONS
+        .iter()
+        .position(|op| *op == operation)
+        .expect("all operations are listed") as i32
+}
+
+fn operation_from_proto(operation: i32) -> StdResult<TunnelOperation> {
+    usize::try_from(operation)
+        .ok()
+        .and_then(|index| OPERATIONS.get(index).copied())
+        .ok_or_else(|| parse_err(format!("unknown operation {operation}")))
+}
+
+/// `ibc.core.channel.v1.Acknowledgement`
+#[derive(Clone, PartialEq, Message)]
+struct ProtoAck {
+    #[prost(oneof = "Response", tags = "21, 22")]
+    response: Option<Response>,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+enum Response {
+    #[prost(bytes, tag = "21")]
+    Result(Vec<u8>),
+    #[prost(string, tag = "22")]
+    Error(String),
+}
+
+fn parse_err(err: impl ToString) -> StdError {
+    StdError::parse_err("PacketMsg", err.to_string())
+}
+
+fn missing(field: &str) -> StdError {
+    parse_err(format!("missing {field}"))
+}
+
+impl From<&StdAck> for ProtoAck {
+    fn from(ack: &StdAck) -> Self {
+        let response = match ack {
+            StdAck::Result(data) => Response::Result(data.to_vec()),
+            StdAck::Error(err) => Response::Error(err.clone()),
+        };
+        ProtoAck {
+            response: Some(response),
+        }
+    }
+}
+
+impl TryFrom<ProtoAck> for StdAck {
+    type Error = StdError;
+
+    fn try_from(ack: ProtoAck) -> StdResult<Self> {
+        match ack.response.ok_or_else(|| missing("ack response"))? {
+            Response::Result(data) => Ok(StdAck::Result(data.into())),
+            Response::Error(err) => Ok(StdAck::Error(err)),
+        }
+    }
+}
+
+fn encode_msg<T: Serialize>(msg: &CosmosMsg<T>) -> StdResult<Vec<u8>> {
+    to_vec(msg)
+}
+
+/// The messages are JSON, refused like JSON packets when nested deeper than `MAX_PACKET_DEPTH`
+fn decode_msg<T: DeserializeOwned>(data: &[u8], depth: usize) -> StdResult<CosmosMsg<T>> {
+    if depth + json_depth(data) > MAX_PACKET_DEPTH {
+        return Err(parse_err(format!("nested deeper than {MAX_PACKET_DEPTH}")));
+    }
+    from_slice(data)
+}
+
+fn to_proto<T: Serialize>(packet: &PacketMsg<T>) -> StdResult<ProtoPacketMsg> {
+    let packet = match packet {
+        PacketMsg::Instantiate {
+            controller,
+            inst_msg,
+            job_id,
+            code_id,
+            nonce,
+        } => Packet::Instantiate(Instantiate {
+            controller: controller.clone(),
+            inst_msg: encode_msg(inst_msg)?,
+            job_id: job_id.clone(),
+            code_id: *code_id,
+            nonce: *nonce,
+        }),
+        PacketMsg::Migrate {
+            controller,
+            migration_msg,
+            new_code_id,
+            job_id,
+            fail_on_error,
+            nonce,
+        } => Packet::Migrate(Migrate {
+            controller: controller.clone(),
+            migration_msg: encode_msg(migration_msg)?,
+            new_code_id: *new_code_id,
+            job_id: job_id.clone(),
+            fail_on_error: *fail_on_error,
+            nonce: *nonce,
+        }),
+        PacketMsg::Dispatch {
+            controller,
+            msg,
+            job_id,
+            fail_on_error,
+            nonce,
+        } => Packet::Dispatch(Dispatch {
+            controller: controller.clone(),
+            msg: encode_msg(msg)?,
+            job_id: job_id.clone(),
+            fail_on_error: *fail_on_error,
+            nonce: *nonce,
+        }),
+        PacketMsg::WhoAmI { controller, nonce } => Packet::WhoAmI(WhoAmI {
+            controller: controller.clone(),
+            nonce: *nonce,
+        }),
+        PacketMsg::Forward {
+            hops,
+            visited,
+            packet,
+        } => Packet::Forward(Box::new(Forward {
+            hops: hops.clone(),
+            visited: visited.clone(),
+            packet: Some(to_proto(packet)?),
+        })),
+        PacketMsg::ForwardAck {
+            controller,
+            job_id,
+            nonce,
+            ack,
+        } => Packet::ForwardAck(ForwardAck {
+            controller: controller.clone(),
+            job_id: job_id.clone(),
+            ack: Some(ack.into()),
+            nonce: *nonce,
+        }),
+        PacketMsg::Chunk {
+            controller,
+            job_id,
+            operation,
+            chunk,
+        } => Packet::Chunk(Chunk {
+            controller: controller.clone(),
+            job_id: job_id.clone(),
+            operation: operation_to_proto(*operation),
+            hash: chunk.hash.to_vec(),
+            index: chunk.index,
+            total: chunk.total,
+            data: chunk.data.to_vec(),
+        }),
+    };
+    Ok(ProtoPacketMsg {
+        packet: Some(packet),
+    })
+}
+
+/// `depth` counts the nesting like the JSON encoding, two levels per `Forward`
+fn from_proto<T: DeserializeOwned>(
+    packet: ProtoPacketMsg,
+    depth: usize,
+) -> StdResult<PacketMsg<T>> {
+    let depth = depth + 2;
+    let packet = match packet.packet.ok_or_else(|| missing("packet"))? {
+        Packet::Instantiate(packet) => PacketMsg::Instantiate {
+            controller: packet.controller,
+            inst_msg: decode_msg(&packet.inst_msg, depth)?,
+            job_id: packet.job_id,
+            code_id: packet.code_id,
+            nonce: packet.nonce,
+        },
+        Packet::Migrate(packet) => PacketMsg::Migrate {
+            controller: packet.controller,
+            migration_msg: decode_msg(&packet.migration_msg, depth)?,
+            new_code_id: packet.new_code_id,
+            job_id: packet.job_id,
+            fail_on_error: packet.fail_on_error,
+            nonce: packet.nonce,
+        },
+        Packet::Dispatch(packet) => PacketMsg::Dispatch {
+            controller: packet.controller,
+            msg: decode_msg(&packet.msg, depth)?,
+            job_id: packet.job_id,
+            fail_on_error: packet.fail_on_error,
+            nonce: packet.nonce,
+        },
+        Packet::WhoAmI(packet) => PacketMsg::WhoAmI {
+            controller: packet.controller,
+            nonce: packet.nonce,
+        },
+        Packet::Forward(forward) => {
+            if depth + 2 > MAX_PACKET_DEPTH {
+                return Err(parse_err(format!("nested deeper than {MAX_PACKET_DEPTH}")));
+            }
+            let packet = forward.packet.ok_or_else(|| missing("forwarded packet"))?;
+            PacketMsg::Forward {
+                hops: forward.hops,
+                visited: forward.visited,
+                packet: Box::new(from_proto(packet, depth)?),
+            }
+        }
+        Packet::ForwardAck(packet) => PacketMsg::ForwardAck {
+            controller: packet.controller,
+            job_id: packet.job_id,
+            nonce: packet.nonce,
+            ack: packet.ack.ok_or_else(|| missing("ack"))?.try_into()?,
+        },
+        Packet::Chunk(packet) => PacketMsg::Chunk {
+            controller: packet.controller,
+            job_id: packet.job_id,
+            operation: operation_from_proto(packet.operation)?,
+            chunk: PacketChunk {
+                hash: packet.hash.into(),
+                index: packet.index,
+                total: packet.total,
+                data: packet.data.into(),
+            },
+        },
+    };
+    Ok(packet)
+}
+
+pub(crate) fn encode_packet<T: Serialize>(packet: &PacketMsg<T>) -> StdResult<Binary> {
+    Ok(to_proto(packet)?.encode_to_vec().into())
+}
+
+pub(crate) fn decode_packet<T: DeserializeOwned>(data: &[u8]) -> StdResult<PacketMsg<T>> {
+    let packet = ProtoPacketMsg::decode(data).map_err(parse_err)?;
+    from_proto(packet, 0)
+}
+
+pub(crate) fn encode_ack(ack: &StdAck) -> Binary {
+    ProtoAck::from(ack).encode_to_vec().into()
+}
+
+pub(crate) fn decode_ack(data: &[u8]) -> StdResult<StdAck> {
+    ProtoAck::decode(data)
+        .map_err(|err| StdError::parse_err("StdAck", err.to_string()))?
+        .try_into()
+}
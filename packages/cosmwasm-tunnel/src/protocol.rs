@@ -0,0 +1,201 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_schema::serde::{de::DeserializeOwned, Serialize};
+use cosmwasm_std::{from_slice, to_binary, Binary, StdResult};
+
+use crate::{proto, v1};
+use crate::{
+    ChannelError, ChannelMetadata, ErrorResponse, PacketEncoding, PacketMsg, StdAck,
+    TunnelOperation, IBC_APP_VERSION, LATEST_APP_VERSION,
+};
+
+/// The versions of the tunnel protocol, the version of a channel is negotiated in its handshake
+#[cw_serde]
+#[derive(Copy, PartialOrd, Ord, Eq)]
+pub enum ProtocolVersion {
+    /// The version is the bare `cw-tunnel-v1` string
+    V1,
+    /// The version is `ChannelMetadata`
+    V2,
+}
+
+/// The versions this tunnel speaks, by preference
+pub const SUPPORTED_VERSIONS: [ProtocolVersion; 2] = [ProtocolVersion::V2, ProtocolVersion::V1];
+
+impl ProtocolVersion {
+    pub const LATEST: ProtocolVersion = ProtocolVersion::V2;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProtocolVersion::V1 => IBC_APP_VERSION,
+            ProtocolVersion::V2 => LATEST_APP_VERSION,
+        }
+    }
+
+    /// Whether packets of this version carry the operation
+    pub fn supports(&self, operation: TunnelOperation) -> bool {
+        match self {
+            ProtocolVersion::V1 => v1::supports(operation),
+            ProtocolVersion::V2 => true,
+        }
+    }
+
+    /// The supported version named `version`
+    pub fn parse(version: &str) -> Option<ProtocolVersion> {
+        SUPPORTED_VERSIONS
+            .into_iter()
+            .find(|supported| supported.as_str() == version)
+    }
+}
+
+impl PacketEncoding {
+    /// Encodes a packet sent on a channel with this encoding
+    pub fn encode_packet<T: Serialize>(&self, packet: &PacketMsg<T>) -> StdResult<Binary> {
+        match self {
+            PacketEncoding::Json => to_binary(packet),
+            PacketEncoding::Proto => proto::encode_packet(packet),
+        }
+    }
+
+    /// Decodes a packet received on a channel with this encoding
+    pub fn decode_packet<T: DeserializeOwned>(&self, data: &[u8]) -> StdResult<PacketMsg<T>> {
+        match self {
+            PacketEncoding::Json => PacketMsg::decode(data),
+            PacketEncoding::Proto => proto::decode_packet(data),
+        }
+    }
+
+    /// Encodes the ack of a packet received on a channel with this encoding
+    pub fn encode_ack(&self, ack: &StdAck) -> Binary {
+        match self {
+            PacketEncoding::Json => ack.ack(),
+            PacketEncoding::Proto => proto::encode_ack(ack),
+        }
+    }
+
+    /// Decodes the ack of a packet sent on a channel with this encoding
+    pub fn decode_ack(&self, data: &[u8]) -> StdResult<StdAck> {
+        match self {
+            PacketEncoding::Json => from_slice(data),
+            PacketEncoding::Proto => proto::decode_ack(data),
+        }
+    }
+
+    /// A success ack with the JSON encoded `data`, as `StdAck::success`
+    pub fn success(&self, data: &impl Serialize) -> StdResult<Binary> {
+        Ok(self.encode_ack(&StdAck::Result(to_binary(data)?)))
+    }
+
+    /// An error ack with a structured reason, as `StdAck::fail_with`
+    pub fn fail_with(&self, reason: &impl Serialize) -> StdResult<Binary> {
+        Ok(self.encode_ack(&StdAck::error_with(reason)?))
+    }
+}
+
+/// How the packets and acks of a channel are written, by its protocol version and encoding.
+/// `cw-tunnel-v1` channels carry the packets of `v1`, without nonces, and plain string errors.
+#[cw_serde]
+#[derive(Copy)]
+pub struct PacketCodec {
+    pub version: ProtocolVersion,
+    pub encoding: PacketEncoding,
+}
+
+impl Default for PacketCodec {
+    fn default() -> Self {
+        PacketCodec {
+            version: ProtocolVersion::LATEST,
+            encoding: PacketEncoding::default(),
+        }
+    }
+}
+
+impl PacketCodec {
+    /// Whether the packets carry nonces, checked against replays
+    pub fn has_nonces(&self) -> bool {
+        self.version != ProtocolVersion::V1
+    }
+
+    /// Encodes a packet sent on the channel
+    pub fn encode_packet<T: Serialize>(&self, packet: &PacketMsg<T>) -> StdResult<Binary> {
+        match self.version {
+            ProtocolVersion::V1 => v1::encode_packet(packet),
+            _ => self.encoding.encode_packet(packet),
+        }
+    }
+
+    /// Decodes a packet received on the channel, the packets of `cw-tunnel-v1` have a nonce of 0
+    pub fn decode_packet<T: DeserializeOwned>(&self, data: &[u8]) -> StdResult<PacketMsg<T>> {
+        match self.version {
+            ProtocolVersion::V1 => v1::decode_packet(data),
+            _ => self.encoding.decode_packet(data),
+        }
+    }
+
+    /// Encodes the ack of a packet received on the channel
+    pub fn encode_ack(&self, ack: &StdAck) -> Binary {
+        self.encoding.encode_ack(ack)
+    }
+
+    /// Decodes the ack of a packet sent on the channel
+    pub fn decode_ack(&self, data: &[u8]) -> StdResult<StdAck> {
+        self.encoding.decode_ack(data)
+    }
+
+    /// A success ack with the JSON encoded `data`, as `StdAck::success`
+    pub fn success(&self, data: &impl Serialize) -> StdResult<Binary> {
+        self.encoding.success(data)
+    }
+
+    /// An error ack, with the structured reason or only its message on `cw-tunnel-v1` channels
+    pub fn fail_with(&self, err: &ErrorResponse) -> StdResult<Binary> {
+        match self.version {
+            ProtocolVersion::V1 => Ok(self.encode_ack(&StdAck::Error(err.message.clone()))),
+            _ => self.encoding.fail_with(err),
+        }
+    }
+}
+
+/// The outcome of a channel handshake
+#[derive(Clone, Debug, PartialEq)]
+pub struct Negotiated {
+    pub version: ProtocolVersion,
+    /// The encoding of the packets and acks, `cw-tunnel-v1` channels are JSON
+    pub encoding: PacketEncoding,
+    /// The metadata the counterparty sent, `cw-tunnel-v1` tunnels send none
+    pub counterparty: Option<ChannelMetadata>,
+}
+
+/// Negotiates the protocol of a channel with the version the counterparty sent,
+/// the encoding is the one the channel was opened with
+pub fn negotiate_version(counterparty_version: &str) -> Result<Negotiated, ChannelError> {
+    if counterparty_version == ProtocolVersion::V1.as_str() {
+        return Ok(Negotiated {
+            version: ProtocolVersion::V1,
+            encoding: PacketEncoding::Json,
+            counterparty: None,
+        });
+    }
+    let metadata = ChannelMetadata::parse(counterparty_version)?;
+    Ok(Negotiated {
+        version: metadata.protocol_version()?,
+        encoding: metadata.encoding,
+        counterparty: Some(metadata),
+    })
+}
+
+/// The channel version this tunnel answers with, the bare version for `cw-tunnel-v1` channels
+pub fn channel_version(
+    version: ProtocolVersion,
+    encoding: PacketEncoding,
+    metadata: &ChannelMetadata,
+) -> StdResult<String> {
+    match version {
+        ProtocolVersion::V1 => Ok(version.as_str().to_string()),
+        _ => ChannelMetadata {
+            version: version.as_str().to_string(),
+            encoding,
+            ..metadata.clone()
+        }
+        .to_version(),
+    }
+}
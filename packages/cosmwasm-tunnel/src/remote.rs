@@ -0,0 +1,535 @@
+use std::fmt::Display;
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_schema::serde::{de::DeserializeOwned, Serialize};
+use cosmwasm_std::{
+    to_binary, wasm_execute, Addr, Binary, ChannelResponse, CosmosMsg, Deps, DepsMut, Env,
+    IbcPacketReceiveMsg, IbcQuery, IbcReceiveResponse, QueryRequest, Reply, Response, StdError,
+    Storage, SubMsg, SubMsgResult, WasmMsg,
+};
+use cw_utils::parse_reply_instantiate_data;
+
+use crate::events::ProxyInstantiated;
+use crate::{
+    DispatchMigrateResponse, ErrorResponse, InstantiateResponse, PacketChunk, PacketCodec,
+    PacketMsg, StdAck, TunnelError, TunnelErrorCode, TunnelOperation, WhoAmIResponse,
+};
+
+pub const RECEIVE_DISPATCH_ID: u64 = 1234;
+pub const INIT_CALLBACK_ID: u64 = 7890;
+pub const MIGRATE_CALLBACK_ID: u64 = 7899;
+
+/// Where a received packet comes from
+#[cw_serde]
+pub struct PacketSource {
+    /// The channel on this chain the packet is received on
+    pub channel_id: String,
+    /// Sender's light client id on this chain
+    pub connection_id: String,
+    /// Sender's module / cw contract id
+    pub port_id: String,
+    /// The protocol version and encoding of the channel, the ack is written in
+    pub codec: PacketCodec,
+}
+
+#[cw_serde]
+pub struct SenderInfo {
+    /// Sender's light client id on this chain
+    pub connection_id: String,
+    /// Sender's module / cw contract id
+    pub port_id: String,
+    /// Sender's account on the remote chain
+    pub controller: String,
+    /// Job id for ref on the sending chain
+    pub job_id: Option<String>,
+    /// Acknowledge with an error if the submessage fails
+    pub fail_on_error: bool,
+    /// The protocol version and encoding of the channel, the ack is written in
+    #[serde(default)]
+    pub codec: PacketCodec,
+}
+
+/// The remote side of a tunnel: runs the operations of the controllers on other chains through their proxies.
+/// Contracts embedding the tunnel implement the hooks and call the handlers from their entry points.
+pub trait TunnelRemote<T: Serialize + DeserializeOwned = cosmwasm_std::Empty> {
+    type Error: From<StdError> + From<TunnelError> + Display;
+
+    /// The reply ids of the submessages of received packets
+    const INIT_CALLBACK_ID: u64 = INIT_CALLBACK_ID;
+    const RECEIVE_DISPATCH_ID: u64 = RECEIVE_DISPATCH_ID;
+    const MIGRATE_CALLBACK_ID: u64 = MIGRATE_CALLBACK_ID;
+
+    /// The proxy of `controller` sending through (connection, port)
+    fn account(
+        &self,
+        deps: Deps,
+        connection_id: &str,
+        port_id: &str,
+        controller: &str,
+    ) -> Result<Option<Addr>, Self::Error>;
+
+    /// Saves the proxy instantiated for `sender`
+    fn save_account(
+        &self,
+        deps: DepsMut,
+        sender: &SenderInfo,
+        account: &Addr,
+    ) -> Result<(), Self::Error>;
+
+    /// Saves the sender of the packet whose submessage is running, for the ack in `reply`
+    fn save_pending(
+        &self,
+        storage: &mut dyn Storage,
+        sender: &SenderInfo,
+    ) -> Result<(), Self::Error>;
+
+    /// Loads and removes the sender saved by `save_pending`
+    fn take_pending(&self, storage: &mut dyn Storage) -> Result<SenderInfo, Self::Error>;
+
+    /// Checks the nonce of `controller` against replays and records it
+    fn check_nonce(
+        &self,
+        deps: DepsMut,
+        source: &PacketSource,
+        controller: &str,
+        nonce: u64,
+    ) -> Result<(), Self::Error>;
+
+    /// Checks `packet` may be run, before its nonce is recorded
+    fn authorize(
+        &self,
+        _deps: Deps,
+        _source: &PacketSource,
+        _packet: &PacketMsg<T>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Checks `msg` may be dispatched through the proxy `account`, before its submessage is sent
+    fn check_dispatch(
+        &self,
+        _deps: DepsMut,
+        _env: &Env,
+        _sender: &SenderInfo,
+        _account: &Addr,
+        _msg: &CosmosMsg<T>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Checks the proxy `account` may be migrated to `new_code_id` with `migrate_msg`, before its submessage is sent
+    fn check_migrate(
+        &self,
+        _deps: Deps,
+        _sender: &SenderInfo,
+        _account: &Addr,
+        _migrate_msg: &CosmosMsg<T>,
+        _new_code_id: u64,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The protocol version and packet encoding negotiated for the channel,
+    /// the latest version in JSON unless the channel was opened with others
+    fn codec(&self, _storage: &dyn Storage, _channel_id: &str) -> Result<PacketCodec, Self::Error> {
+        Ok(PacketCodec::default())
+    }
+
+    /// The code of `err` in the error ack
+    fn error_code(&self, _err: &Self::Error) -> TunnelErrorCode {
+        TunnelErrorCode::Unknown
+    }
+
+    /// The connection of the channel the packet is received on
+    fn connection_id(
+        &self,
+        deps: Deps,
+        channel_id: String,
+        port_id: String,
+    ) -> Result<String, Self::Error> {
+        let channel_resp: ChannelResponse =
+            deps.querier.query(&QueryRequest::Ibc(IbcQuery::Channel {
+                channel_id,
+                port_id: Some(port_id),
+            }))?;
+        Ok(channel_resp
+            .channel
+            .ok_or(TunnelError::InvalidConnectionId)?
+            .connection_id)
+    }
+
+    /// Handles `ibc_packet_receive`, a failing packet is acknowledged with an `ErrorResponse`
+    fn receive(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketReceiveMsg,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        let codec = self
+            .codec(deps.storage, &msg.packet.dest.channel_id)
+            .unwrap_or_default();
+        // the job id of the packet, for the error ack once it is decoded
+        let mut job_id = None;
+        let res = (|| {
+            let packet = msg.packet;
+            let msg: PacketMsg<T> = codec.decode_packet(&packet.data)?;
+            job_id = msg.job_id().cloned();
+            let source = PacketSource {
+                connection_id: self.connection_id(
+                    deps.as_ref(),
+                    packet.dest.channel_id.clone(),
+                    packet.dest.port_id,
+                )?,
+                channel_id: packet.dest.channel_id,
+                port_id: packet.src.port_id,
+                codec,
+            };
+            self.receive_decoded(deps, env, source, msg)
+        })();
+        res.or_else(|e| {
+            let ack = codec.fail_with(&ErrorResponse {
+                code: self.error_code(&e),
+                message: format!("IBC Packet Error: {e}"),
+                job_id,
+            })?;
+            Ok(IbcReceiveResponse::new().set_ack(ack))
+        })
+    }
+
+    /// Authorizes the decoded packet and checks its nonce before running it,
+    /// also runs the packets assembled from their chunks
+    fn receive_decoded(
+        &self,
+        mut deps: DepsMut,
+        env: Env,
+        source: PacketSource,
+        msg: PacketMsg<T>,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        self.authorize(deps.as_ref(), &source, &msg)?;
+        // `cw-tunnel-v1` packets have no nonce to check
+        if let Some(nonce) = msg.nonce().filter(|_| source.codec.has_nonces()) {
+            self.check_nonce(deps.branch(), &source, msg.controller(), nonce)?;
+        }
+        self.receive_packet(deps, env, source, msg)
+    }
+
+    /// Runs the decoded packet
+    fn receive_packet(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        source: PacketSource,
+        msg: PacketMsg<T>,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        match msg {
+            PacketMsg::Instantiate {
+                controller,
+                inst_msg,
+                job_id,
+                code_id,
+                ..
+            } => {
+                let sender = SenderInfo {
+                    connection_id: source.connection_id,
+                    port_id: source.port_id,
+                    controller,
+                    job_id,
+                    fail_on_error: false,
+                    codec: source.codec,
+                };
+                self.receive_instantiate(deps, env, sender, inst_msg, code_id)
+            }
+            PacketMsg::Migrate {
+                controller,
+                migration_msg,
+                job_id,
+                new_code_id,
+                fail_on_error,
+                ..
+            } => {
+                let sender = SenderInfo {
+                    connection_id: source.connection_id,
+                    port_id: source.port_id,
+                    controller,
+                    job_id,
+                    fail_on_error,
+                    codec: source.codec,
+                };
+                self.receive_migrate(deps, sender, migration_msg, new_code_id)
+            }
+            PacketMsg::Dispatch {
+                msg,
+                controller,
+                job_id,
+                fail_on_error,
+                ..
+            } => {
+                let sender = SenderInfo {
+                    connection_id: source.connection_id,
+                    port_id: source.port_id,
+                    controller,
+                    job_id,
+                    fail_on_error,
+                    codec: source.codec,
+                };
+                self.receive_dispatch(deps, env, sender, msg)
+            }
+            PacketMsg::WhoAmI { controller, .. } => {
+                self.receive_who_am_i(deps, &source, &controller)
+            }
+            PacketMsg::Forward {
+                hops,
+                visited,
+                packet,
+            } => self.receive_forward(deps, env, source, hops, visited, *packet),
+            PacketMsg::ForwardAck {
+                controller,
+                job_id,
+                nonce,
+                ack,
+            } => self.receive_forward_ack(deps, env, source, controller, job_id, nonce, ack),
+            PacketMsg::Chunk {
+                controller,
+                job_id,
+                chunk,
+                ..
+            } => self.receive_chunk(deps, env, source, controller, job_id, chunk),
+        }
+    }
+
+    /// Creates the proxy of the sender, the reply to `INIT_CALLBACK_ID` saves it
+    fn create_account(
+        &self,
+        env: &Env,
+        sender: &SenderInfo,
+        inst_msg: CosmosMsg<T>,
+        code_id: u64,
+    ) -> Result<SubMsg, Self::Error> {
+        let msg = WasmMsg::Instantiate {
+            admin: Some(env.contract.address.to_string()),
+            code_id,
+            msg: to_binary(&inst_msg)?,
+            // TODO: allow user to deposit funds to this contract for this
+            funds: vec![],
+            label: format!(
+                "cosmwasm-ica-{}-{}-{}",
+                sender.connection_id, sender.port_id, sender.controller
+            ),
+        };
+        Ok(SubMsg::reply_on_success(msg, Self::INIT_CALLBACK_ID))
+    }
+
+    // processes PacketMsg::Instantiate variant
+    fn receive_instantiate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        sender: SenderInfo,
+        inst_msg: CosmosMsg<T>,
+        code_id: u64,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        let msg = self.create_account(&env, &sender, inst_msg, code_id)?;
+        // store the relevant calling chain (host) data to be handled
+        self.save_pending(deps.storage, &sender)?;
+
+        Ok(IbcReceiveResponse::new()
+            .add_submessage(msg)
+            .add_attribute("action", "recieve_instantiate"))
+    }
+
+    // processes PacketMsg::Migrate variant
+    fn receive_migrate(
+        &self,
+        deps: DepsMut,
+        sender: SenderInfo,
+        migrate_msg: CosmosMsg<T>,
+        new_code_id: u64,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        let account = self
+            .account(
+                deps.as_ref(),
+                &sender.connection_id,
+                &sender.port_id,
+                &sender.controller,
+            )?
+            .ok_or(TunnelError::AccountNotFound)?;
+        self.check_migrate(deps.as_ref(), &sender, &account, &migrate_msg, new_code_id)?;
+        let msg = WasmMsg::Migrate {
+            contract_addr: account.to_string(),
+            new_code_id,
+            msg: to_binary(&migrate_msg)?,
+        };
+        let msg = SubMsg::reply_always(msg, Self::MIGRATE_CALLBACK_ID);
+        self.save_pending(deps.storage, &sender)?;
+
+        Ok(IbcReceiveResponse::new()
+            .add_submessage(msg)
+            .add_attribute("action", "recieve_migrate"))
+    }
+
+    // processes PacketMsg::Dispatch variant
+    fn receive_dispatch(
+        &self,
+        mut deps: DepsMut,
+        env: Env,
+        sender: SenderInfo,
+        msg: CosmosMsg<T>,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        let account = self
+            .account(
+                deps.as_ref(),
+                &sender.connection_id,
+                &sender.port_id,
+                &sender.controller,
+            )?
+            .ok_or(TunnelError::AccountNotFound)?;
+        self.check_dispatch(deps.branch(), &env, &sender, &account, &msg)?;
+        let wasm_msg = wasm_execute(account, &msg, vec![])?;
+        let msg = SubMsg::reply_always(wasm_msg, Self::RECEIVE_DISPATCH_ID);
+        self.save_pending(deps.storage, &sender)?;
+
+        Ok(IbcReceiveResponse::new()
+            .add_submessage(msg)
+            .add_attribute("action", "receive_dispatch"))
+    }
+
+    // processes PacketMsg::WhoAmI variant
+    fn receive_who_am_i(
+        &self,
+        deps: DepsMut,
+        source: &PacketSource,
+        controller: &str,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        let account = self
+            .account(
+                deps.as_ref(),
+                &source.connection_id,
+                &source.port_id,
+                controller,
+            )?
+            .ok_or(TunnelError::AccountNotFound)?;
+        let acknowledgement = source.codec.success(&WhoAmIResponse {
+            account: account.into(),
+        })?;
+        Ok(IbcReceiveResponse::new()
+            .set_ack(acknowledgement)
+            .add_attribute("action", "receive_who_am_i"))
+    }
+
+    // processes PacketMsg::Forward variant, not supported by default
+    fn receive_forward(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _source: PacketSource,
+        _hops: Vec<String>,
+        _visited: Vec<String>,
+        _packet: PacketMsg<T>,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        Err(TunnelError::InvalidForward.into())
+    }
+
+    // processes PacketMsg::ForwardAck variant, not supported by default
+    #[allow(clippy::too_many_arguments)]
+    fn receive_forward_ack(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _source: PacketSource,
+        _controller: String,
+        _job_id: Option<String>,
+        _nonce: u64,
+        _ack: StdAck,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        Err(TunnelError::InvalidForward.into())
+    }
+
+    // processes PacketMsg::Chunk variant, not supported by default.
+    // Once all chunks are received, the assembled packet is run with `receive_decoded`
+    fn receive_chunk(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _source: PacketSource,
+        _controller: String,
+        _job_id: Option<String>,
+        _chunk: PacketChunk,
+    ) -> Result<IbcReceiveResponse, Self::Error> {
+        Err(TunnelError::UnsupportedOperation(TunnelOperation::Chunk).into())
+    }
+
+    /// Handles the replies of the submessages of received packets
+    fn reply(&self, deps: DepsMut, _env: Env, reply: Reply) -> Result<Response, Self::Error> {
+        match reply.id {
+            id if id == Self::INIT_CALLBACK_ID => self.reply_init_callback(deps, reply),
+            id if id == Self::RECEIVE_DISPATCH_ID || id == Self::MIGRATE_CALLBACK_ID => {
+                let sender = self.take_pending(deps.storage)?;
+                let data = self.dispatch_migrate_ack(sender, reply.result)?;
+                Ok(Response::new().set_data(data))
+            }
+            _ => Err(TunnelError::InvalidReplyId.into()),
+        }
+    }
+
+    /// Saves the instantiated proxy and acknowledges its address
+    fn reply_init_callback(&self, deps: DepsMut, reply: Reply) -> Result<Response, Self::Error> {
+        let sender = self.take_pending(deps.storage)?;
+
+        // parse contract address from reply data
+        let raw_addr = parse_reply_instantiate_data(reply)
+            .map_err(TunnelError::from)?
+            .contract_address;
+        let new_contract_addr = deps.api.addr_validate(&raw_addr)?;
+
+        // Save this new account so it is callable in the future
+        if self
+            .account(
+                deps.as_ref(),
+                &sender.connection_id,
+                &sender.port_id,
+                &sender.controller,
+            )?
+            .is_some()
+        {
+            return Err(TunnelError::AccountAlreadyExists.into());
+        }
+        self.save_account(deps, &sender, &new_contract_addr)?;
+
+        // Send Ack to the sending chain
+        let data = sender.codec.success(&InstantiateResponse {
+            contract_address: new_contract_addr.to_string(),
+            job_id: sender.job_id,
+        })?;
+        let event = ProxyInstantiated {
+            contract_addr: new_contract_addr.to_string(),
+            connection_id: sender.connection_id,
+            port_id: sender.port_id,
+            controller: sender.controller,
+        };
+
+        Ok(Response::new().set_data(data).add_event(event.into()))
+    }
+
+    /// Builds the ack for a dispatched / migrated submessage.
+    /// A failed submessage has its state changes reverted by the runtime,
+    /// so with `fail_on_error` the host receives `StdAck::Error` for an operation that had no effect
+    fn dispatch_migrate_ack(
+        &self,
+        sender: SenderInfo,
+        result: SubMsgResult,
+    ) -> Result<Binary, Self::Error> {
+        match result {
+            SubMsgResult::Err(message) if sender.fail_on_error => {
+                Ok(sender.codec.fail_with(&ErrorResponse {
+                    code: TunnelErrorCode::ExecutionFailed,
+                    message,
+                    job_id: sender.job_id,
+                })?)
+            }
+            result => Ok(sender.codec.success(&DispatchMigrateResponse {
+                result,
+                job_id: sender.job_id,
+            })?),
+        }
+    }
+}
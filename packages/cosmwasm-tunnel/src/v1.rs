@@ -0,0 +1,178 @@
+//! The packets of `cw-tunnel-v1` channels, as the first tunnels send and expect them:
+//! only the first four operations, without nonces or `fail_on_error`.
+//! The acks are the same `StdAck`, with the error as a plain string.
+use cosmwasm_schema::cw_serde;
+use cosmwasm_schema::serde::{de::DeserializeOwned, Serialize};
+use cosmwasm_std::{from_slice, to_binary, Binary, CosmosMsg, Empty, StdError, StdResult};
+
+use crate::msg::check_depth;
+use crate::TunnelOperation;
+
+/// The `PacketMsg` of `cw-tunnel-v1`
+#[cw_serde]
+pub enum PacketMsg<T = Empty> {
+    Instantiate {
+        controller: String,
+        inst_msg: CosmosMsg<T>,
+        job_id: Option<String>,
+        code_id: u64,
+    },
+    Migrate {
+        controller: String,
+        migration_msg: CosmosMsg<T>,
+        new_code_id: u64,
+        job_id: Option<String>,
+    },
+    Dispatch {
+        controller: String,
+        msg: CosmosMsg<T>,
+        job_id: Option<String>,
+    },
+    WhoAmI {
+        controller: String,
+    },
+}
+
+/// `PacketMsg` borrowing from the packet it is encoded from
+#[derive(Serialize)]
+#[serde(crate = "cosmwasm_schema::serde", rename_all = "snake_case")]
+enum PacketRef<'a, T> {
+    Instantiate {
+        controller: &'a str,
+        inst_msg: &'a CosmosMsg<T>,
+        job_id: &'a Option<String>,
+        code_id: u64,
+    },
+    Migrate {
+        controller: &'a str,
+        migration_msg: &'a CosmosMsg<T>,
+        new_code_id: u64,
+        job_id: &'a Option<String>,
+    },
+    Dispatch {
+        controller: &'a str,
+        msg: &'a CosmosMsg<T>,
+        job_id: &'a Option<String>,
+    },
+    WhoAmI {
+        controller: &'a str,
+    },
+}
+
+/// Whether `cw-tunnel-v1` tunnels run the operation
+pub fn supports(operation: TunnelOperation) -> bool {
+    matches!(
+        operation,
+        TunnelOperation::Instantiate
+            | TunnelOperation::Migrate
+            | TunnelOperation::Dispatch
+            | TunnelOperation::WhoAmI
+    )
+}
+
+/// Encodes `packet` as a `cw-tunnel-v1` packet, dropping its nonce.
+/// Packets a `cw-tunnel-v1` tunnel cannot run as sent are refused
+pub fn encode_packet<T: Serialize>(packet: &crate::PacketMsg<T>) -> StdResult<Binary> {
+    let unsupported = |what: &str| {
+        Err(StdError::generic_err(format!(
+            "{what} is not supported on cw-tunnel-v1 channels"
+        )))
+    };
+    let packet = match packet {
+        crate::PacketMsg::Instantiate {
+            controller,
+            inst_msg,
+            job_id,
+            code_id,
+            ..
+        } => PacketRef::Instantiate {
+            controller,
+            inst_msg,
+            job_id,
+            code_id: *code_id,
+        },
+        crate::PacketMsg::Migrate {
+            fail_on_error: true,
+            ..
+        }
+        | crate::PacketMsg::Dispatch {
+            fail_on_error: true,
+            ..
+        } => return unsupported("fail_on_error"),
+        crate::PacketMsg::Migrate {
+            controller,
+            migration_msg,
+            new_code_id,
+            job_id,
+            ..
+        } => PacketRef::Migrate {
+            controller,
+            migration_msg,
+            new_code_id: *new_code_id,
+            job_id,
+        },
+        crate::PacketMsg::Dispatch {
+            controller,
+            msg,
+            job_id,
+            ..
+        } => PacketRef::Dispatch {
+            controller,
+            msg,
+            job_id,
+        },
+        crate::PacketMsg::WhoAmI { controller, .. } => PacketRef::WhoAmI { controller },
+        crate::PacketMsg::Forward { .. } => return unsupported("Forward"),
+        crate::PacketMsg::ForwardAck { .. } => return unsupported("ForwardAck"),
+        crate::PacketMsg::Chunk { .. } => return unsupported("Chunk"),
+    };
+    to_binary(&packet)
+}
+
+/// Decodes a `cw-tunnel-v1` packet, with a nonce of 0 as it has none
+pub fn decode_packet<T: DeserializeOwned>(data: &[u8]) -> StdResult<crate::PacketMsg<T>> {
+    check_depth(data)?;
+    let packet = match from_slice(data)? {
+        PacketMsg::Instantiate {
+            controller,
+            inst_msg,
+            job_id,
+            code_id,
+        } => crate::PacketMsg::Instantiate {
+            controller,
+            inst_msg,
+            job_id,
+            code_id,
+            nonce: 0,
+        },
+        PacketMsg::Migrate {
+            controller,
+            migration_msg,
+            new_code_id,
+            job_id,
+        } => crate::PacketMsg::Migrate {
+            controller,
+            migration_msg,
+            new_code_id,
+            job_id,
+            fail_on_error: false,
+            nonce: 0,
+        },
+        PacketMsg::Dispatch {
+            controller,
+            msg,
+            job_id,
+        } => crate::PacketMsg::Dispatch {
+            controller,
+            msg,
+            job_id,
+            fail_on_error: false,
+            nonce: 0,
+        },
+        PacketMsg::WhoAmI { controller } => crate::PacketMsg::WhoAmI {
+            controller,
+            nonce: 0,
+        },
+    };
+    Ok(packet)
+}
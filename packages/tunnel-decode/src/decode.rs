@@ -0,0 +1,353 @@
+use cosmwasm_schema::serde::Serialize;
+use cosmwasm_std::{from_slice, Binary, CosmosMsg, Empty, WasmMsg};
+use cosmwasm_tunnel::{
+    negotiate_version, v1, ChunkResponse, DispatchMigrateResponse, ErrorResponse, ForwardResponse,
+    InstantiateResponse, PacketCodec, PacketEncoding, PacketMsg, ProtocolVersion, StdAck,
+    TunnelAck, WhoAmIResponse, IBC_APP_VERSION, LATEST_APP_VERSION,
+};
+use serde_json::Value;
+
+/// The encoding of the bytes given on the command line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// JSON if it starts with `{`, hex if it only has hex digits, base64 otherwise
+    Auto,
+    Base64,
+    Hex,
+    Json,
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Result<Format, String> {
+        match format {
+            "auto" => Ok(Format::Auto),
+            "base64" => Ok(Format::Base64),
+            "hex" => Ok(Format::Hex),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format {format}, expected auto, base64, hex or json"
+            )),
+        }
+    }
+}
+
+/// Returns the raw bytes of `input`
+pub fn parse_input(input: &str, format: Format) -> Result<Vec<u8>, String> {
+    let input = input.trim();
+    let format = match format {
+        Format::Auto if input.starts_with('{') || input.starts_with('"') => Format::Json,
+        Format::Auto
+            if input.len().is_multiple_of(2) && input.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            Format::Hex
+        }
+        Format::Auto => Format::Base64,
+        format => format,
+    };
+    match format {
+        Format::Json => Ok(input.as_bytes().to_vec()),
+        Format::Hex => {
+            let input = input.strip_prefix("0x").unwrap_or(input);
+            hex::decode(input).map_err(|e| format!("invalid hex: {e}"))
+        }
+        _ => Binary::from_base64(input)
+            .map(Binary::into)
+            .map_err(|e| format!("invalid base64: {e}")),
+    }
+}
+
+/// The decoded bytes, with warnings about the protocol version
+#[derive(Debug, Default)]
+pub struct Report {
+    pub lines: Vec<String>,
+    pub mismatches: Vec<String>,
+    /// The protocol version and encoding of the channel, the latest in JSON unless the channel version says otherwise
+    pub codec: PacketCodec,
+}
+
+impl Report {
+    fn line(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+    }
+
+    fn mismatch(&mut self, warning: impl Into<String>) {
+        self.mismatches.push(warning.into());
+    }
+
+    /// Flags a channel that does not speak the version of this decoder
+    pub fn check_channel_version(&mut self, version: Option<&str>) {
+        match version.map(negotiate_version) {
+            Some(Ok(negotiated)) => match negotiated.counterparty {
+                Some(metadata) => {
+                    self.codec = PacketCodec {
+                        version: negotiated.version,
+                        encoding: metadata.encoding,
+                    };
+                    self.line(format!(
+                        "channel:    {} ({:?} encoding) from {}",
+                        metadata.version, metadata.encoding, metadata.chain_id
+                    ))
+                }
+                None => {
+                    self.codec.version = negotiated.version;
+                    self.line(format!("channel:    {}", negotiated.version.as_str()))
+                }
+            },
+            Some(Err(_)) => self.mismatch(format!(
+                "the channel version is {}, this decoder speaks {LATEST_APP_VERSION}",
+                version.unwrap_or_default()
+            )),
+            None => {}
+        }
+    }
+}
+
+/// The variants of `PacketMsg` on the wire
+const PACKET_KINDS: [&str; 7] = [
+    "instantiate",
+    "migrate",
+    "dispatch",
+    "who_am_i",
+    "forward",
+    "forward_ack",
+    "chunk",
+];
+
+/// Decodes packet data sent by a tunnel
+pub fn decode_packet(data: &[u8], report: &mut Report) -> Result<PacketMsg, String> {
+    let packet: PacketMsg = match report.codec.decode_packet(data) {
+        Ok(packet) => packet,
+        Err(err) if report.codec.encoding == PacketEncoding::Proto => {
+            report.mismatch(format!(
+                "the data is not a {LATEST_APP_VERSION} packet in the proto encoding of the channel"
+            ));
+            return Err(format!("not a {LATEST_APP_VERSION} packet: {err}"));
+        }
+        Err(err) => return Err(diagnose_packet(data, &err.to_string(), report)),
+    };
+
+    describe_packet(&packet, "", report);
+    report.line("");
+    match report.codec.version {
+        // as sent, without the nonce it is decoded with
+        ProtocolVersion::V1 => report.line(pretty(&from_slice::<v1::PacketMsg>(data).ok())),
+        _ => report.line(pretty(&packet)),
+    }
+    Ok(packet)
+}
+
+fn describe_packet(packet: &PacketMsg, indent: &str, report: &mut Report) {
+    let kind = match packet {
+        PacketMsg::Instantiate { code_id, .. } => format!("instantiate (code id {code_id})"),
+        PacketMsg::Migrate {
+            new_code_id,
+            fail_on_error,
+            ..
+        } => format!("migrate (new code id {new_code_id}, fail on error {fail_on_error})"),
+        PacketMsg::Dispatch { fail_on_error, .. } => {
+            format!("dispatch (fail on error {fail_on_error})")
+        }
+        PacketMsg::WhoAmI { .. } => "who_am_i".to_string(),
+        PacketMsg::Forward { .. } => "forward".to_string(),
+        PacketMsg::ForwardAck { .. } => "forward_ack".to_string(),
+        PacketMsg::Chunk {
+            operation, chunk, ..
+        } => format!(
+            "chunk {} of {} ({operation:?})",
+            chunk.index + 1,
+            chunk.total
+        ),
+    };
+    report.line(format!("{indent}packet:     {kind}"));
+    report.line(format!("{indent}controller: {}", packet.controller()));
+    if let Some(nonce) = packet.nonce().filter(|_| report.codec.has_nonces()) {
+        report.line(format!("{indent}nonce:      {nonce}"));
+    }
+    if let Some(job_id) = packet.job_id() {
+        report.line(format!("{indent}job id:     {job_id}"));
+    }
+    match packet {
+        PacketMsg::Instantiate { inst_msg: msg, .. }
+        | PacketMsg::Migrate {
+            migration_msg: msg, ..
+        }
+        | PacketMsg::Dispatch { msg, .. } => {
+            if let Some(inner) = inner_msg(msg) {
+                report.line(format!("{indent}wasm msg:   {inner}"));
+            }
+        }
+        PacketMsg::Forward {
+            hops,
+            visited,
+            packet,
+        } => {
+            report.line(format!("{indent}hops:       {}", hops.join(" -> ")));
+            report.line(format!("{indent}visited:    {}", visited.join(" -> ")));
+            describe_packet(packet, &format!("{indent}  "), report);
+        }
+        PacketMsg::ForwardAck { ack, .. } => {
+            report.line(format!("{indent}ack:        {}", pretty(ack)));
+        }
+        PacketMsg::Chunk { chunk, .. } => {
+            report.line(format!(
+                "{indent}hash:       {}",
+                hex::encode(chunk.hash.as_slice())
+            ));
+            report.line(format!("{indent}size:       {} bytes", chunk.data.len()));
+        }
+        PacketMsg::WhoAmI { .. } => {}
+    }
+}
+
+/// The JSON message in a `WasmMsg`, which is base64 encoded in the packet
+fn inner_msg(msg: &CosmosMsg<Empty>) -> Option<String> {
+    let msg = match msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { msg, .. })
+        | CosmosMsg::Wasm(WasmMsg::Instantiate { msg, .. })
+        | CosmosMsg::Wasm(WasmMsg::Migrate { msg, .. }) => msg,
+        _ => return None,
+    };
+    let value: Value = serde_json::from_slice(msg).ok()?;
+    Some(value.to_string())
+}
+
+/// Explains why the data is not a packet of this protocol version
+fn diagnose_packet(data: &[u8], err: &str, report: &mut Report) -> String {
+    let value: Value = match serde_json::from_slice(data) {
+        Ok(value) => value,
+        Err(_) if PacketEncoding::Proto.decode_packet::<Empty>(data).is_ok() => {
+            report.mismatch(format!(
+                "the data is a proto {LATEST_APP_VERSION} packet, \
+                 pass the --channel-version of the channel to decode it"
+            ));
+            return format!("not a JSON {LATEST_APP_VERSION} packet: {err}");
+        }
+        Err(_) => {
+            report.mismatch(format!(
+                "the data is not JSON, {LATEST_APP_VERSION} packets are unless the channel is proto encoded"
+            ));
+            return format!("not a {LATEST_APP_VERSION} packet: {err}");
+        }
+    };
+    let keys: Vec<&str> = value
+        .as_object()
+        .map(|object| object.keys().map(String::as_str).collect())
+        .unwrap_or_default();
+
+    if ["denom", "amount", "sender", "receiver"]
+        .iter()
+        .all(|key| keys.contains(key))
+    {
+        report.mismatch(format!(
+            "this is an ICS-20 transfer packet (ics20-1), not {LATEST_APP_VERSION}"
+        ));
+    } else if ["type", "data"].iter().all(|key| keys.contains(key)) {
+        report.mismatch(format!(
+            "this is an ICS-27 interchain account packet (ics27-1), not {LATEST_APP_VERSION}"
+        ));
+    } else if v1::decode_packet::<Empty>(data).is_ok() {
+        report.mismatch(format!(
+            "this is a {IBC_APP_VERSION} packet, \
+             pass the --channel-version of the channel to decode it"
+        ));
+    } else if let [kind] = keys[..] {
+        if PACKET_KINDS.contains(&kind) {
+            report.mismatch(format!(
+                "this is a `{kind}` packet that does not match {LATEST_APP_VERSION}, \
+                 it may be from another version of the tunnel"
+            ));
+        }
+    }
+    report.line(pretty(&value));
+    format!("not a {LATEST_APP_VERSION} packet: {err}")
+}
+
+/// Decodes an acknowledgement, into the response of `packet` if it is known
+pub fn decode_ack(
+    data: &[u8],
+    packet: Option<&PacketMsg>,
+    report: &mut Report,
+) -> Result<StdAck, String> {
+    let encoding = report.codec.encoding;
+    let ack: StdAck = encoding.decode_ack(data).map_err(|err| {
+        report.mismatch(match encoding {
+            PacketEncoding::Json => format!(
+                "the ack is not a StdAck, {LATEST_APP_VERSION} acks are {{\"result\": <base64>}} or {{\"error\": <string>}}"
+            ),
+            PacketEncoding::Proto => format!(
+                "the ack is not a proto {LATEST_APP_VERSION} ack, the ibc.core.channel.v1.Acknowledgement of the channel"
+            ),
+        });
+        format!("not a {LATEST_APP_VERSION} ack: {err}")
+    })?;
+
+    match &ack {
+        StdAck::Result(data) => {
+            report.line("ack:        result");
+            let response = match packet {
+                Some(packet) => TunnelAck::from_std_ack(packet, &ack).map_err(|err| {
+                    report.mismatch(
+                        "the result does not match the response to the packet, \
+                         it may be from another version of the tunnel",
+                    );
+                    format!("invalid response: {err}")
+                })?,
+                None => guess_response(data).ok_or_else(|| {
+                    report.line(format!("data:       {}", String::from_utf8_lossy(data)));
+                    "the result is not a known response, pass the packet to decode it".to_string()
+                })?,
+            };
+            describe_response(&response, packet.is_some(), report);
+        }
+        StdAck::Error(err) => {
+            let error = ErrorResponse::from_ack_error(err);
+            report.line("ack:        error");
+            report.line(format!("code:       {:?}", error.code));
+            if let Some(job_id) = &error.job_id {
+                report.line(format!("job id:     {job_id}"));
+            }
+            report.line(format!("message:    {}", error.message));
+        }
+    }
+    Ok(ack)
+}
+
+/// The response the result data decodes into, without the packet it is ambiguous between dispatch and migrate
+fn guess_response(data: &Binary) -> Option<TunnelAck> {
+    if let Ok(res) = from_slice::<InstantiateResponse>(data) {
+        return Some(TunnelAck::Instantiate(res));
+    }
+    if let Ok(res) = from_slice::<DispatchMigrateResponse>(data) {
+        return Some(TunnelAck::Dispatch(res));
+    }
+    if let Ok(res) = from_slice::<WhoAmIResponse>(data) {
+        return Some(TunnelAck::WhoAmI(res));
+    }
+    if let Ok(res) = from_slice::<ForwardResponse>(data) {
+        return Some(TunnelAck::Forward(res));
+    }
+    if let Ok(res) = from_slice::<ChunkResponse>(data) {
+        return Some(TunnelAck::Chunk(res));
+    }
+    None
+}
+
+fn describe_response(response: &TunnelAck, known_packet: bool, report: &mut Report) {
+    let kind = match response {
+        TunnelAck::Instantiate(_) => "instantiate",
+        TunnelAck::Migrate(_) => "migrate",
+        TunnelAck::Dispatch(_) if known_packet => "dispatch",
+        TunnelAck::Dispatch(_) => "dispatch or migrate",
+        TunnelAck::WhoAmI(_) => "who_am_i",
+        TunnelAck::Forward(_) => "forward (the ack of the forwarded operation follows)",
+        TunnelAck::Chunk(_) => "chunk (the packet runs once all chunks are received)",
+        TunnelAck::Error(_) => "error",
+    };
+    report.line(format!("response:   {kind}"));
+    report.line("");
+    report.line(pretty(response));
+}
+
+fn pretty(value: &impl Serialize) -> String {
+    serde_json::to_string_pretty(value).expect("decoded values serialize")
+}
@@ -0,0 +1,124 @@
+//! Decodes the packets and acknowledgements of a tunnel channel, e.g. of a packet stuck on a relayer.
+//!
+//! ```text
+//! tunnel-decode packet [--format F] [--channel-version V] <DATA>
+//! tunnel-decode ack [--format F] [--channel-version V] [--packet PACKET] <DATA>
+//! ```
+//!
+//! `DATA` is base64, hex or JSON (`--format auto|base64|hex|json`, auto by default), or `-` to read stdin.
+//! Exits with 1 if the bytes cannot be decoded, and with 2 if they do not match the protocol version of this decoder.
+use std::io::{Read, Write};
+use std::process::exit;
+
+mod decode;
+
+use crate::decode::{decode_ack, decode_packet, parse_input, Format, Report};
+
+const USAGE: &str = "usage:
+  tunnel-decode packet [--format F] [--channel-version V] <DATA>
+  tunnel-decode ack [--format F] [--channel-version V] [--packet PACKET] <DATA>
+
+DATA and PACKET are base64, hex or JSON, `-` reads DATA from stdin.
+  --format F            auto (default), base64, hex or json
+  --channel-version V   the version of the channel, flagged if it is not the version of this decoder
+  --packet PACKET       the packet of the ack, to decode the result into its response";
+
+#[derive(Debug, Default)]
+struct Args {
+    command: String,
+    data: Option<String>,
+    packet: Option<String>,
+    format: Option<String>,
+    channel_version: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        command: args.next().ok_or("missing command")?,
+        ..Args::default()
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value of {arg}"));
+        match arg.as_str() {
+            "--format" => parsed.format = Some(value()?),
+            "--channel-version" => parsed.channel_version = Some(value()?),
+            "--packet" => parsed.packet = Some(value()?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if parsed.data.is_none() => parsed.data = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+    Ok(parsed)
+}
+
+fn run(args: Args, report: &mut Report) -> Result<(), String> {
+    let format = Format::parse(args.format.as_deref().unwrap_or("auto"))?;
+    let data = match args.data.as_deref() {
+        None | Some("-") => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| format!("cannot read stdin: {e}"))?;
+            input
+        }
+        Some(data) => data.to_string(),
+    };
+    let data = parse_input(&data, format)?;
+    report.check_channel_version(args.channel_version.as_deref());
+
+    match args.command.as_str() {
+        "packet" => decode_packet(&data, report).map(|_| ()),
+        "ack" => {
+            let packet = match args.packet {
+                Some(packet) => {
+                    let packet = parse_input(&packet, format)?;
+                    let mut packet_report = Report {
+                        codec: report.codec,
+                        ..Report::default()
+                    };
+                    let packet = decode_packet(&packet, &mut packet_report)
+                        .map_err(|e| format!("--packet: {e}"))?;
+                    report.mismatches.extend(packet_report.mismatches);
+                    Some(packet)
+                }
+                None => None,
+            };
+            decode_ack(&data, packet.as_ref(), report).map(|_| ())
+        }
+        command => Err(format!("unknown command {command}")),
+    }
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("error: {err}\n");
+            }
+            eprintln!("{USAGE}");
+            exit(1);
+        }
+    };
+
+    let mut report = Report::default();
+    let result = run(args, &mut report);
+    // the output is often piped, e.g. into `head`
+    let mut stdout = std::io::stdout().lock();
+    for line in &report.lines {
+        let _ = writeln!(stdout, "{line}");
+    }
+    for mismatch in &report.mismatches {
+        eprintln!("protocol mismatch: {mismatch}");
+    }
+    if let Err(err) = &result {
+        eprintln!("error: {err}");
+    }
+    // bytes of another version do not decode, they are reported as the mismatch they are
+    if !report.mismatches.is_empty() {
+        exit(2);
+    }
+    if result.is_err() {
+        exit(1);
+    }
+}
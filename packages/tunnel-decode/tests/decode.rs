@@ -0,0 +1,196 @@
+//! Runs the decoder as operators do, on packets and acks in each input format
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use cosmwasm_std::{to_binary, to_vec, Binary, SubMsgResult, WasmMsg};
+use cosmwasm_tunnel::{
+    v1, DispatchMigrateResponse, ErrorResponse, PacketMsg, StdAck, TunnelErrorCode, IBC_APP_VERSION,
+};
+
+struct Decoded {
+    code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+fn run(args: &[&str], stdin: Option<&str>) -> Decoded {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tunnel-decode"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = child.stdin.take().unwrap();
+    input.write_all(stdin.unwrap_or("").as_bytes()).unwrap();
+    drop(input);
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = child.wait_with_output().unwrap();
+    Decoded {
+        code: status.code().unwrap(),
+        stdout: String::from_utf8(stdout).unwrap(),
+        stderr: String::from_utf8(stderr).unwrap(),
+    }
+}
+
+fn decode(args: &[&str]) -> Decoded {
+    run(args, None)
+}
+
+fn increment() -> WasmMsg {
+    WasmMsg::Execute {
+        contract_addr: "counter".to_string(),
+        msg: Binary::from(br#"{"increment":{}}"#.to_vec()),
+        funds: vec![],
+    }
+}
+
+fn dispatch() -> Vec<u8> {
+    let packet: PacketMsg = PacketMsg::Dispatch {
+        controller: "controller".to_string(),
+        msg: increment().into(),
+        job_id: Some("job-1".to_string()),
+        fail_on_error: true,
+        nonce: 7,
+    };
+    to_vec(&packet).unwrap()
+}
+
+fn assert_dispatch(decoded: &Decoded) {
+    assert_eq!(decoded.code, 0, "{}", decoded.stderr);
+    for line in [
+        "packet:     dispatch (fail on error true)",
+        "controller: controller",
+        "nonce:      7",
+        "job id:     job-1",
+        r#"wasm msg:   {"increment":{}}"#,
+    ] {
+        assert!(decoded.stdout.contains(line), "{}", decoded.stdout);
+    }
+}
+
+#[test]
+fn packets_are_decoded_in_each_format() {
+    let data = dispatch();
+    let base64 = Binary::from(data.clone()).to_base64();
+    let hex = hex::encode(&data);
+    let json = String::from_utf8(data).unwrap();
+
+    assert_dispatch(&decode(&["packet", &base64]));
+    assert_dispatch(&decode(&["packet", &hex]));
+    assert_dispatch(&decode(&["packet", &json]));
+    assert_dispatch(&decode(&["packet", "--format", "base64", &base64]));
+    assert_dispatch(&decode(&["packet", "--format", "hex", &format!("0x{hex}")]));
+    assert_dispatch(&decode(&["packet", "--format", "json", &json]));
+    assert_dispatch(&run(&["packet", "-"], Some(&format!("{base64}\n"))));
+}
+
+#[test]
+fn invalid_input_fails() {
+    let decoded = decode(&["packet", "--format", "hex", "xyz"]);
+    assert_eq!(decoded.code, 1);
+    assert!(decoded.stderr.contains("invalid hex"), "{}", decoded.stderr);
+
+    let decoded = decode(&["packet", "--format", "base64", "not base64!"]);
+    assert_eq!(decoded.code, 1);
+    assert!(
+        decoded.stderr.contains("invalid base64"),
+        "{}",
+        decoded.stderr
+    );
+
+    let decoded = decode(&["packet", "--format", "yaml", "{}"]);
+    assert_eq!(decoded.code, 1);
+    assert!(decoded.stderr.contains("unknown format yaml"));
+
+    let decoded = decode(&["transfer", "{}"]);
+    assert_eq!(decoded.code, 1);
+    assert!(decoded.stderr.contains("unknown command transfer"));
+}
+
+#[test]
+fn v1_packets_are_flagged_and_decoded_on_v1_channels() {
+    let packet: v1::PacketMsg = v1::PacketMsg::Dispatch {
+        controller: "controller".to_string(),
+        msg: increment().into(),
+        job_id: None,
+    };
+    let data = Binary::from(to_vec(&packet).unwrap()).to_base64();
+
+    let decoded = decode(&["packet", &data]);
+    assert_eq!(decoded.code, 2);
+    assert!(
+        decoded
+            .stderr
+            .contains(&format!("this is a {IBC_APP_VERSION} packet")),
+        "{}",
+        decoded.stderr
+    );
+
+    let decoded = decode(&["packet", "--channel-version", IBC_APP_VERSION, &data]);
+    assert_eq!(decoded.code, 0, "{}", decoded.stderr);
+    assert!(decoded.stdout.contains("packet:     dispatch"));
+    assert!(decoded
+        .stdout
+        .contains(&format!("channel:    {IBC_APP_VERSION}")));
+    // v1 packets are sent without nonces
+    assert!(!decoded.stdout.contains("nonce:"), "{}", decoded.stdout);
+}
+
+#[test]
+fn packets_of_other_protocols_are_flagged() {
+    let transfer = r#"{"denom":"uatom","amount":"100","sender":"a","receiver":"b"}"#;
+    let decoded = decode(&["packet", transfer]);
+    assert_eq!(decoded.code, 2);
+    assert!(decoded.stderr.contains("ICS-20 transfer packet"));
+
+    let data = Binary::from(dispatch()).to_base64();
+    let decoded = decode(&["packet", "--channel-version", "ics20-1", &data]);
+    assert_eq!(decoded.code, 2);
+    assert!(decoded.stderr.contains("the channel version is ics20-1"));
+}
+
+#[test]
+fn acks_are_decoded_into_the_response_of_their_packet() {
+    let response = DispatchMigrateResponse {
+        result: SubMsgResult::Err("counter failed".to_string()),
+        job_id: Some("job-1".to_string()),
+    };
+    let ack = StdAck::Result(to_binary(&response).unwrap()).ack();
+    let packet = Binary::from(dispatch()).to_base64();
+
+    let decoded = decode(&["ack", "--packet", &packet, &ack.to_base64()]);
+    assert_eq!(decoded.code, 0, "{}", decoded.stderr);
+    assert!(decoded.stdout.contains("ack:        result"));
+    assert!(decoded.stdout.contains("response:   dispatch\n"));
+    assert!(decoded.stdout.contains("counter failed"));
+
+    // without the packet the response is ambiguous
+    let decoded = decode(&["ack", &hex::encode(ack.as_slice())]);
+    assert_eq!(decoded.code, 0, "{}", decoded.stderr);
+    assert!(decoded.stdout.contains("response:   dispatch or migrate"));
+
+    let error = StdAck::fail_with(&ErrorResponse {
+        code: TunnelErrorCode::ExecutionFailed,
+        message: "counter failed".to_string(),
+        job_id: Some("job-1".to_string()),
+    })
+    .unwrap();
+    let decoded = decode(&["ack", std::str::from_utf8(&error).unwrap()]);
+    assert_eq!(decoded.code, 0, "{}", decoded.stderr);
+    for line in [
+        "ack:        error",
+        "code:       ExecutionFailed",
+        "job id:     job-1",
+        "message:    counter failed",
+    ] {
+        assert!(decoded.stdout.contains(line), "{}", decoded.stdout);
+    }
+
+    let decoded = decode(&["ack", r#"{"success":true}"#]);
+    assert_eq!(decoded.code, 2);
+    assert!(decoded.stderr.contains("the ack is not a StdAck"));
+}
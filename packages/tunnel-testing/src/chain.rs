@@ -0,0 +1,897 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use cosmwasm_schema::serde::de::DeserializeOwned;
+use cosmwasm_schema::serde::Serialize;
+use cosmwasm_std::testing::{mock_env, MockApi};
+use cosmwasm_std::{
+    from_slice, to_binary, Addr, AllBalanceResponse, Attribute, BalanceResponse, BankMsg,
+    BankQuery, Binary, BlockInfo, ChannelResponse, Coin, ContractInfo,
+    ContractResult as QueryContractResult, CosmosMsg, Deps, DepsMut, Empty, Env, Event,
+    Ibc3ChannelOpenResponse, IbcAcknowledgement, IbcBasicResponse, IbcChannel,
+    IbcChannelConnectMsg, IbcChannelOpenMsg, IbcMsg, IbcPacket, IbcPacketAckMsg,
+    IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcQuery, IbcReceiveResponse, IbcTimeout,
+    ListChannelsResponse, MessageInfo, PortIdResponse, Querier, QuerierResult, QuerierWrapper,
+    QueryRequest, Reply, ReplyOn, Response, StdError, StdResult, Storage, SubMsg, SubMsgResponse,
+    SubMsgResult, SystemError, SystemResult, TransactionInfo, Uint128, WasmMsg, WasmQuery,
+};
+use cosmwasm_tunnel::{InstantiateMsg, StdAck};
+
+use crate::contracts::TunnelApp;
+use crate::storage::TestStorage;
+
+/// Contract errors are compared by their message, as on chain
+pub type ContractResult<T> = Result<T, String>;
+
+/// The entry points of a contract deployed on a test chain
+pub trait Contract {
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response>;
+
+    fn execute(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response>;
+
+    fn query(&self, deps: Deps, env: Env, msg: &[u8]) -> ContractResult<Binary>;
+
+    fn migrate(&self, _deps: DepsMut, _env: Env, _msg: &[u8]) -> ContractResult<Response> {
+        Err("migrate is not implemented".to_string())
+    }
+
+    fn reply(&self, _deps: DepsMut, _env: Env, _reply: Reply) -> ContractResult<Response> {
+        Err("reply is not implemented".to_string())
+    }
+
+    fn sudo(&self, _deps: DepsMut, _env: Env, _msg: &[u8]) -> ContractResult<Response> {
+        Err("sudo is not implemented".to_string())
+    }
+
+    /// The IBC entry points are only called on the tunnel of a chain
+    fn ibc_channel_open(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _msg: IbcChannelOpenMsg,
+    ) -> ContractResult<Option<Ibc3ChannelOpenResponse>> {
+        Err("ibc_channel_open is not implemented".to_string())
+    }
+
+    fn ibc_channel_connect(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _msg: IbcChannelConnectMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        Err("ibc_channel_connect is not implemented".to_string())
+    }
+
+    fn ibc_packet_receive(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _msg: IbcPacketReceiveMsg,
+    ) -> ContractResult<IbcReceiveResponse> {
+        Err("ibc_packet_receive is not implemented".to_string())
+    }
+
+    fn ibc_packet_ack(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _msg: IbcPacketAckMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        Err("ibc_packet_ack is not implemented".to_string())
+    }
+
+    fn ibc_packet_timeout(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _msg: IbcPacketTimeoutMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        Err("ibc_packet_timeout is not implemented".to_string())
+    }
+}
+
+/// The events and data of an executed message, including its submessages
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AppResponse {
+    pub events: Vec<Event>,
+    pub data: Option<Binary>,
+}
+
+/// A stargate message sent by a contract, which the test chain only records
+#[derive(Clone, Debug, PartialEq)]
+pub struct StargateMsg {
+    pub sender: Addr,
+    pub type_url: String,
+    pub value: Binary,
+}
+
+#[derive(Clone)]
+struct ContractData {
+    code_id: u64,
+    admin: Option<Addr>,
+    storage: TestStorage,
+}
+
+/// Everything that is reverted when a message fails
+#[derive(Clone, Default)]
+struct State {
+    contracts: BTreeMap<Addr, ContractData>,
+    contract_count: u64,
+    channels: BTreeMap<String, IbcChannel>,
+    next_sequence: BTreeMap<String, u64>,
+    outbox: Vec<IbcPacket>,
+    stargate_msgs: Vec<StargateMsg>,
+    balances: BTreeMap<Addr, BTreeMap<String, Uint128>>,
+}
+
+/// An in-memory chain running the tunnel (at `Chain::tunnel`) and any mock contracts
+pub struct Chain {
+    pub chain_id: String,
+    pub block: BlockInfo,
+    api: MockApi,
+    codes: BTreeMap<u64, Rc<dyn Contract>>,
+    state: State,
+    tunnel: Addr,
+}
+
+/// The port of a contract, as bound by wasmd
+pub fn ibc_port(contract: &Addr) -> String {
+    format!("wasm.{contract}")
+}
+
+impl Chain {
+    /// Creates the chain and instantiates the tunnel
+    pub fn new(chain_id: &str) -> Self {
+        Self::new_with_tunnel(
+            chain_id,
+            TunnelApp,
+            &InstantiateMsg {
+                init: 0,
+                nonce_window: None,
+            },
+        )
+    }
+
+    /// Creates the chain with another tunnel contract, instantiated with `init_msg`
+    pub fn new_with_tunnel(
+        chain_id: &str,
+        tunnel: impl Contract + 'static,
+        init_msg: &impl Serialize,
+    ) -> Self {
+        let init_msg = to_binary(init_msg).expect("instantiate message serializes");
+        let mut block = mock_env().block;
+        block.chain_id = chain_id.to_string();
+        let mut chain = Chain {
+            chain_id: chain_id.to_string(),
+            block,
+            api: MockApi::default(),
+            codes: BTreeMap::new(),
+            state: State::default(),
+            tunnel: Addr::unchecked("tunnel"),
+        };
+        let code_id = chain.store_code(tunnel);
+        chain.state.contracts.insert(
+            chain.tunnel.clone(),
+            ContractData {
+                code_id,
+                admin: None,
+                storage: TestStorage::default(),
+            },
+        );
+        chain
+            .call(&chain.tunnel.clone(), |code, deps, env| {
+                let info = MessageInfo {
+                    sender: Addr::unchecked("creator"),
+                    funds: vec![],
+                };
+                code.instantiate(deps, env, info, &init_msg)
+            })
+            .expect("tunnel instantiates");
+        chain
+    }
+
+    pub fn tunnel(&self) -> Addr {
+        self.tunnel.clone()
+    }
+
+    /// Calls `f` with the tunnel's storage and a querier of the chain, without dispatching anything.
+    /// Used to check what an entry point returns itself, before wasmd handles its errors.
+    pub fn with_tunnel<R>(&mut self, f: impl FnOnce(DepsMut, Env) -> R) -> R {
+        self.call(&self.tunnel(), |_, deps, env| Ok(f(deps, env)))
+            .expect("the tunnel is instantiated")
+    }
+
+    /// Advances the block height by one and the time by `seconds`
+    pub fn next_block(&mut self, seconds: u64) {
+        self.block.height += 1;
+        self.block.time = self.block.time.plus_seconds(seconds);
+    }
+
+    pub fn store_code(&mut self, contract: impl Contract + 'static) -> u64 {
+        let code_id = self.codes.len() as u64 + 1;
+        self.codes.insert(code_id, Rc::new(contract));
+        code_id
+    }
+
+    /// The stargate messages sent on this chain, in order
+    pub fn stargate_msgs(&self) -> &[StargateMsg] {
+        &self.state.stargate_msgs
+    }
+
+    /// Sets the native tokens an address holds, replacing its balance
+    pub fn set_balance(&mut self, addr: &Addr, amount: Vec<Coin>) {
+        let balance = amount
+            .into_iter()
+            .filter(|coin| !coin.amount.is_zero())
+            .map(|coin| (coin.denom, coin.amount))
+            .collect();
+        self.state.balances.insert(addr.clone(), balance);
+    }
+
+    /// The amount of `denom` an address holds
+    pub fn balance(&self, addr: &Addr, denom: &str) -> Coin {
+        let amount = self
+            .state
+            .balances
+            .get(addr)
+            .and_then(|balance| balance.get(denom))
+            .copied()
+            .unwrap_or_default();
+        Coin::new(amount.u128(), denom)
+    }
+
+    /// All the native tokens an address holds, by denom
+    pub fn all_balances(&self, addr: &Addr) -> Vec<Coin> {
+        self.state
+            .balances
+            .get(addr)
+            .into_iter()
+            .flatten()
+            .map(|(denom, amount)| Coin::new(amount.u128(), denom))
+            .collect()
+    }
+
+    /// The code id a contract is running, to check migrations
+    pub fn code_id(&self, contract: &Addr) -> Option<u64> {
+        self.state.contracts.get(contract).map(|data| data.code_id)
+    }
+
+    pub fn instantiate(
+        &mut self,
+        sender: &str,
+        code_id: u64,
+        msg: &impl Serialize,
+        admin: Option<&str>,
+    ) -> ContractResult<Addr> {
+        let msg = to_binary(msg).map_err(|e| e.to_string())?;
+        let admin = admin.map(Addr::unchecked);
+        self.transact(|chain| {
+            chain
+                .instantiate_contract(&Addr::unchecked(sender), code_id, &msg, admin, vec![])
+                .map(|(addr, _)| addr)
+        })
+    }
+
+    pub fn execute(
+        &mut self,
+        sender: &str,
+        contract: &Addr,
+        msg: &impl Serialize,
+        funds: Vec<Coin>,
+    ) -> ContractResult<AppResponse> {
+        let msg = to_binary(msg).map_err(|e| e.to_string())?;
+        self.transact(|chain| {
+            chain.execute_contract(&Addr::unchecked(sender), contract, &msg, funds)
+        })
+    }
+
+    pub fn migrate(
+        &mut self,
+        sender: &str,
+        contract: &Addr,
+        new_code_id: u64,
+        msg: &impl Serialize,
+    ) -> ContractResult<AppResponse> {
+        let msg = to_binary(msg).map_err(|e| e.to_string())?;
+        self.transact(|chain| {
+            chain.migrate_contract(&Addr::unchecked(sender), contract, new_code_id, &msg)
+        })
+    }
+
+    /// Calls `sudo` on the contract, as the chain does for governance proposals
+    pub fn sudo(&mut self, contract: &Addr, msg: &impl Serialize) -> ContractResult<AppResponse> {
+        let msg = to_binary(msg).map_err(|e| e.to_string())?;
+        self.transact(|chain| {
+            let res = chain.call(contract, |code, deps, env| code.sudo(deps, env, &msg))?;
+            chain.dispatch_response(contract, res)
+        })
+    }
+
+    pub fn query<T: DeserializeOwned>(
+        &self,
+        contract: &Addr,
+        msg: &impl Serialize,
+    ) -> StdResult<T> {
+        let msg = to_binary(msg)?;
+        let data = self
+            .query_contract(contract, &msg)
+            .map_err(StdError::generic_err)?;
+        from_slice(&data)
+    }
+
+    /// Runs `f`, reverting the state if it fails
+    fn transact<R>(&mut self, f: impl FnOnce(&mut Self) -> ContractResult<R>) -> ContractResult<R> {
+        let snapshot = self.state.clone();
+        let result = f(self);
+        if result.is_err() {
+            self.state = snapshot;
+        }
+        result
+    }
+
+    fn env(&self, contract: &Addr) -> Env {
+        Env {
+            block: self.block.clone(),
+            transaction: Some(TransactionInfo { index: 0 }),
+            contract: ContractInfo {
+                address: contract.clone(),
+            },
+        }
+    }
+
+    /// Calls an entry point of `contract` with its storage and a querier of the rest of the chain
+    fn call<R>(
+        &mut self,
+        contract: &Addr,
+        f: impl FnOnce(&dyn Contract, DepsMut, Env) -> ContractResult<R>,
+    ) -> ContractResult<R> {
+        let mut data = self
+            .state
+            .contracts
+            .remove(contract)
+            .ok_or_else(|| format!("contract {contract} not found"))?;
+        let result = match self.codes.get(&data.code_id).cloned() {
+            Some(code) => {
+                let querier = ChainQuerier {
+                    chain: self,
+                    contract,
+                };
+                let deps = DepsMut {
+                    storage: &mut data.storage,
+                    api: &self.api,
+                    querier: QuerierWrapper::new(&querier),
+                };
+                f(code.as_ref(), deps, self.env(contract))
+            }
+            None => Err(format!("code {} not found", data.code_id)),
+        };
+        self.state.contracts.insert(contract.clone(), data);
+        result
+    }
+
+    fn query_contract(&self, contract: &Addr, msg: &[u8]) -> ContractResult<Binary> {
+        let data = self
+            .state
+            .contracts
+            .get(contract)
+            .ok_or_else(|| format!("contract {contract} not found"))?;
+        let code = self
+            .codes
+            .get(&data.code_id)
+            .ok_or_else(|| format!("code {} not found", data.code_id))?;
+        let querier = ChainQuerier {
+            chain: self,
+            contract,
+        };
+        let deps = Deps {
+            storage: &data.storage,
+            api: &self.api,
+            querier: QuerierWrapper::new(&querier),
+        };
+        code.query(deps, self.env(contract), msg)
+    }
+
+    fn instantiate_contract(
+        &mut self,
+        sender: &Addr,
+        code_id: u64,
+        msg: &[u8],
+        admin: Option<Addr>,
+        funds: Vec<Coin>,
+    ) -> ContractResult<(Addr, AppResponse)> {
+        if !self.codes.contains_key(&code_id) {
+            return Err(format!("code {code_id} not found"));
+        }
+        self.state.contract_count += 1;
+        let addr = Addr::unchecked(format!("contract{}", self.state.contract_count));
+        self.state.contracts.insert(
+            addr.clone(),
+            ContractData {
+                code_id,
+                admin,
+                storage: TestStorage::default(),
+            },
+        );
+        let mut app = self.transfer(sender, &addr, &funds)?;
+        let info = MessageInfo {
+            sender: sender.clone(),
+            funds,
+        };
+        let res = self.call(&addr, |code, deps, env| {
+            code.instantiate(deps, env, info, msg)
+        })?;
+        let res = self.dispatch_response(&addr, res)?;
+        app.events.extend(res.events);
+        app.data = Some(instantiate_response_data(&addr, res.data));
+        Ok((addr, app))
+    }
+
+    fn execute_contract(
+        &mut self,
+        sender: &Addr,
+        contract: &Addr,
+        msg: &[u8],
+        funds: Vec<Coin>,
+    ) -> ContractResult<AppResponse> {
+        if !self.state.contracts.contains_key(contract) {
+            return Err(format!("contract {contract} not found"));
+        }
+        let mut app = self.transfer(sender, contract, &funds)?;
+        let info = MessageInfo {
+            sender: sender.clone(),
+            funds,
+        };
+        let res = self.call(contract, |code, deps, env| {
+            code.execute(deps, env, info, msg)
+        })?;
+        let res = self.dispatch_response(contract, res)?;
+        app.events.extend(res.events);
+        app.data = res.data;
+        Ok(app)
+    }
+
+    /// Moves native tokens as the bank module does, failing if the sender does not hold them
+    fn transfer(&mut self, from: &Addr, to: &Addr, amount: &[Coin]) -> ContractResult<AppResponse> {
+        if amount.is_empty() {
+            return Ok(AppResponse::default());
+        }
+        self.burn(from, amount)?;
+        let balance = self.state.balances.entry(to.clone()).or_default();
+        for coin in amount {
+            *balance.entry(coin.denom.clone()).or_default() += coin.amount;
+        }
+        let event = Event::new("transfer")
+            .add_attribute("recipient", to)
+            .add_attribute("sender", from)
+            .add_attribute("amount", coins_string(amount));
+        Ok(AppResponse {
+            events: vec![event],
+            data: None,
+        })
+    }
+
+    /// Removes native tokens from the balance of `from`, failing if it does not hold them
+    fn burn(&mut self, from: &Addr, amount: &[Coin]) -> ContractResult<()> {
+        let balance = self.state.balances.entry(from.clone()).or_default();
+        for coin in amount {
+            if coin.amount.is_zero() {
+                return Err(format!("{coin}: invalid coins"));
+            }
+            let held = balance.entry(coin.denom.clone()).or_default();
+            if *held < coin.amount {
+                return Err(format!(
+                    "{held}{} is smaller than {coin}: insufficient funds",
+                    coin.denom
+                ));
+            }
+            *held -= coin.amount;
+        }
+        balance.retain(|_, amount| !amount.is_zero());
+        Ok(())
+    }
+
+    fn migrate_contract(
+        &mut self,
+        sender: &Addr,
+        contract: &Addr,
+        new_code_id: u64,
+        msg: &[u8],
+    ) -> ContractResult<AppResponse> {
+        let data = self
+            .state
+            .contracts
+            .get_mut(contract)
+            .ok_or_else(|| format!("contract {contract} not found"))?;
+        if data.admin.as_ref() != Some(sender) {
+            return Err(format!("{sender} is not the admin of {contract}"));
+        }
+        if !self.codes.contains_key(&new_code_id) {
+            return Err(format!("code {new_code_id} not found"));
+        }
+        data.code_id = new_code_id;
+        let res = self.call(contract, |code, deps, env| code.migrate(deps, env, msg))?;
+        self.dispatch_response(contract, res)
+    }
+
+    fn reply(&mut self, contract: &Addr, reply: Reply) -> ContractResult<AppResponse> {
+        let res = self.call(contract, |code, deps, env| code.reply(deps, env, reply))?;
+        self.dispatch_response(contract, res)
+    }
+
+    /// Executes the messages of a response in order as wasmd does:
+    /// a failed submessage is reverted and, unless it is replied on error, fails the whole response.
+    /// The data set in a reply replaces the data of the response.
+    fn dispatch_response(&mut self, contract: &Addr, res: Response) -> ContractResult<AppResponse> {
+        let mut app = AppResponse {
+            events: contract_events(contract, res.attributes, res.events),
+            data: res.data,
+        };
+        for msg in res.messages {
+            let SubMsg {
+                id, msg, reply_on, ..
+            } = msg;
+            let snapshot = self.state.clone();
+            let result = match self.execute_msg(contract, msg) {
+                Ok(sub) => {
+                    app.events.extend(sub.events.clone());
+                    matches!(reply_on, ReplyOn::Success | ReplyOn::Always).then(|| {
+                        SubMsgResult::Ok(SubMsgResponse {
+                            events: sub.events,
+                            data: sub.data,
+                        })
+                    })
+                }
+                Err(err) => {
+                    self.state = snapshot;
+                    if !matches!(reply_on, ReplyOn::Error | ReplyOn::Always) {
+                        return Err(err);
+                    }
+                    Some(SubMsgResult::Err(err))
+                }
+            };
+            if let Some(result) = result {
+                let reply = self.reply(contract, Reply { id, result })?;
+                app.events.extend(reply.events);
+                if reply.data.is_some() {
+                    app.data = reply.data;
+                }
+            }
+        }
+        Ok(app)
+    }
+
+    fn execute_msg(&mut self, sender: &Addr, msg: CosmosMsg) -> ContractResult<AppResponse> {
+        match msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg,
+                funds,
+            }) => self.execute_contract(sender, &Addr::unchecked(contract_addr), &msg, funds),
+            CosmosMsg::Wasm(WasmMsg::Instantiate {
+                admin,
+                code_id,
+                msg,
+                funds,
+                ..
+            }) => self
+                .instantiate_contract(sender, code_id, &msg, admin.map(Addr::unchecked), funds)
+                .map(|(_, app)| app),
+            CosmosMsg::Wasm(WasmMsg::Migrate {
+                contract_addr,
+                new_code_id,
+                msg,
+            }) => self.migrate_contract(sender, &Addr::unchecked(contract_addr), new_code_id, &msg),
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                self.transfer(sender, &Addr::unchecked(to_address), &amount)
+            }
+            CosmosMsg::Bank(BankMsg::Burn { amount }) => {
+                self.burn(sender, &amount)?;
+                let event = Event::new("burn")
+                    .add_attribute("burner", sender)
+                    .add_attribute("amount", coins_string(&amount));
+                Ok(AppResponse {
+                    events: vec![event],
+                    data: None,
+                })
+            }
+            CosmosMsg::Ibc(IbcMsg::SendPacket {
+                channel_id,
+                data,
+                timeout,
+            }) => self.send_packet(sender, channel_id, data, timeout),
+            CosmosMsg::Stargate { type_url, value } => {
+                self.state.stargate_msgs.push(StargateMsg {
+                    sender: sender.clone(),
+                    type_url,
+                    value,
+                });
+                Ok(AppResponse::default())
+            }
+            msg => Err(format!("unsupported message {msg:?}")),
+        }
+    }
+
+    pub(crate) fn send_packet(
+        &mut self,
+        sender: &Addr,
+        channel_id: String,
+        data: Binary,
+        timeout: IbcTimeout,
+    ) -> ContractResult<AppResponse> {
+        let channel = self
+            .state
+            .channels
+            .get(&channel_id)
+            .ok_or_else(|| format!("channel {channel_id} not found"))?;
+        if channel.endpoint.port_id != ibc_port(sender) {
+            return Err(format!("{sender} does not own the port of {channel_id}"));
+        }
+        let sequence = self.state.next_sequence.entry(channel_id).or_insert(1);
+        let packet = IbcPacket::new(
+            data,
+            channel.endpoint.clone(),
+            channel.counterparty_endpoint.clone(),
+            *sequence,
+            timeout,
+        );
+        *sequence += 1;
+        let event = Event::new("send_packet")
+            .add_attribute("packet_src_channel", &packet.src.channel_id)
+            .add_attribute("packet_sequence", packet.sequence.to_string());
+        self.state.outbox.push(packet);
+        Ok(AppResponse {
+            events: vec![event],
+            data: None,
+        })
+    }
+
+    /// Runs the tunnel's `ibc_channel_open`, returns the version of the channel
+    pub(crate) fn ibc_channel_open(&mut self, msg: IbcChannelOpenMsg) -> ContractResult<String> {
+        let version = msg.channel().version.clone();
+        let res = self.call(&self.tunnel(), |code, deps, env| {
+            code.ibc_channel_open(deps, env, msg)
+        })?;
+        Ok(res.map_or(version, |res| res.version))
+    }
+
+    /// Runs the tunnel's `ibc_channel_connect` and registers the channel on this chain
+    pub(crate) fn ibc_channel_connect(
+        &mut self,
+        msg: IbcChannelConnectMsg,
+    ) -> ContractResult<AppResponse> {
+        let channel = msg.channel().clone();
+        self.transact(|chain| {
+            let res = chain.call(&chain.tunnel(), |code, deps, env| {
+                code.ibc_channel_connect(deps, env, msg)
+            })?;
+            chain
+                .state
+                .channels
+                .insert(channel.endpoint.channel_id.clone(), channel);
+            chain.dispatch_ibc_response(res.messages, res.attributes, res.events)
+        })
+    }
+
+    /// Delivers a packet to the tunnel, returns the acknowledgement.
+    /// As in wasmd, a failing `ibc_packet_receive` is reverted and acknowledged with an error.
+    pub(crate) fn receive_packet(
+        &mut self,
+        packet: IbcPacket,
+    ) -> ContractResult<(Binary, AppResponse)> {
+        let result = self.transact(|chain| {
+            let msg = IbcPacketReceiveMsg::new(packet, Addr::unchecked("relayer"));
+            let res = chain.call(&chain.tunnel(), |code, deps, env| {
+                code.ibc_packet_receive(deps, env, msg)
+            })?;
+            let mut app = chain.dispatch_ibc_response(res.messages, res.attributes, res.events)?;
+            let ack = app.data.take().unwrap_or(res.acknowledgement);
+            Ok((ack, app))
+        });
+        Ok(result.unwrap_or_else(|err| (StdAck::fail(err), AppResponse::default())))
+    }
+
+    pub(crate) fn ack_packet(
+        &mut self,
+        packet: IbcPacket,
+        ack: Binary,
+    ) -> ContractResult<AppResponse> {
+        self.transact(|chain| {
+            let msg = IbcPacketAckMsg::new(
+                IbcAcknowledgement::new(ack),
+                packet,
+                Addr::unchecked("relayer"),
+            );
+            let res = chain.call(&chain.tunnel(), |code, deps, env| {
+                code.ibc_packet_ack(deps, env, msg)
+            })?;
+            chain.dispatch_ibc_response(res.messages, res.attributes, res.events)
+        })
+    }
+
+    pub(crate) fn timeout_packet(&mut self, packet: IbcPacket) -> ContractResult<AppResponse> {
+        self.transact(|chain| {
+            let msg = IbcPacketTimeoutMsg::new(packet, Addr::unchecked("relayer"));
+            let res = chain.call(&chain.tunnel(), |code, deps, env| {
+                code.ibc_packet_timeout(deps, env, msg)
+            })?;
+            chain.dispatch_ibc_response(res.messages, res.attributes, res.events)
+        })
+    }
+
+    fn dispatch_ibc_response(
+        &mut self,
+        messages: Vec<SubMsg>,
+        attributes: Vec<Attribute>,
+        events: Vec<Event>,
+    ) -> ContractResult<AppResponse> {
+        let res = Response::new()
+            .add_submessages(messages)
+            .add_attributes(attributes)
+            .add_events(events);
+        self.dispatch_response(&self.tunnel(), res)
+    }
+
+    pub(crate) fn outbox(&self) -> &[IbcPacket] {
+        &self.state.outbox
+    }
+
+    pub(crate) fn outbox_mut(&mut self) -> &mut Vec<IbcPacket> {
+        &mut self.state.outbox
+    }
+
+    /// Whether the packet can no longer be received on this chain
+    pub(crate) fn has_timed_out(&self, packet: &IbcPacket) -> bool {
+        let by_time = packet
+            .timeout
+            .timestamp()
+            .is_some_and(|timestamp| self.block.time >= timestamp);
+        let by_height = packet
+            .timeout
+            .block()
+            .is_some_and(|block| self.block.height >= block.height);
+        by_time || by_height
+    }
+}
+
+/// The events of a contract as wasmd emits them, its attributes in a `wasm` event
+/// and its custom events prefixed with `wasm-`
+fn contract_events(contract: &Addr, attributes: Vec<Attribute>, events: Vec<Event>) -> Vec<Event> {
+    let wasm = (!attributes.is_empty()).then(|| {
+        Event::new("wasm")
+            .add_attribute("_contract_address", contract)
+            .add_attributes(attributes)
+    });
+    wasm.into_iter()
+        .chain(events.into_iter().map(|event| {
+            Event::new(format!("wasm-{}", event.ty))
+                .add_attribute("_contract_address", contract)
+                .add_attributes(event.attributes)
+        }))
+        .collect()
+}
+
+/// Coins as the bank module writes them in its events, e.g. `100uatom,5ujuno`
+fn coins_string(amount: &[Coin]) -> String {
+    amount
+        .iter()
+        .map(Coin::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The protobuf `MsgInstantiateContractResponse` wasmd returns to the reply of an instantiation
+fn instantiate_response_data(addr: &Addr, data: Option<Binary>) -> Binary {
+    let mut out = vec![];
+    encode_field(&mut out, 1, addr.as_bytes());
+    if let Some(data) = data {
+        encode_field(&mut out, 2, data.as_slice());
+    }
+    out.into()
+}
+
+fn encode_field(out: &mut Vec<u8>, field: u8, bytes: &[u8]) {
+    out.push(field << 3 | 2);
+    let mut len = bytes.len();
+    while len >= 0x80 {
+        out.push((len as u8 & 0x7f) | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+    out.extend_from_slice(bytes);
+}
+
+/// Answers the queries of a contract from the rest of the chain
+struct ChainQuerier<'a> {
+    chain: &'a Chain,
+    contract: &'a Addr,
+}
+
+impl Querier for ChainQuerier<'_> {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(request) => request,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: e.to_string(),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        let response = match request {
+            QueryRequest::Ibc(IbcQuery::PortId {}) => to_binary(&PortIdResponse {
+                port_id: ibc_port(self.contract),
+            }),
+            QueryRequest::Ibc(IbcQuery::Channel {
+                channel_id,
+                port_id,
+            }) => {
+                let port_id = port_id.unwrap_or_else(|| ibc_port(self.contract));
+                let channel = self
+                    .chain
+                    .state
+                    .channels
+                    .get(&channel_id)
+                    .filter(|channel| channel.endpoint.port_id == port_id)
+                    .cloned();
+                to_binary(&ChannelResponse { channel })
+            }
+            QueryRequest::Ibc(IbcQuery::ListChannels { port_id }) => {
+                let port_id = port_id.unwrap_or_else(|| ibc_port(self.contract));
+                let channels = self
+                    .chain
+                    .state
+                    .channels
+                    .values()
+                    .filter(|channel| channel.endpoint.port_id == port_id)
+                    .cloned()
+                    .collect();
+                to_binary(&ListChannelsResponse { channels })
+            }
+            QueryRequest::Bank(BankQuery::Balance { address, denom }) => {
+                to_binary(&BalanceResponse {
+                    amount: self.chain.balance(&Addr::unchecked(address), &denom),
+                })
+            }
+            QueryRequest::Bank(BankQuery::AllBalances { address }) => {
+                to_binary(&AllBalanceResponse {
+                    amount: self.chain.all_balances(&Addr::unchecked(address)),
+                })
+            }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                let result = self
+                    .chain
+                    .query_contract(&Addr::unchecked(contract_addr), &msg);
+                return SystemResult::Ok(QueryContractResult::from(result));
+            }
+            QueryRequest::Wasm(WasmQuery::Raw { contract_addr, key }) => {
+                let value = self
+                    .chain
+                    .state
+                    .contracts
+                    .get(&Addr::unchecked(contract_addr))
+                    .and_then(|data| data.storage.get(&key));
+                Ok(Binary::from(value.unwrap_or_default()))
+            }
+            request => {
+                return SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: format!("{request:?}"),
+                })
+            }
+        };
+        SystemResult::Ok(QueryContractResult::from(
+            response.map_err(|e| e.to_string()),
+        ))
+    }
+}
@@ -0,0 +1,556 @@
+//! The tunnel and the mock contracts deployed on the test chains
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    from_slice, to_binary, Addr, Binary, CosmosMsg, Deps, DepsMut, Empty, Env,
+    Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannelConnectMsg, IbcChannelOpenMsg,
+    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, MessageInfo,
+    Order, Reply, Response, StdError, StdResult, Storage, SubMsg, WasmMsg,
+};
+use cosmwasm_tunnel::{
+    OutgoingPacket, PacketSource, ReceiveIcaResponseMsg, SenderInfo, TunnelError, TunnelErrorCode,
+    TunnelHost, TunnelReceiverMsg, TunnelRemote, LATEST_APP_VERSION,
+};
+use cw_storage_plus::{Item, Map};
+
+use crate::chain::{Contract, ContractResult};
+
+fn parse<T: cosmwasm_schema::serde::de::DeserializeOwned>(msg: &[u8]) -> ContractResult<T> {
+    from_slice(msg).map_err(|e| e.to_string())
+}
+
+/// The tunnel contract, through the entry points it exports
+pub struct TunnelApp;
+
+impl Contract for TunnelApp {
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        ic_tunnel::instantiate(deps, env, info, parse(msg)?).map_err(|e| e.to_string())
+    }
+
+    fn execute(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        ic_tunnel::execute(deps, env, info, parse(msg)?).map_err(|e| e.to_string())
+    }
+
+    fn query(&self, deps: Deps, env: Env, msg: &[u8]) -> ContractResult<Binary> {
+        ic_tunnel::query(deps, env, parse(msg)?).map_err(|e| e.to_string())
+    }
+
+    fn migrate(&self, deps: DepsMut, env: Env, msg: &[u8]) -> ContractResult<Response> {
+        ic_tunnel::migrate(deps, env, parse(msg)?).map_err(|e| e.to_string())
+    }
+
+    fn reply(&self, deps: DepsMut, env: Env, reply: Reply) -> ContractResult<Response> {
+        ic_tunnel::reply(deps, env, reply).map_err(|e| e.to_string())
+    }
+
+    fn sudo(&self, deps: DepsMut, env: Env, msg: &[u8]) -> ContractResult<Response> {
+        ic_tunnel::sudo(deps, env, parse(msg)?).map_err(|e| e.to_string())
+    }
+
+    fn ibc_channel_open(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcChannelOpenMsg,
+    ) -> ContractResult<Option<Ibc3ChannelOpenResponse>> {
+        ic_tunnel::ibc_channel_open(deps, env, msg).map_err(|e| e.to_string())
+    }
+
+    fn ibc_channel_connect(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcChannelConnectMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        ic_tunnel::ibc_channel_connect(deps, env, msg).map_err(|e| e.to_string())
+    }
+
+    fn ibc_packet_receive(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketReceiveMsg,
+    ) -> ContractResult<IbcReceiveResponse> {
+        ic_tunnel::ibc_packet_receive(deps, env, msg).map_err(|e| e.to_string())
+    }
+
+    fn ibc_packet_ack(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketAckMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        ic_tunnel::ibc_packet_ack(deps, env, msg).map_err(|e| e.to_string())
+    }
+
+    fn ibc_packet_timeout(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketTimeoutMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        ic_tunnel::ibc_packet_timeout(deps, env, msg).map_err(|e| e.to_string())
+    }
+}
+
+#[cw_serde]
+pub struct MinimalTunnelInstantiateMsg {
+    /// The controllers allowed to send operations
+    pub controllers: Vec<String>,
+}
+
+const CONTROLLERS: Item<Vec<Addr>> = Item::new("controllers");
+const SENT_NONCES: Map<(&str, &str), u64> = Map::new("sent_nonces");
+const RECEIVED_NONCES: Map<(&str, &str, &str), u64> = Map::new("received_nonces");
+const PROXIES: Map<(&str, &str, &str), Addr> = Map::new("proxies");
+const PENDING_SENDER: Item<SenderInfo> = Item::new("pending_sender");
+
+/// A tunnel contract embedding `TunnelHost` and `TunnelRemote` with the state they need and the default handlers,
+/// as a contract acting as a tunnel without forking `ic-tunnel` would.
+/// It overrides two hooks: only its controllers may send operations (`authorize`),
+/// and it instantiates every proxy from the code it is built with, without an admin (`create_account`).
+pub struct MinimalTunnel {
+    /// The code of the proxies, whatever code the controllers ask for
+    pub proxy_code_id: u64,
+}
+
+impl Contract for MinimalTunnel {
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        let msg: MinimalTunnelInstantiateMsg = parse(msg)?;
+        let controllers = msg
+            .controllers
+            .iter()
+            .map(|controller| deps.api.addr_validate(controller))
+            .collect::<StdResult<_>>()
+            .map_err(|e| e.to_string())?;
+        CONTROLLERS
+            .save(deps.storage, &controllers)
+            .map_err(|e| e.to_string())?;
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        self.execute_remote(deps, env, info, parse(msg)?)
+            .map_err(|e| e.to_string())
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, _msg: &[u8]) -> ContractResult<Binary> {
+        Err("query is not implemented".to_string())
+    }
+
+    fn reply(&self, deps: DepsMut, env: Env, reply: Reply) -> ContractResult<Response> {
+        // a failed callback of a controller does not revert the ack
+        if reply.id == <Self as TunnelHost>::ACK_CALLBACK_ID {
+            return Ok(Response::new());
+        }
+        TunnelRemote::reply(self, deps, env, reply).map_err(|e| e.to_string())
+    }
+
+    fn ibc_channel_open(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _msg: IbcChannelOpenMsg,
+    ) -> ContractResult<Option<Ibc3ChannelOpenResponse>> {
+        Ok(Some(Ibc3ChannelOpenResponse {
+            version: LATEST_APP_VERSION.to_string(),
+        }))
+    }
+
+    fn ibc_channel_connect(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _msg: IbcChannelConnectMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        Ok(IbcBasicResponse::new())
+    }
+
+    fn ibc_packet_receive(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketReceiveMsg,
+    ) -> ContractResult<IbcReceiveResponse> {
+        self.receive(deps, env, msg).map_err(|e| e.to_string())
+    }
+
+    fn ibc_packet_ack(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketAckMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        self.packet_ack(deps, env, msg).map_err(|e| e.to_string())
+    }
+
+    fn ibc_packet_timeout(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        msg: IbcPacketTimeoutMsg,
+    ) -> ContractResult<IbcBasicResponse> {
+        self.packet_timeout(deps, env, msg)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl TunnelHost for MinimalTunnel {
+    type Error = TunnelError;
+
+    fn next_nonce(
+        &self,
+        deps: DepsMut,
+        channel_id: &str,
+        controller: &str,
+    ) -> Result<u64, TunnelError> {
+        let nonce = SENT_NONCES
+            .may_load(deps.storage, (channel_id, controller))?
+            .unwrap_or_default()
+            + 1;
+        SENT_NONCES.save(deps.storage, (channel_id, controller), &nonce)?;
+        Ok(nonce)
+    }
+
+    fn authorize(
+        &self,
+        deps: Deps,
+        _env: &Env,
+        info: &MessageInfo,
+        _packet: &OutgoingPacket<Empty>,
+    ) -> Result<(), TunnelError> {
+        if !CONTROLLERS.load(deps.storage)?.contains(&info.sender) {
+            return Err(TunnelError::Unauthorized);
+        }
+        Ok(())
+    }
+}
+
+impl TunnelRemote for MinimalTunnel {
+    type Error = TunnelError;
+
+    fn account(
+        &self,
+        deps: Deps,
+        connection_id: &str,
+        port_id: &str,
+        controller: &str,
+    ) -> Result<Option<Addr>, TunnelError> {
+        Ok(PROXIES.may_load(deps.storage, (connection_id, port_id, controller))?)
+    }
+
+    fn save_account(
+        &self,
+        deps: DepsMut,
+        sender: &SenderInfo,
+        account: &Addr,
+    ) -> Result<(), TunnelError> {
+        let key = (
+            sender.connection_id.as_str(),
+            sender.port_id.as_str(),
+            sender.controller.as_str(),
+        );
+        Ok(PROXIES.save(deps.storage, key, account)?)
+    }
+
+    fn save_pending(
+        &self,
+        storage: &mut dyn Storage,
+        sender: &SenderInfo,
+    ) -> Result<(), TunnelError> {
+        Ok(PENDING_SENDER.save(storage, sender)?)
+    }
+
+    fn take_pending(&self, storage: &mut dyn Storage) -> Result<SenderInfo, TunnelError> {
+        let sender = PENDING_SENDER.load(storage)?;
+        PENDING_SENDER.remove(storage);
+        Ok(sender)
+    }
+
+    /// Only accepts nonces above the last received one
+    fn check_nonce(
+        &self,
+        deps: DepsMut,
+        source: &PacketSource,
+        controller: &str,
+        nonce: u64,
+    ) -> Result<(), TunnelError> {
+        let key = (
+            source.connection_id.as_str(),
+            source.port_id.as_str(),
+            controller,
+        );
+        if nonce
+            <= RECEIVED_NONCES
+                .may_load(deps.storage, key)?
+                .unwrap_or_default()
+        {
+            return Err(StdError::generic_err(format!("nonce {nonce} already received")).into());
+        }
+        Ok(RECEIVED_NONCES.save(deps.storage, key, &nonce)?)
+    }
+
+    fn error_code(&self, err: &TunnelError) -> TunnelErrorCode {
+        err.code()
+    }
+
+    fn create_account(
+        &self,
+        _env: &Env,
+        sender: &SenderInfo,
+        inst_msg: CosmosMsg,
+        _code_id: u64,
+    ) -> Result<SubMsg, TunnelError> {
+        let msg = WasmMsg::Instantiate {
+            admin: None,
+            code_id: self.proxy_code_id,
+            msg: to_binary(&inst_msg)?,
+            funds: vec![],
+            label: format!("minimal-tunnel-{}", sender.controller),
+        };
+        Ok(SubMsg::reply_on_success(msg, Self::INIT_CALLBACK_ID))
+    }
+}
+
+const ADMIN: Item<Addr> = Item::new("admin");
+const INIT_MSG: Item<Binary> = Item::new("init_msg");
+const MIGRATE_MSG: Item<Binary> = Item::new("migrate_msg");
+
+#[cw_serde]
+pub enum ProxyQueryMsg {
+    AdminList {},
+    /// The messages the proxy was instantiated and last migrated with
+    Messages {},
+}
+
+/// As the cw1-whitelist `AdminListResponse`
+#[cw_serde]
+pub struct AdminListResponse {
+    pub admins: Vec<String>,
+    pub mutable: bool,
+}
+
+#[cw_serde]
+pub struct ProxyMessagesResponse {
+    pub init_msg: Binary,
+    pub migrate_msg: Option<Binary>,
+}
+
+/// A proxy as deployed by the tunnel, with the whitelist of the `cw1_whitelist.wasm` artifact:
+/// its creator (the tunnel) is its only admin and the only address that can execute through it.
+/// The tunnel sends the dispatched `CosmosMsg` as the execute message, which the proxy re-sends as its own.
+pub struct MockProxy;
+
+impl Contract for MockProxy {
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        ADMIN
+            .save(deps.storage, &info.sender)
+            .map_err(|e| e.to_string())?;
+        INIT_MSG
+            .save(deps.storage, &msg.into())
+            .map_err(|e| e.to_string())?;
+        Ok(Response::new().add_attribute("action", "instantiate"))
+    }
+
+    fn execute(
+        &self,
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        let admin = ADMIN.load(deps.storage).map_err(|e| e.to_string())?;
+        if info.sender != admin {
+            return Err("Unauthorized".to_string());
+        }
+        let msg: CosmosMsg = parse(msg)?;
+        Ok(Response::new()
+            .add_message(msg)
+            .add_attribute("action", "execute"))
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: &[u8]) -> ContractResult<Binary> {
+        let res = match parse(msg)? {
+            ProxyQueryMsg::AdminList {} => to_binary(&AdminListResponse {
+                admins: vec![ADMIN.load(deps.storage).map_err(|e| e.to_string())?.into()],
+                mutable: false,
+            }),
+            ProxyQueryMsg::Messages {} => to_binary(&ProxyMessagesResponse {
+                init_msg: INIT_MSG.load(deps.storage).map_err(|e| e.to_string())?,
+                migrate_msg: MIGRATE_MSG
+                    .may_load(deps.storage)
+                    .map_err(|e| e.to_string())?,
+            }),
+        };
+        res.map_err(|e| e.to_string())
+    }
+
+    fn migrate(&self, deps: DepsMut, _env: Env, msg: &[u8]) -> ContractResult<Response> {
+        MIGRATE_MSG
+            .save(deps.storage, &msg.into())
+            .map_err(|e| e.to_string())?;
+        Ok(Response::new().add_attribute("action", "migrate"))
+    }
+}
+
+/// A proxy code that refuses to be migrated to, to test failed migrations
+pub struct FailingMigrateProxy;
+
+impl Contract for FailingMigrateProxy {
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        MockProxy.instantiate(deps, env, info, msg)
+    }
+
+    fn execute(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        MockProxy.execute(deps, env, info, msg)
+    }
+
+    fn query(&self, deps: Deps, env: Env, msg: &[u8]) -> ContractResult<Binary> {
+        MockProxy.query(deps, env, msg)
+    }
+
+    fn migrate(&self, _deps: DepsMut, _env: Env, _msg: &[u8]) -> ContractResult<Response> {
+        Err("migration refused".to_string())
+    }
+}
+
+const COUNT: Item<u64> = Item::new("count");
+
+#[cw_serde]
+pub enum CounterExecuteMsg {
+    Increment {},
+    Fail {},
+}
+
+#[cw_serde]
+pub enum CounterQueryMsg {
+    Count {},
+}
+
+/// A target for dispatched messages on the remote chain
+pub struct MockCounter;
+
+impl Contract for MockCounter {
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        _msg: &[u8],
+    ) -> ContractResult<Response> {
+        COUNT.save(deps.storage, &0).map_err(|e| e.to_string())?;
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &self,
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        match parse(msg)? {
+            CounterExecuteMsg::Increment {} => {
+                let count = COUNT
+                    .update(deps.storage, |count| StdResult::Ok(count + 1))
+                    .map_err(|e| e.to_string())?;
+                Ok(Response::new().add_attribute("count", count.to_string()))
+            }
+            CounterExecuteMsg::Fail {} => Err("counter failed".to_string()),
+        }
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: &[u8]) -> ContractResult<Binary> {
+        let CounterQueryMsg::Count {} = parse(msg)?;
+        let count = COUNT.load(deps.storage).map_err(|e| e.to_string())?;
+        to_binary(&count).map_err(|e| e.to_string())
+    }
+}
+
+const CALLBACKS: Map<&str, ReceiveIcaResponseMsg> = Map::new("callbacks");
+
+#[cw_serde]
+pub enum ControllerQueryMsg {
+    /// All received callbacks, by job id
+    Callbacks {},
+}
+
+/// A controller on the host chain recording the callbacks of the tunnel
+pub struct MockController;
+
+impl Contract for MockController {
+    fn instantiate(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        _msg: &[u8],
+    ) -> ContractResult<Response> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &self,
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: &[u8],
+    ) -> ContractResult<Response> {
+        let TunnelReceiverMsg::ReceiveTunnelResponse(callback) = parse(msg)?;
+        CALLBACKS
+            .save(deps.storage, &callback.id.clone(), &callback)
+            .map_err(|e| e.to_string())?;
+        Ok(Response::new().add_attribute("action", "receive_tunnel_response"))
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: &[u8]) -> ContractResult<Binary> {
+        let ControllerQueryMsg::Callbacks {} = parse(msg)?;
+        let callbacks = CALLBACKS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, callback)| callback))
+            .collect::<StdResult<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        to_binary(&callbacks).map_err(|e| e.to_string())
+    }
+}
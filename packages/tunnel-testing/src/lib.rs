@@ -0,0 +1,21 @@
+//! A two-chain IBC simulation to test the tunnel offline.
+//!
+//! `TestEnv` runs a host and a remote chain in memory, each with a tunnel, and a relayer driven by the test.
+//! `Network` runs any number of chains joined by channels, to test forwarding.
+//! Contracts on the chains are `Contract` implementations, such as the mock proxies in `contracts`.
+//! Messages and submessages are executed and reverted as in wasmd, with replies and `wasm-` events.
+//! The tunnel is called through the entry points it exports, and native tokens move as in the bank module:
+//! `Chain::set_balance` funds an address, funds sent with a call or a `BankMsg` fail if the sender does not hold them.
+mod chain;
+pub mod contracts;
+mod network;
+mod relayer;
+mod storage;
+
+pub use crate::chain::{ibc_port, AppResponse, Chain, Contract, ContractResult, StargateMsg};
+pub use crate::network::Network;
+pub use crate::relayer::{
+    PendingAck, Side, TestEnv, HOST_CHAIN_ID, HOST_CHANNEL_ID, HOST_CONNECTION_ID, REMOTE_CHAIN_ID,
+    REMOTE_CHANNEL_ID, REMOTE_CONNECTION_ID,
+};
+pub use crate::storage::TestStorage;
@@ -0,0 +1,175 @@
+use cosmwasm_std::{Binary, IbcPacket, IbcTimeout};
+use cosmwasm_tunnel::PACKET_LIFETIME;
+
+use crate::chain::{AppResponse, Chain, ContractResult};
+use crate::relayer::open_channel;
+
+/// A channel between the tunnels of two chains of a `Network`
+struct Link {
+    a: usize,
+    b: usize,
+}
+
+impl Link {
+    /// The chain at the other end of the link from `chain` on the channel `channel_id`, if it is an end of the link
+    fn other(&self, index: usize, chain: usize, channel_id: &str) -> Option<usize> {
+        if chain == self.a && channel_id == channel_id_of(index, true) {
+            Some(self.b)
+        } else if chain == self.b && channel_id == channel_id_of(index, false) {
+            Some(self.a)
+        } else {
+            None
+        }
+    }
+}
+
+/// The channel id on the `a` or `b` end of the link at `index`, unique in the network
+fn channel_id_of(index: usize, a: bool) -> String {
+    format!("channel-{}", 2 * index + usize::from(!a))
+}
+
+/// The connection id on the `a` or `b` end of the link at `index`, unique in the network
+fn connection_id_of(index: usize, a: bool) -> String {
+    format!("connection-{}", 2 * index + usize::from(!a))
+}
+
+/// An acknowledgement written on the receiving chain, waiting to be relayed back to `chain`
+struct NetworkAck {
+    chain: usize,
+    packet: IbcPacket,
+    ack: Binary,
+}
+
+/// Chains running a tunnel each, joined by channels between their tunnels, to test forwarding.
+/// Chains are referred to by their index, the relayer is driven by the test as in `TestEnv`.
+pub struct Network {
+    pub chains: Vec<Chain>,
+    links: Vec<Link>,
+    acks: Vec<NetworkAck>,
+}
+
+impl Network {
+    /// Creates the chains and opens a channel for each `(a, b)` link, with the latest version.
+    /// The link at index `i` has `channel-{2i}` and `connection-{2i}` on `a`, `channel-{2i+1}` and `connection-{2i+1}` on `b`.
+    pub fn new(chain_ids: &[&str], links: &[(usize, usize)]) -> Self {
+        let mut chains: Vec<Chain> = chain_ids.iter().map(|id| Chain::new(id)).collect();
+        for (index, (a, b)) in links.iter().copied().enumerate() {
+            assert!(a < b, "links are given as (a, b) with a < b");
+            let (left, right) = chains.split_at_mut(b);
+            open_channel(
+                (
+                    &mut left[a],
+                    &channel_id_of(index, true),
+                    &connection_id_of(index, true),
+                ),
+                (
+                    &mut right[0],
+                    &channel_id_of(index, false),
+                    &connection_id_of(index, false),
+                ),
+                "",
+            )
+            .expect("channel handshake succeeds");
+        }
+        let links = links.iter().map(|&(a, b)| Link { a, b }).collect();
+        Network {
+            chains,
+            links,
+            acks: vec![],
+        }
+    }
+
+    fn link(&self, from: usize, to: usize) -> (usize, bool) {
+        self.links
+            .iter()
+            .enumerate()
+            .find_map(|(index, link)| match (link.a, link.b) {
+                (a, b) if (a, b) == (from, to) => Some((index, true)),
+                (a, b) if (a, b) == (to, from) => Some((index, false)),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("chains {from} and {to} are not linked"))
+    }
+
+    /// The channel on chain `from` to chain `to`
+    pub fn channel_id(&self, from: usize, to: usize) -> String {
+        let (index, a) = self.link(from, to);
+        channel_id_of(index, a)
+    }
+
+    /// The connection on chain `from` to chain `to`
+    pub fn connection_id(&self, from: usize, to: usize) -> String {
+        let (index, a) = self.link(from, to);
+        connection_id_of(index, a)
+    }
+
+    /// The packets sent from `chain` waiting to be delivered, in delivery order
+    pub fn pending_packets(&self, chain: usize) -> Vec<IbcPacket> {
+        self.chains[chain].outbox().to_vec()
+    }
+
+    /// Sends `data` from the tunnel of `chain` on `channel_id`, as a tunnel that does not follow the protocol
+    pub fn send_raw(&mut self, chain: usize, channel_id: &str, data: Binary) -> ContractResult<()> {
+        let chain = &mut self.chains[chain];
+        let timeout = IbcTimeout::with_timestamp(chain.block.time.plus_seconds(PACKET_LIFETIME));
+        chain.send_packet(&chain.tunnel(), channel_id.to_string(), data, timeout)?;
+        Ok(())
+    }
+
+    /// Queues a packet again, as a relayer submitting an already delivered packet
+    pub fn resubmit(&mut self, chain: usize, packet: IbcPacket) {
+        self.chains[chain].outbox_mut().insert(0, packet);
+    }
+
+    /// Delivers the first pending packet from `chain` and queues its ack, returns the ack
+    pub fn deliver_next(&mut self, chain: usize) -> ContractResult<Binary> {
+        let outbox = self.chains[chain].outbox_mut();
+        if outbox.is_empty() {
+            return Err(format!("no pending packet from chain {chain}"));
+        }
+        let packet = outbox.remove(0);
+        let dest = self
+            .links
+            .iter()
+            .enumerate()
+            .find_map(|(index, link)| link.other(index, chain, &packet.src.channel_id))
+            .ok_or_else(|| format!("channel {} not found", packet.src.channel_id))?;
+        let (ack, _) = self.chains[dest].receive_packet(packet.clone())?;
+        self.acks.push(NetworkAck {
+            chain,
+            packet,
+            ack: ack.clone(),
+        });
+        Ok(ack)
+    }
+
+    /// Relays the first pending ack back to the chain that sent the packet
+    fn relay_next_ack(&mut self) -> ContractResult<AppResponse> {
+        let NetworkAck { chain, packet, ack } = self.acks.remove(0);
+        self.chains[chain].ack_packet(packet, ack)
+    }
+
+    /// Delivers all packets and relays all acks, including the ones they cause, in order.
+    /// Returns the delivered packets with their acks.
+    pub fn relay_all(&mut self) -> ContractResult<Vec<(IbcPacket, Binary)>> {
+        let mut relayed = vec![];
+        loop {
+            if let Some(chain) =
+                (0..self.chains.len()).find(|c| !self.chains[*c].outbox().is_empty())
+            {
+                let ack = self.deliver_next(chain)?;
+                let packet = self
+                    .acks
+                    .last()
+                    .expect("delivered packet has an ack")
+                    .packet
+                    .clone();
+                relayed.push((packet, ack));
+            } else if !self.acks.is_empty() {
+                self.relay_next_ack()?;
+            } else {
+                return Ok(relayed);
+            }
+        }
+    }
+}
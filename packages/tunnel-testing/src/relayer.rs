@@ -0,0 +1,306 @@
+use cosmwasm_std::{
+    Binary, IbcChannel, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcEndpoint, IbcPacket, IbcTimeout,
+};
+use cosmwasm_tunnel::{ChannelMetadata, PacketEncoding, APP_ORDER, PACKET_LIFETIME};
+
+use crate::chain::{ibc_port, AppResponse, Chain, ContractResult};
+
+pub const HOST_CHAIN_ID: &str = "host-1";
+pub const REMOTE_CHAIN_ID: &str = "remote-1";
+pub const HOST_CONNECTION_ID: &str = "connection-0";
+pub const REMOTE_CONNECTION_ID: &str = "connection-1";
+pub const HOST_CHANNEL_ID: &str = "channel-0";
+pub const REMOTE_CHANNEL_ID: &str = "channel-1";
+
+/// The chain a packet was sent from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Host,
+    Remote,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::Host => Side::Remote,
+            Side::Remote => Side::Host,
+        }
+    }
+}
+
+/// An acknowledgement written on the receiving chain, waiting to be relayed back
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingAck {
+    /// The chain the packet was sent from
+    pub side: Side,
+    pub packet: IbcPacket,
+    pub ack: Binary,
+}
+
+/// A host and a remote chain, each running a tunnel, joined by a channel between the tunnels.
+/// The relayer is driven by the test: packets wait in the sending chain's outbox until they are
+/// delivered, delayed, reordered, dropped or timed out, and acks wait until they are relayed back.
+pub struct TestEnv {
+    pub host: Chain,
+    pub remote: Chain,
+    acks: Vec<PendingAck>,
+}
+
+impl Default for TestEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestEnv {
+    /// Creates both chains and runs the channel handshake between their tunnels,
+    /// the relayer leaves the version to the tunnel opening the channel
+    pub fn new() -> Self {
+        Self::with_init_version("")
+    }
+
+    /// Like `new`, with the relayer proposing `version` when it opens the channel on the host,
+    /// `cw-tunnel-v1` opens a channel as between two v1 tunnels
+    pub fn with_init_version(version: &str) -> Self {
+        Self::try_with_init_version(version).expect("channel handshake succeeds")
+    }
+
+    /// Like `with_init_version`, returning the error of a failed handshake
+    pub fn try_with_init_version(version: &str) -> ContractResult<Self> {
+        let mut env = TestEnv {
+            host: Chain::new(HOST_CHAIN_ID),
+            remote: Chain::new(REMOTE_CHAIN_ID),
+            acks: vec![],
+        };
+        env.open_channel(version)?;
+        Ok(env)
+    }
+
+    /// Like `new`, with chains created by the test, e.g. running another tunnel contract
+    pub fn with_chains(host: Chain, remote: Chain) -> Self {
+        let mut env = TestEnv {
+            host,
+            remote,
+            acks: vec![],
+        };
+        env.open_channel("").expect("channel handshake succeeds");
+        env
+    }
+
+    /// Like `new`, with the relayer proposing channel metadata with `encoding`
+    pub fn with_encoding(encoding: PacketEncoding) -> Self {
+        let proposal = ChannelMetadata {
+            encoding,
+            ..ChannelMetadata::new(HOST_CHAIN_ID, vec![])
+        };
+        Self::with_init_version(&proposal.to_version().expect("metadata serializes"))
+    }
+
+    fn open_channel(&mut self, init_version: &str) -> ContractResult<()> {
+        open_channel(
+            (&mut self.host, HOST_CHANNEL_ID, HOST_CONNECTION_ID),
+            (&mut self.remote, REMOTE_CHANNEL_ID, REMOTE_CONNECTION_ID),
+            init_version,
+        )
+    }
+
+    pub fn chain(&self, side: Side) -> &Chain {
+        match side {
+            Side::Host => &self.host,
+            Side::Remote => &self.remote,
+        }
+    }
+
+    pub fn chain_mut(&mut self, side: Side) -> &mut Chain {
+        match side {
+            Side::Host => &mut self.host,
+            Side::Remote => &mut self.remote,
+        }
+    }
+
+    /// The packets sent from `side` waiting to be delivered, in delivery order
+    pub fn pending_packets(&self, side: Side) -> Vec<IbcPacket> {
+        self.chain(side).outbox().to_vec()
+    }
+
+    /// The acknowledgements of packets sent from `side` waiting to be relayed back
+    pub fn pending_acks(&self, side: Side) -> Vec<PendingAck> {
+        self.acks
+            .iter()
+            .filter(|ack| ack.side == side)
+            .cloned()
+            .collect()
+    }
+
+    /// Sends `data` from the tunnel of `side` on its channel, as a tunnel that does not follow the protocol
+    pub fn send_raw(&mut self, side: Side, data: Binary) -> ContractResult<()> {
+        let channel_id = match side {
+            Side::Host => HOST_CHANNEL_ID,
+            Side::Remote => REMOTE_CHANNEL_ID,
+        };
+        let chain = self.chain_mut(side);
+        let timeout = IbcTimeout::with_timestamp(chain.block.time.plus_seconds(PACKET_LIFETIME));
+        chain.send_packet(&chain.tunnel(), channel_id.to_string(), data, timeout)?;
+        Ok(())
+    }
+
+    fn take_packet(&mut self, side: Side, sequence: u64) -> ContractResult<IbcPacket> {
+        let outbox = self.chain_mut(side).outbox_mut();
+        let index = outbox
+            .iter()
+            .position(|packet| packet.sequence == sequence)
+            .ok_or_else(|| format!("no pending packet {sequence} from {side:?}"))?;
+        Ok(outbox.remove(index))
+    }
+
+    /// Delivers the packet to the other chain and queues its ack, returns the ack
+    pub fn deliver(&mut self, side: Side, sequence: u64) -> ContractResult<Binary> {
+        let packet = self.take_packet(side, sequence)?;
+        let dest = self.chain_mut(side.other());
+        if dest.has_timed_out(&packet) {
+            self.chain_mut(side).outbox_mut().insert(0, packet);
+            return Err(format!("packet {sequence} from {side:?} has timed out"));
+        }
+        let (ack, _) = dest.receive_packet(packet.clone())?;
+        self.acks.push(PendingAck {
+            side,
+            packet,
+            ack: ack.clone(),
+        });
+        Ok(ack)
+    }
+
+    /// Delivers the first pending packet from `side`
+    pub fn deliver_next(&mut self, side: Side) -> ContractResult<Binary> {
+        let sequence = self
+            .chain(side)
+            .outbox()
+            .first()
+            .map(|packet| packet.sequence)
+            .ok_or_else(|| format!("no pending packet from {side:?}"))?;
+        self.deliver(side, sequence)
+    }
+
+    /// Moves the packet to the end of the queue
+    pub fn delay(&mut self, side: Side, sequence: u64) -> ContractResult<()> {
+        let packet = self.take_packet(side, sequence)?;
+        self.chain_mut(side).outbox_mut().push(packet);
+        Ok(())
+    }
+
+    /// Moves the packets to the front of the queue, in the given order
+    pub fn reorder(&mut self, side: Side, sequences: &[u64]) -> ContractResult<()> {
+        let packets = sequences
+            .iter()
+            .map(|sequence| self.take_packet(side, *sequence))
+            .collect::<ContractResult<Vec<_>>>()?;
+        self.chain_mut(side).outbox_mut().splice(0..0, packets);
+        Ok(())
+    }
+
+    /// Queues a packet again, as a relayer submitting an already delivered packet
+    pub fn resubmit(&mut self, side: Side, packet: IbcPacket) {
+        self.chain_mut(side).outbox_mut().insert(0, packet);
+    }
+
+    /// Loses the packet, it is neither received nor timed out
+    pub fn drop_packet(&mut self, side: Side, sequence: u64) -> ContractResult<IbcPacket> {
+        self.take_packet(side, sequence)
+    }
+
+    /// Loses the ack of the packet, the packet stays received but is never acknowledged
+    pub fn drop_ack(&mut self, side: Side, sequence: u64) -> ContractResult<PendingAck> {
+        let index = self.ack_index(side, sequence)?;
+        Ok(self.acks.remove(index))
+    }
+
+    /// Times out the packet on the sending chain, which must be past the timeout on the other chain
+    pub fn timeout(&mut self, side: Side, sequence: u64) -> ContractResult<AppResponse> {
+        let packet = self.take_packet(side, sequence)?;
+        if !self.chain(side.other()).has_timed_out(&packet) {
+            self.chain_mut(side).outbox_mut().insert(0, packet);
+            return Err(format!("packet {sequence} from {side:?} has not timed out"));
+        }
+        self.chain_mut(side).timeout_packet(packet)
+    }
+
+    fn ack_index(&self, side: Side, sequence: u64) -> ContractResult<usize> {
+        self.acks
+            .iter()
+            .position(|ack| ack.side == side && ack.packet.sequence == sequence)
+            .ok_or_else(|| format!("no pending ack of packet {sequence} from {side:?}"))
+    }
+
+    /// Relays the ack of the packet back to the chain that sent it
+    pub fn relay_ack(&mut self, side: Side, sequence: u64) -> ContractResult<AppResponse> {
+        let index = self.ack_index(side, sequence)?;
+        let PendingAck { packet, ack, .. } = self.acks.remove(index);
+        self.chain_mut(side).ack_packet(packet, ack)
+    }
+
+    /// Delivers the packet and relays its ack back, returns the ack
+    pub fn relay(&mut self, side: Side, sequence: u64) -> ContractResult<Binary> {
+        let ack = self.deliver(side, sequence)?;
+        self.relay_ack(side, sequence)?;
+        Ok(ack)
+    }
+
+    /// Delivers all packets and relays all acks, including the ones they cause, in order.
+    /// Returns the delivered packets with their acks.
+    pub fn relay_all(&mut self) -> ContractResult<Vec<(IbcPacket, Binary)>> {
+        let mut relayed = vec![];
+        loop {
+            if let Some(side) = [Side::Host, Side::Remote]
+                .into_iter()
+                .find(|side| !self.chain(*side).outbox().is_empty())
+            {
+                self.deliver_next(side)?;
+                let pending = self.acks.last().expect("delivered packet has an ack");
+                relayed.push((pending.packet.clone(), pending.ack.clone()));
+            } else if let Some(pending) = self.acks.first() {
+                let (side, sequence) = (pending.side, pending.packet.sequence);
+                self.relay_ack(side, sequence)?;
+            } else {
+                return Ok(relayed);
+            }
+        }
+    }
+}
+
+/// Runs the channel handshake between the tunnels of two chains, each given with its end of the channel
+/// and its connection. `a` opens the channel with the relayer proposing `init_version`.
+pub(crate) fn open_channel(
+    (a, a_channel_id, a_connection_id): (&mut Chain, &str, &str),
+    (b, b_channel_id, b_connection_id): (&mut Chain, &str, &str),
+    init_version: &str,
+) -> ContractResult<()> {
+    let a_end = IbcEndpoint {
+        port_id: ibc_port(&a.tunnel()),
+        channel_id: a_channel_id.to_string(),
+    };
+    let b_end = IbcEndpoint {
+        port_id: ibc_port(&b.tunnel()),
+        channel_id: b_channel_id.to_string(),
+    };
+    let channel = |version: &str, on_a: bool| {
+        let (endpoint, counterparty, connection_id) = if on_a {
+            (a_end.clone(), b_end.clone(), a_connection_id)
+        } else {
+            (b_end.clone(), a_end.clone(), b_connection_id)
+        };
+        IbcChannel::new(endpoint, counterparty, APP_ORDER, version, connection_id)
+    };
+
+    let init_version =
+        a.ibc_channel_open(IbcChannelOpenMsg::new_init(channel(init_version, true)))?;
+    let version = b.ibc_channel_open(IbcChannelOpenMsg::new_try(
+        channel(&init_version, false),
+        &init_version,
+    ))?;
+    a.ibc_channel_connect(IbcChannelConnectMsg::new_ack(
+        channel(&version, true),
+        &version,
+    ))?;
+    b.ibc_channel_connect(IbcChannelConnectMsg::new_confirm(channel(&version, false)))?;
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use cosmwasm_std::{Order, Record, Storage};
+
+/// The storage of a contract, cloned to revert the state of failed (sub)messages
+#[derive(Clone, Debug, Default)]
+pub struct TestStorage {
+    data: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Storage for TestStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.data.get(key).cloned()
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        let start = start.map_or(Bound::Unbounded, |s| Bound::Included(s.to_vec()));
+        let end = end.map_or(Bound::Unbounded, |e| Bound::Excluded(e.to_vec()));
+        // an empty range panics in `BTreeMap::range` if start > end
+        if let (Bound::Included(s), Bound::Excluded(e)) = (&start, &end) {
+            if s >= e {
+                return Box::new(std::iter::empty());
+            }
+        }
+        let iter = self
+            .data
+            .range((start, end))
+            .map(|(k, v)| (k.clone(), v.clone()));
+        match order {
+            Order::Ascending => Box::new(iter),
+            Order::Descending => Box::new(iter.rev()),
+        }
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.data.insert(key.to_vec(), value.to_vec());
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.data.remove(key);
+    }
+}
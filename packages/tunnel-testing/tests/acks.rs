@@ -0,0 +1,236 @@
+//! Decoding the acknowledgements of each packet kind, as host contracts do on-chain
+use cosmwasm_schema::serde::Serialize;
+use cosmwasm_std::{to_binary, BankMsg, Binary, CosmosMsg, Empty, StdError, SubMsgResult};
+use cosmwasm_tunnel::{
+    AckError, ChunkResponse, DispatchMigrateResponse, ErrorResponse, ForwardResponse,
+    InstantiateResponse, PacketChunk, PacketMsg, StdAck, TunnelAck, TunnelErrorCode,
+    TunnelOperation, WhoAmIResponse,
+};
+
+const CONTROLLER: &str = "controller";
+
+fn job_id() -> Option<String> {
+    Some("job".to_string())
+}
+
+fn noop() -> CosmosMsg {
+    BankMsg::Burn { amount: vec![] }.into()
+}
+
+fn dispatch() -> PacketMsg {
+    PacketMsg::Dispatch {
+        controller: CONTROLLER.to_string(),
+        msg: noop(),
+        job_id: job_id(),
+        fail_on_error: false,
+        nonce: 1,
+    }
+}
+
+fn chunk(operation: TunnelOperation) -> PacketMsg {
+    PacketMsg::Chunk {
+        controller: CONTROLLER.to_string(),
+        job_id: job_id(),
+        operation,
+        chunk: PacketChunk {
+            hash: Binary::from(vec![0; 32]),
+            index: 0,
+            total: 2,
+            data: Binary::from(b"{}".to_vec()),
+        },
+    }
+}
+
+fn success(data: &impl Serialize) -> StdAck {
+    StdAck::Result(to_binary(data).unwrap())
+}
+
+fn failure(code: TunnelErrorCode) -> (StdAck, ErrorResponse) {
+    let reason = ErrorResponse {
+        code,
+        message: "failed".to_string(),
+        job_id: job_id(),
+    };
+    (StdAck::error_with(&reason).unwrap(), reason)
+}
+
+#[test]
+fn acks_decode_into_the_response_of_their_packet() {
+    let instantiated = InstantiateResponse {
+        contract_address: "proxy".to_string(),
+        job_id: job_id(),
+    };
+    let dispatched = DispatchMigrateResponse {
+        result: SubMsgResult::Err("counter failed".to_string()),
+        job_id: job_id(),
+    };
+    let who_am_i = WhoAmIResponse {
+        account: "proxy".to_string(),
+    };
+    let forwarded = ForwardResponse { job_id: job_id() };
+    let chunked = ChunkResponse {
+        received: 1,
+        total: 2,
+        job_id: job_id(),
+    };
+    let cases: Vec<(PacketMsg, StdAck, TunnelAck)> = vec![
+        (
+            PacketMsg::Instantiate {
+                controller: CONTROLLER.to_string(),
+                inst_msg: noop(),
+                job_id: job_id(),
+                code_id: 1,
+                nonce: 1,
+            },
+            success(&instantiated),
+            TunnelAck::Instantiate(instantiated.clone()),
+        ),
+        (
+            PacketMsg::Migrate {
+                controller: CONTROLLER.to_string(),
+                migration_msg: noop(),
+                new_code_id: 2,
+                job_id: job_id(),
+                fail_on_error: false,
+                nonce: 1,
+            },
+            success(&dispatched),
+            TunnelAck::Migrate(dispatched.clone()),
+        ),
+        (
+            dispatch(),
+            success(&dispatched),
+            TunnelAck::Dispatch(dispatched.clone()),
+        ),
+        (
+            PacketMsg::WhoAmI {
+                controller: CONTROLLER.to_string(),
+                nonce: 1,
+            },
+            success(&who_am_i),
+            TunnelAck::WhoAmI(who_am_i),
+        ),
+        (
+            PacketMsg::Forward {
+                hops: vec!["channel-7".to_string()],
+                visited: vec![],
+                packet: Box::new(dispatch()),
+            },
+            success(&forwarded),
+            TunnelAck::Forward(forwarded.clone()),
+        ),
+        (
+            PacketMsg::ForwardAck {
+                controller: CONTROLLER.to_string(),
+                job_id: job_id(),
+                nonce: 1,
+                ack: success(&dispatched),
+            },
+            success(&forwarded),
+            TunnelAck::Forward(forwarded),
+        ),
+        // a chunk is acknowledged with the progress of its upload,
+        // the one completing the packet with the response to the packet
+        (
+            chunk(TunnelOperation::Dispatch),
+            success(&chunked),
+            TunnelAck::Chunk(chunked),
+        ),
+        (
+            chunk(TunnelOperation::Dispatch),
+            success(&dispatched),
+            TunnelAck::Dispatch(dispatched),
+        ),
+        (
+            chunk(TunnelOperation::Instantiate),
+            success(&instantiated),
+            TunnelAck::Instantiate(instantiated),
+        ),
+    ];
+    for (packet, ack, expected) in cases {
+        assert_eq!(
+            TunnelAck::from_std_ack(&packet, &ack).unwrap(),
+            expected,
+            "{packet:?}"
+        );
+        assert_eq!(TunnelAck::decode(&packet, &ack.ack()).unwrap(), expected);
+    }
+}
+
+#[test]
+fn error_acks_decode_into_their_reason() {
+    let (ack, reason) = failure(TunnelErrorCode::ExecutionFailed);
+    assert_eq!(
+        TunnelAck::from_std_ack(&dispatch(), &ack).unwrap(),
+        TunnelAck::Error(reason.clone())
+    );
+    assert_eq!(
+        TunnelAck::from_std_ack(&chunk(TunnelOperation::Dispatch), &ack).unwrap(),
+        TunnelAck::Error(reason.clone())
+    );
+    // nor does a packet without an operation of its own
+    let forward_ack: PacketMsg = PacketMsg::ForwardAck {
+        controller: CONTROLLER.to_string(),
+        job_id: job_id(),
+        nonce: 1,
+        ack: ack.clone(),
+    };
+    assert_eq!(
+        TunnelAck::from_std_ack(&forward_ack, &ack).unwrap(),
+        TunnelAck::Error(reason.clone())
+    );
+    assert_eq!(
+        ack.try_decode::<DispatchMigrateResponse>(),
+        Err(AckError::Remote(reason))
+    );
+
+    // errors of the runtime or of older tunnels are not structured
+    let ack = StdAck::Error("IBC Packet Error: out of gas".to_string());
+    let TunnelAck::Error(reason) = TunnelAck::from_std_ack(&dispatch(), &ack).unwrap() else {
+        panic!("expected an error");
+    };
+    assert_eq!(reason.code, TunnelErrorCode::Unknown);
+    assert_eq!(reason.message, "IBC Packet Error: out of gas");
+    assert_eq!(reason.job_id, None);
+}
+
+#[test]
+fn invalid_acks_are_errors() {
+    // the data of another response
+    let ack = success(&WhoAmIResponse {
+        account: "proxy".to_string(),
+    });
+    assert!(matches!(
+        TunnelAck::from_std_ack(&dispatch(), &ack),
+        Err(StdError::ParseErr { .. })
+    ));
+    assert!(matches!(
+        ack.try_decode::<InstantiateResponse>(),
+        Err(AckError::Std(StdError::ParseErr { .. }))
+    ));
+    // bytes that are not a `StdAck`
+    for bytes in [&b"not json"[..], b"{}", b"{\"result\":1}"] {
+        assert!(TunnelAck::decode(&dispatch(), &Binary::from(bytes)).is_err());
+    }
+}
+
+#[test]
+fn std_acks_do_not_panic() {
+    let data = to_binary(&Empty {}).unwrap();
+    assert_eq!(StdAck::Result(data.clone()).into_result(), Ok(data.clone()));
+    assert_eq!(
+        StdAck::Error("failed".to_string()).into_result(),
+        Err("failed".to_string())
+    );
+    assert_eq!(
+        StdAck::success(Empty {}).unwrap(),
+        StdAck::Result(data).ack()
+    );
+
+    let (ack, reason) = failure(TunnelErrorCode::Unauthorized);
+    assert_eq!(StdAck::fail_with(&reason).unwrap(), ack.ack());
+    assert_eq!(
+        ErrorResponse::from_ack_error(&ack.into_result().unwrap_err()),
+        reason
+    );
+}
@@ -0,0 +1,174 @@
+#![cfg(all(feature = "host", feature = "remote"))]
+use cosmwasm_std::{coin, coins, from_binary, Addr, BankMsg, CosmosMsg, Empty, ReplyOn, WasmMsg};
+use cosmwasm_tunnel::{ExecuteMsg, TunnelContract};
+use tunnel_testing::{Side, TestEnv, HOST_CHANNEL_ID};
+
+const CONTROLLER: &str = "controller";
+
+fn send_coins() -> CosmosMsg {
+    BankMsg::Send {
+        to_address: "recipient".to_string(),
+        amount: coins(100, "ucosm"),
+    }
+    .into()
+}
+
+fn tunnel() -> TunnelContract {
+    TunnelContract::new(Addr::unchecked("tunnel"))
+}
+
+#[test]
+fn calls_set_the_parameters_of_the_operation() {
+    let call = tunnel()
+        .dispatch(HOST_CHANNEL_ID, send_coins())
+        .job_id("job")
+        .timeout(600)
+        .fail_on_error();
+    assert_eq!(
+        call.msg(),
+        &ExecuteMsg::RemoteDispatch {
+            dispatch_msg: send_coins(),
+            job_id: Some("job".to_string()),
+            channel_id: HOST_CHANNEL_ID.to_string(),
+            fail_on_error: true,
+            timeout: Some(600),
+        }
+    );
+
+    // parameters an operation does not have are ignored
+    let call = tunnel()
+        .query_remote_addr::<Empty>(HOST_CHANNEL_ID)
+        .job_id("job")
+        .fail_on_error();
+    assert_eq!(
+        call.msg(),
+        &ExecuteMsg::QueryRemoteAddr {
+            channel_id: HOST_CHANNEL_ID.to_string(),
+            timeout: None,
+        }
+    );
+}
+
+#[test]
+fn forwarded_calls_set_the_parameters_of_the_inner_operation() {
+    let call = tunnel()
+        .dispatch(HOST_CHANNEL_ID, send_coins())
+        .forward(vec!["channel-7".to_string()])
+        .execute_as(CONTROLLER)
+        .job_id("job")
+        .timeout(600);
+    let ExecuteMsg::ExecuteAs { controller, msg } = call.msg() else {
+        panic!("not sent as an operator: {:?}", call.msg());
+    };
+    assert_eq!(controller, CONTROLLER);
+    let ExecuteMsg::RemoteForward { msg, hops } = msg.as_ref() else {
+        panic!("not forwarded: {msg:?}");
+    };
+    assert_eq!(hops, &vec!["channel-7".to_string()]);
+    assert!(matches!(
+        msg.as_ref(),
+        ExecuteMsg::RemoteDispatch {
+            job_id: Some(job_id),
+            timeout: Some(600),
+            ..
+        } if job_id == "job"
+    ));
+}
+
+#[test]
+fn calls_are_sent_without_funds() {
+    let call = tunnel()
+        .dispatch(HOST_CHANNEL_ID, send_coins())
+        .job_id("job");
+    let msg: CosmosMsg = call.clone().into_cosmos_msg().unwrap();
+    let CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr,
+        msg,
+        funds,
+    }) = msg
+    else {
+        panic!("not a contract call");
+    };
+    assert_eq!(contract_addr, "tunnel");
+    assert_eq!(funds, vec![]);
+    assert_eq!(&from_binary::<ExecuteMsg>(&msg).unwrap(), call.msg());
+
+    let sub_msg = call.into_sub_msg::<Empty>(7, ReplyOn::Error).unwrap();
+    assert_eq!(sub_msg.id, 7);
+    assert_eq!(sub_msg.reply_on, ReplyOn::Error);
+}
+
+#[test]
+fn tunnel_refuses_funds() {
+    let mut env = TestEnv::new();
+    let tunnel = TunnelContract::new(env.host.tunnel());
+    for sender in [CONTROLLER, "operator"] {
+        env.host
+            .set_balance(&Addr::unchecked(sender), coins(10, "ucosm"));
+    }
+    let call = tunnel.dispatch(HOST_CHANNEL_ID, send_coins());
+    let err = env
+        .host
+        .execute(
+            CONTROLLER,
+            &env.host.tunnel(),
+            call.msg(),
+            coins(1, "ucosm"),
+        )
+        .unwrap_err();
+    assert!(err.contains("does no accept funds"), "{err}");
+
+    // also when the funds come with an operation sent by an operator
+    let call = tunnel
+        .dispatch(HOST_CHANNEL_ID, send_coins())
+        .execute_as(CONTROLLER);
+    let err = env
+        .host
+        .execute(
+            "operator",
+            &env.host.tunnel(),
+            call.msg(),
+            coins(1, "ucosm"),
+        )
+        .unwrap_err();
+    assert!(err.contains("does no accept funds"), "{err}");
+
+    // the refused funds stay with their senders
+    for sender in [CONTROLLER, "operator"] {
+        let sender = Addr::unchecked(sender);
+        assert_eq!(env.host.balance(&sender, "ucosm"), coin(10, "ucosm"));
+    }
+    assert_eq!(env.host.all_balances(&env.host.tunnel()), vec![]);
+}
+
+#[test]
+fn tunnel_refuses_a_timeout_of_zero() {
+    let mut env = TestEnv::new();
+    let tunnel = TunnelContract::new(env.host.tunnel());
+    for call in [
+        tunnel.dispatch(HOST_CHANNEL_ID, send_coins()).timeout(0),
+        tunnel.query_remote_addr(HOST_CHANNEL_ID).timeout(0),
+        tunnel
+            .dispatch(HOST_CHANNEL_ID, send_coins())
+            .forward(vec!["channel-7".to_string()])
+            .timeout(0),
+    ] {
+        let err = env
+            .host
+            .execute(CONTROLLER, &env.host.tunnel(), call.msg(), vec![])
+            .unwrap_err();
+        assert!(err.contains("more than 0 seconds"), "{err}");
+    }
+
+    let call = tunnel
+        .query_remote_addr::<Empty>(HOST_CHANNEL_ID)
+        .timeout(1);
+    env.host
+        .execute(CONTROLLER, &env.host.tunnel(), call.msg(), vec![])
+        .unwrap();
+    let packet = &env.pending_packets(Side::Host)[0];
+    assert_eq!(
+        packet.timeout.timestamp(),
+        Some(env.host.block.time.plus_seconds(1))
+    );
+}
@@ -0,0 +1,133 @@
+//! The events of the tunnel convert into `Event` and parse back, as emitted and as found in a transaction result
+use cosmwasm_std::{Event, StdError};
+use cosmwasm_tunnel::events::{
+    DispatchRequested, ForwardRequested, MsgPolicySet, ProxyInstantiated, TunnelEvent,
+    TunnelInstantiated, TunnelMigrated,
+};
+
+/// Converts `event` and parses it back, also with the `wasm-` prefix the chain adds
+fn round_trip<E: TunnelEvent + Clone + PartialEq + std::fmt::Debug>(event: E) -> Event {
+    let emitted = event.clone().into_event();
+    assert_eq!(emitted.ty, E::EVENT_TYPE);
+    assert_eq!(E::from_event(&emitted).unwrap(), event);
+
+    let mut in_tx = emitted.clone();
+    in_tx.ty = format!("wasm-{}", emitted.ty);
+    in_tx = in_tx.add_attribute("_contract_address", "tunnel");
+    assert!(E::matches(&in_tx));
+    assert_eq!(E::find(&[Event::new("wasm"), in_tx]), Some(event));
+    emitted
+}
+
+fn attribute<'a>(event: &'a Event, key: &str) -> Option<&'a str> {
+    event
+        .attributes
+        .iter()
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.value.as_str())
+}
+
+#[test]
+fn events_round_trip() {
+    let event = round_trip(DispatchRequested {
+        channel_id: "channel-0".to_string(),
+        controller: "controller".to_string(),
+        job_id: Some("job-1".to_string()),
+    });
+    // the attributes are lower case and the values plain
+    assert_eq!(attribute(&event, "controller"), Some("controller"));
+    assert_eq!(attribute(&event, "job_id"), Some("job-1"));
+
+    round_trip(ProxyInstantiated {
+        contract_addr: "proxy".to_string(),
+        connection_id: "connection-0".to_string(),
+        port_id: "wasm.tunnel".to_string(),
+        controller: "controller".to_string(),
+    });
+    let event = round_trip(ForwardRequested {
+        channel_id: "channel-0".to_string(),
+        controller: "controller".to_string(),
+        hops: vec!["channel-7".to_string(), "channel-9".to_string()],
+        job_id: None,
+    });
+    assert_eq!(attribute(&event, "hops"), Some("channel-7,channel-9"));
+    round_trip(TunnelInstantiated {
+        roles: vec!["host".to_string(), "remote".to_string()],
+    });
+}
+
+#[test]
+fn missing_values_are_left_out() {
+    let event = round_trip(DispatchRequested {
+        channel_id: "channel-0".to_string(),
+        controller: "controller".to_string(),
+        job_id: None,
+    });
+    assert_eq!(attribute(&event, "job_id"), None);
+    let event = round_trip(MsgPolicySet { account: None });
+    assert!(event.attributes.is_empty());
+
+    // the chain rejects attributes with an empty value
+    let event = round_trip(TunnelMigrated {
+        from_version: "0.2.0".to_string(),
+        to_version: "0.2.0".to_string(),
+        migrations: vec![],
+    });
+    assert_eq!(attribute(&event, "migrations"), None);
+    assert!(event.attributes.iter().all(|attr| !attr.value.is_empty()));
+}
+
+#[test]
+fn other_events_are_not_parsed() {
+    let event = Event::new("wasm-ica-tunnel.V1.HostMsg.DispatchRequested")
+        .add_attribute("channel_id", "channel-0");
+    // a required attribute is missing
+    assert!(matches!(
+        DispatchRequested::from_event(&event),
+        Err(StdError::GenericErr { msg }) if msg == "missing event attribute controller"
+    ));
+    assert_eq!(DispatchRequested::find(&[event]), None);
+
+    let event = Event::new("wasm-ica-tunnel.V1.HostMsg.MigrationRequested")
+        .add_attribute("channel_id", "channel-0")
+        .add_attribute("controller", "controller");
+    assert!(!DispatchRequested::matches(&event));
+    assert!(DispatchRequested::try_from(&event).is_err());
+    // the type is matched exactly, not by suffix
+    let event = Event::new("ica-tunnel.V1.MsgInstantiatedAgain").add_attribute("roles", "host");
+    assert!(!TunnelInstantiated::matches(&event));
+}
+
+#[cfg(all(feature = "host", feature = "remote"))]
+#[test]
+fn emitted_events_are_found_in_the_transaction() {
+    use cosmwasm_std::{to_binary, Empty, WasmMsg};
+    use cosmwasm_tunnel::events::InstantiationRequested;
+    use cosmwasm_tunnel::TunnelContract;
+    use tunnel_testing::{TestEnv, HOST_CHANNEL_ID};
+
+    let mut env = TestEnv::new();
+    let inst_msg = WasmMsg::Instantiate {
+        admin: None,
+        code_id: 2,
+        msg: to_binary(&Empty {}).unwrap(),
+        funds: vec![],
+        label: "proxy".to_string(),
+    };
+    let call = TunnelContract::new(env.host.tunnel())
+        .instantiate::<Empty>(HOST_CHANNEL_ID, 2, inst_msg.into())
+        .job_id("init");
+    let res = env
+        .host
+        .execute("controller", &env.host.tunnel(), call.msg(), vec![])
+        .unwrap();
+    assert_eq!(
+        InstantiationRequested::find(&res.events),
+        Some(InstantiationRequested {
+            channel_id: HOST_CHANNEL_ID.to_string(),
+            controller: "controller".to_string(),
+            job_id: Some("init".to_string()),
+        })
+    );
+    assert_eq!(DispatchRequested::find(&res.events), None);
+}
@@ -0,0 +1,2294 @@
+#![cfg(all(feature = "host", feature = "remote"))]
+use cosmwasm_std::{
+    coin, coins, from_binary, from_slice, to_binary, to_vec, Addr, BankMsg, Binary, CosmosMsg,
+    Empty, IbcChannel, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcEndpoint, IbcOrder, IbcPacket,
+    Order, SubMsgResult, WasmMsg,
+};
+use cosmwasm_tunnel::events::{TunnelEvent, TunnelMigrated};
+use cosmwasm_tunnel::ica::{
+    ica_controller_port, ica_owner, IcaMemo, IcaMetadata, IcaPacketData, IcaSudoMsg, IcaTxResponse,
+    RequestPacket, MSG_REGISTER_INTERCHAIN_ACCOUNT_TYPE_URL, MSG_SEND_TX_TYPE_URL,
+};
+use cosmwasm_tunnel::{
+    forwarded_controller, v1, AccountResponse, ChannelMetadata, ChannelMetadataResponse,
+    ChunkResponse, CosmosMsgKind, DenyRule, DispatchMigrateResponse, ErrorResponse, ExecuteMsg,
+    GovernanceMsg, InstantiateResponse, InterchainAccountResponse, MigrateMsg, MsgCategory,
+    MsgPolicy, MsgPolicyResponse, NonceResponse, OperatorGrant, OperatorScope, PacketEncoding,
+    PacketMsg, ProtocolVersion, QueryMsg, ReceiveIcaResponseMsg, SessionKey, SessionKeyResponse,
+    SignedOperation, SigningKey, SpendLimit, SpendLimitResponse, StdAck, SudoMsg, TunnelCall,
+    TunnelContract, TunnelErrorCode, TunnelOperation, TunnelRole, WhoAmIResponse,
+    CHUNK_UPLOAD_LIFETIME, MAX_CHUNKS, MAX_NONCE_WINDOW, MAX_PACKET_SIZE, MAX_UPLOADS,
+    PACKET_LIFETIME,
+};
+use cw_utils::Expiration;
+use ic_tunnel::contract::{CONTRACT_NAME, CONTRACT_VERSION};
+use ic_tunnel::error::ContractError;
+use ic_tunnel::state::{NONCE_WINDOW, RECEIVED_NONCES};
+use k256::ecdsa::signature::Signer;
+use tunnel_testing::contracts::{
+    ControllerQueryMsg, CounterExecuteMsg, CounterQueryMsg, FailingMigrateProxy, MinimalTunnel,
+    MinimalTunnelInstantiateMsg, MockController, MockCounter, MockProxy, ProxyMessagesResponse,
+    ProxyQueryMsg,
+};
+use tunnel_testing::{
+    ibc_port, Chain, Network, Side, TestEnv, HOST_CHAIN_ID, HOST_CHANNEL_ID, HOST_CONNECTION_ID,
+    REMOTE_CHAIN_ID, REMOTE_CHANNEL_ID, REMOTE_CONNECTION_ID,
+};
+
+struct Setup {
+    env: TestEnv,
+    controller: Addr,
+    counter: Addr,
+    proxy_code: u64,
+}
+
+fn setup() -> Setup {
+    setup_on(TestEnv::new())
+}
+
+fn setup_on(mut env: TestEnv) -> Setup {
+    let controller_code = env.host.store_code(MockController);
+    let controller = env
+        .host
+        .instantiate("creator", controller_code, &Empty {}, None)
+        .unwrap();
+    let proxy_code = env.remote.store_code(MockProxy);
+    let counter_code = env.remote.store_code(MockCounter);
+    let counter = env
+        .remote
+        .instantiate("creator", counter_code, &Empty {}, None)
+        .unwrap();
+    Setup {
+        env,
+        controller,
+        counter,
+        proxy_code,
+    }
+}
+
+fn tunnel(env: &TestEnv) -> TunnelContract {
+    TunnelContract::new(env.host.tunnel())
+}
+
+fn send(env: &mut TestEnv, controller: &Addr, call: cosmwasm_tunnel::TunnelCall) {
+    env.host
+        .execute(controller.as_str(), &env.host.tunnel(), call.msg(), vec![])
+        .unwrap();
+}
+
+fn increment(counter: &Addr) -> CosmosMsg {
+    WasmMsg::Execute {
+        contract_addr: counter.to_string(),
+        msg: to_binary(&CounterExecuteMsg::Increment {}).unwrap(),
+        funds: vec![],
+    }
+    .into()
+}
+
+fn fail(counter: &Addr) -> CosmosMsg {
+    WasmMsg::Execute {
+        contract_addr: counter.to_string(),
+        msg: to_binary(&CounterExecuteMsg::Fail {}).unwrap(),
+        funds: vec![],
+    }
+    .into()
+}
+
+fn count(env: &TestEnv, counter: &Addr) -> u64 {
+    env.remote
+        .query(counter, &CounterQueryMsg::Count {})
+        .unwrap()
+}
+
+fn callbacks(env: &TestEnv, controller: &Addr) -> Vec<ReceiveIcaResponseMsg> {
+    env.host
+        .query(controller, &ControllerQueryMsg::Callbacks {})
+        .unwrap()
+}
+
+fn callback(env: &TestEnv, controller: &Addr, id: &str) -> ReceiveIcaResponseMsg {
+    callbacks(env, controller)
+        .into_iter()
+        .find(|callback| callback.id == id)
+        .unwrap_or_else(|| panic!("no callback for {id}"))
+}
+
+fn error(ack: &Binary) -> ErrorResponse {
+    match from_slice::<StdAck>(ack).unwrap() {
+        StdAck::Error(err) => ErrorResponse::from_ack_error(&err),
+        StdAck::Result(_) => panic!("expected an error ack"),
+    }
+}
+
+fn account(env: &TestEnv, controller: &Addr) -> Option<String> {
+    let res: AccountResponse = env
+        .remote
+        .query(
+            &env.remote.tunnel(),
+            &QueryMsg::Account {
+                connection_id: REMOTE_CONNECTION_ID.to_string(),
+                port_id: ibc_port(&env.host.tunnel()),
+                controller: controller.to_string(),
+            },
+        )
+        .unwrap();
+    res.account
+}
+
+fn received_nonce(env: &TestEnv, controller: &Addr) -> u64 {
+    let res: NonceResponse = env
+        .remote
+        .query(
+            &env.remote.tunnel(),
+            &QueryMsg::ReceivedNonce {
+                connection_id: REMOTE_CONNECTION_ID.to_string(),
+                port_id: ibc_port(&env.host.tunnel()),
+                controller: controller.to_string(),
+            },
+        )
+        .unwrap();
+    res.nonce
+}
+
+/// Instantiates the proxy of the controller and relays everything
+fn open_account(setup: &mut Setup) -> Addr {
+    let inst_msg = WasmMsg::Instantiate {
+        admin: None,
+        code_id: setup.proxy_code,
+        msg: to_binary(&Empty {}).unwrap(),
+        funds: vec![],
+        label: "proxy".to_string(),
+    };
+    let call = tunnel(&setup.env)
+        .instantiate(HOST_CHANNEL_ID, setup.proxy_code, inst_msg.into())
+        .job_id("init");
+    send(&mut setup.env, &setup.controller, call);
+    setup.env.relay_all().unwrap();
+    Addr::unchecked(account(&setup.env, &setup.controller).expect("proxy is registered"))
+}
+
+#[test]
+fn instantiate_dispatch_migrate() {
+    let mut setup = setup();
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let init: InstantiateResponse = callback(env, controller, "init").msg.unwrap_into();
+    assert_eq!(init.contract_address, proxy.as_str());
+    assert_eq!(init.job_id.as_deref(), Some("init"));
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, increment(counter))
+        .job_id("dispatch");
+    send(env, controller, call);
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    let dispatch: DispatchMigrateResponse = callback(env, controller, "dispatch").msg.unwrap_into();
+    assert!(matches!(dispatch.result, SubMsgResult::Ok(_)));
+
+    let new_code = env.remote.store_code(MockProxy);
+    let migrate_msg = WasmMsg::Migrate {
+        contract_addr: proxy.to_string(),
+        new_code_id: new_code,
+        msg: to_binary(&Empty {}).unwrap(),
+    };
+    let call = tunnel(env)
+        .migrate(HOST_CHANNEL_ID, new_code, migrate_msg.into())
+        .job_id("migrate");
+    send(env, controller, call);
+    env.relay_all().unwrap();
+    assert_eq!(env.remote.code_id(&proxy), Some(new_code));
+    let messages: ProxyMessagesResponse = env
+        .remote
+        .query(&proxy, &ProxyQueryMsg::Messages {})
+        .unwrap();
+    assert!(messages.migrate_msg.is_some());
+    let migrate: DispatchMigrateResponse = callback(env, controller, "migrate").msg.unwrap_into();
+    assert!(matches!(migrate.result, SubMsgResult::Ok(_)));
+
+    assert_eq!(received_nonce(env, controller), 3);
+}
+
+#[test]
+fn failed_dispatch() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    // without fail_on_error the failure is returned in the result
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, fail(counter))
+        .job_id("lenient");
+    send(env, controller, call);
+    env.relay_all().unwrap();
+    let res: DispatchMigrateResponse = callback(env, controller, "lenient").msg.unwrap_into();
+    assert!(matches!(res.result, SubMsgResult::Err(_)));
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, fail(counter))
+        .job_id("strict")
+        .fail_on_error();
+    send(env, controller, call);
+    let relayed = env.relay_all().unwrap();
+    let (_, ack) = relayed.first().unwrap();
+    assert_eq!(error(ack).code, TunnelErrorCode::ExecutionFailed);
+    let strict = callback(env, controller, "strict");
+    assert_eq!(strict.error_code, Some(TunnelErrorCode::ExecutionFailed));
+    assert_eq!(count(env, counter), 0);
+}
+
+#[test]
+fn failed_migration() {
+    let mut setup = setup();
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env, controller, ..
+    } = &mut setup;
+
+    let code = env.remote.store_code(FailingMigrateProxy);
+    let migrate_msg = WasmMsg::Migrate {
+        contract_addr: proxy.to_string(),
+        new_code_id: code,
+        msg: to_binary(&Empty {}).unwrap(),
+    };
+    let call = tunnel(env)
+        .migrate(HOST_CHANNEL_ID, code, migrate_msg.into())
+        .job_id("migrate")
+        .fail_on_error();
+    send(env, controller, call);
+    env.relay_all().unwrap();
+    let migrate = callback(env, controller, "migrate");
+    assert_eq!(migrate.error_code, Some(TunnelErrorCode::ExecutionFailed));
+    assert_eq!(env.remote.code_id(&proxy), Some(setup.proxy_code));
+}
+
+#[test]
+fn failed_dispatch_rolls_back_with_fail_on_error() {
+    let mut setup = setup();
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+    env.remote
+        .sudo(
+            &env.remote.tunnel(),
+            &GovernanceMsg::SetSpendLimit {
+                account: proxy.to_string(),
+                limit: SpendLimit {
+                    max: coins(100, "uatom"),
+                    period: 3600,
+                },
+            },
+        )
+        .unwrap();
+    env.remote.set_balance(&proxy, coins(100, "uatom"));
+
+    // the proxy sends the funds before the counter fails
+    let paid_fail = WasmMsg::Execute {
+        contract_addr: counter.to_string(),
+        msg: to_binary(&CounterExecuteMsg::Fail {}).unwrap(),
+        funds: coins(40, "uatom"),
+    };
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, paid_fail.into())
+        .job_id("strict")
+        .fail_on_error();
+    send(env, controller, call);
+    let ack = env.deliver_next(Side::Host).unwrap();
+    let err = error(&ack);
+    assert_eq!(err.code, TunnelErrorCode::ExecutionFailed);
+    assert_eq!(err.job_id.as_deref(), Some("strict"));
+    env.relay_all().unwrap();
+    let strict = callback(env, controller, "strict");
+    assert_eq!(strict.error_code, Some(TunnelErrorCode::ExecutionFailed));
+
+    // the transfer is reverted and nothing is counted against the limit
+    assert_eq!(env.remote.balance(&proxy, "uatom"), coin(100, "uatom"));
+    assert_eq!(env.remote.balance(counter, "uatom"), coin(0, "uatom"));
+    assert_eq!(spend_limit(env, &proxy).spent, vec![]);
+    // but the nonce is used, so the packet cannot be replayed
+    assert_eq!(received_nonce(env, controller), 2);
+    env.remote.with_tunnel(|deps, _| {
+        assert_eq!(deps.storage.get(b"pending"), None);
+        assert_eq!(deps.storage.get(b"pending_spend"), None);
+    });
+
+    // the next dispatch spends the whole limit
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, paid_increment(counter, 100));
+    send(env, controller, call);
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    assert_eq!(env.remote.balance(counter, "uatom"), coin(100, "uatom"));
+}
+
+#[test]
+fn dispatch_without_account() {
+    let Setup {
+        mut env,
+        controller,
+        counter,
+        ..
+    } = setup();
+
+    let call = tunnel(&env)
+        .dispatch(HOST_CHANNEL_ID, increment(&counter))
+        .job_id("dispatch");
+    send(&mut env, &controller, call);
+    let ack = env.deliver_next(Side::Host).unwrap();
+    assert_eq!(error(&ack).code, TunnelErrorCode::AccountNotFound);
+    assert_eq!(error(&ack).job_id.as_deref(), Some("dispatch"));
+    env.relay_all().unwrap();
+    let dispatch = callback(&env, &controller, "dispatch");
+    assert_eq!(dispatch.error_code, Some(TunnelErrorCode::AccountNotFound));
+}
+
+fn interchain_account(env: &TestEnv, controller: &Addr) -> InterchainAccountResponse {
+    let msg = QueryMsg::InterchainAccount {
+        controller: controller.to_string(),
+        connection_id: HOST_CONNECTION_ID.to_string(),
+    };
+    env.host.query(&env.host.tunnel(), &msg).unwrap()
+}
+
+/// The ICS-27 packet of a tx sent for `controller`, as the ICA controller middleware returns it in `sudo`
+fn ica_request(controller: &Addr, job_id: &str) -> RequestPacket {
+    let memo = IcaMemo {
+        controller: controller.to_string(),
+        job_id: Some(job_id.to_string()),
+    };
+    let data = IcaPacketData {
+        tx_type: "TYPE_EXECUTE_TX".to_string(),
+        data: Binary::default(),
+        memo: String::from_utf8(to_vec(&memo).unwrap()).unwrap(),
+    };
+    RequestPacket {
+        sequence: Some(1),
+        source_port: None,
+        source_channel: Some("channel-ica".to_string()),
+        destination_port: None,
+        destination_channel: None,
+        data: Some(to_binary(&data).unwrap()),
+        timeout_height: None,
+        timeout_timestamp: None,
+    }
+}
+
+#[test]
+fn native_interchain_accounts() {
+    let Setup {
+        mut env,
+        controller,
+        ..
+    } = setup();
+    let env = &mut env;
+    let tunnel_addr = env.host.tunnel();
+    let register = |env: &TestEnv| {
+        tunnel(env)
+            .register_interchain_account(HOST_CONNECTION_ID, REMOTE_CONNECTION_ID)
+            .job_id("ica")
+    };
+    send(env, &controller, register(env));
+    let msg = env.host.stargate_msgs().last().unwrap();
+    assert_eq!(msg.type_url, MSG_REGISTER_INTERCHAIN_ACCOUNT_TYPE_URL);
+    let account = interchain_account(env, &controller);
+    let owner = ica_owner(tunnel_addr.as_str(), controller.as_str());
+    assert_eq!(account.owner, owner);
+    assert_eq!(account.channel_id, None);
+
+    // each controller has its own account on the connection
+    env.host
+        .execute("other", &tunnel_addr, register(env).msg(), vec![])
+        .unwrap();
+    let other = interchain_account(env, &Addr::unchecked("other"));
+    assert_ne!(other.owner, owner);
+    assert_eq!(other.controller, "other");
+
+    // the channel opens on the port of the controller's owner
+    let open_ack = |port_id: String| {
+        let metadata = IcaMetadata {
+            address: "ica-address".to_string(),
+            ..IcaMetadata::new(
+                HOST_CONNECTION_ID.to_string(),
+                REMOTE_CONNECTION_ID.to_string(),
+            )
+        };
+        SudoMsg::Ica(IcaSudoMsg::OpenAck {
+            port_id,
+            channel_id: "channel-ica".to_string(),
+            counterparty_channel_id: "channel-host".to_string(),
+            counterparty_version: String::from_utf8(to_vec(&metadata).unwrap()).unwrap(),
+        })
+    };
+    let err = env
+        .host
+        .sudo(
+            &tunnel_addr,
+            &open_ack(ica_controller_port(tunnel_addr.as_str())),
+        )
+        .unwrap_err();
+    assert!(err.contains("Unauthorized"), "{err}");
+    env.host
+        .sudo(&tunnel_addr, &open_ack(ica_controller_port(&owner)))
+        .unwrap();
+    let account = interchain_account(env, &controller);
+    assert_eq!(account.channel_id.as_deref(), Some("channel-ica"));
+    assert_eq!(account.address.as_deref(), Some("ica-address"));
+    let opened: WhoAmIResponse = callback(env, &controller, "ica").msg.unwrap_into();
+    assert_eq!(opened.account, "ica-address");
+    let err = env
+        .host
+        .execute(
+            controller.as_str(),
+            &tunnel_addr,
+            register(env).msg(),
+            vec![],
+        )
+        .unwrap_err();
+    assert!(
+        err.contains("Cannot register over an existing channel"),
+        "{err}"
+    );
+
+    // only the controller dispatches on the channel of its account, stargate messages only
+    let stargate = CosmosMsg::Stargate {
+        type_url: "/cosmos.bank.v1beta1.MsgSend".to_string(),
+        value: Binary::default(),
+    };
+    let call = tunnel(env).dispatch("channel-ica", stargate).job_id("tx");
+    let err = env
+        .host
+        .execute("other", &tunnel_addr, call.msg(), vec![])
+        .unwrap_err();
+    assert!(err.contains("Unauthorized"), "{err}");
+    send(env, &controller, call);
+    assert_eq!(
+        env.host.stargate_msgs().last().unwrap().type_url,
+        MSG_SEND_TX_TYPE_URL
+    );
+    let call = tunnel(env).dispatch("channel-ica", increment(&controller));
+    let err = env
+        .host
+        .execute(controller.as_str(), &tunnel_addr, call.msg(), vec![])
+        .unwrap_err();
+    assert!(err.contains("must be CosmosMsg::Stargate"), "{err}");
+
+    // the tx result is delivered to the controller
+    let response = SudoMsg::Ica(IcaSudoMsg::Response {
+        request: ica_request(&controller, "tx"),
+        data: Binary::default(),
+    });
+    env.host.sudo(&tunnel_addr, &response).unwrap();
+    let tx: IcaTxResponse = callback(env, &controller, "tx").msg.unwrap_into();
+    assert_eq!(tx.job_id.as_deref(), Some("tx"));
+
+    // a timeout closes the ordered channel, the controller registers again with the same owner
+    let timeout = SudoMsg::Ica(IcaSudoMsg::Timeout {
+        request: ica_request(&controller, "lost"),
+    });
+    env.host.sudo(&tunnel_addr, &timeout).unwrap();
+    let lost = callback(env, &controller, "lost");
+    assert_eq!(lost.error_code, Some(TunnelErrorCode::Timeout));
+    assert_eq!(interchain_account(env, &controller).channel_id, None);
+    send(env, &controller, register(env));
+    assert_eq!(interchain_account(env, &controller).owner, owner);
+}
+
+#[test]
+fn reordered_and_delayed_packets() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    for job in ["a", "b", "c"] {
+        let call = tunnel(env)
+            .dispatch(HOST_CHANNEL_ID, increment(counter))
+            .job_id(job);
+        send(env, controller, call);
+    }
+    let sequences: Vec<u64> = env
+        .pending_packets(Side::Host)
+        .iter()
+        .map(|packet| packet.sequence)
+        .collect();
+    env.reorder(Side::Host, &[sequences[1]]).unwrap();
+    env.delay(Side::Host, sequences[0]).unwrap();
+
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 3);
+    assert_eq!(callbacks(env, controller).len(), 4);
+    for job in ["a", "b", "c"] {
+        assert_eq!(callback(env, controller, job).error_code, None);
+    }
+    assert_eq!(received_nonce(env, controller), 4);
+}
+
+#[test]
+fn replayed_packet() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, increment(counter));
+    send(env, controller, call);
+    let packet = env.pending_packets(Side::Host).remove(0);
+    env.relay_all().unwrap();
+
+    // a relayer submitting the same packet again
+    env.resubmit(Side::Host, packet);
+    let ack = env.deliver_next(Side::Host).unwrap();
+    assert_eq!(error(&ack).code, TunnelErrorCode::InvalidNonce);
+    assert_eq!(count(env, counter), 1);
+}
+
+#[test]
+fn dropped_packet() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    for _ in 0..2 {
+        let call = tunnel(env).dispatch(HOST_CHANNEL_ID, increment(counter));
+        send(env, controller, call);
+    }
+    let lost = env.pending_packets(Side::Host)[0].sequence;
+    env.drop_packet(Side::Host, lost).unwrap();
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    assert_eq!(received_nonce(env, controller), 3);
+}
+
+#[test]
+fn timed_out_packet() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, increment(counter))
+        .job_id("late");
+    send(env, controller, call);
+    let sequence = env.pending_packets(Side::Host)[0].sequence;
+    assert!(env.timeout(Side::Host, sequence).is_err());
+
+    env.remote.next_block(PACKET_LIFETIME + 1);
+    assert!(env.deliver(Side::Host, sequence).is_err());
+    env.timeout(Side::Host, sequence).unwrap();
+    assert_eq!(count(env, counter), 0);
+    let late = callback(env, controller, "late");
+    assert_eq!(late.error_code, Some(TunnelErrorCode::Timeout));
+
+    // a shorter timeout set by the controller
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, increment(counter))
+        .job_id("short")
+        .timeout(10);
+    send(env, controller, call);
+    let sequence = env.pending_packets(Side::Host)[0].sequence;
+    env.remote.next_block(11);
+    env.timeout(Side::Host, sequence).unwrap();
+    let short = callback(env, controller, "short");
+    assert_eq!(short.error_code, Some(TunnelErrorCode::Timeout));
+}
+
+#[test]
+fn lost_ack_keeps_the_callback_pending() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, increment(counter))
+        .job_id("unacked");
+    send(env, controller, call);
+    let sequence = env.pending_packets(Side::Host)[0].sequence;
+    let ack = env.deliver(Side::Host, sequence).unwrap();
+    let res: DispatchMigrateResponse = from_binary::<StdAck>(&ack).unwrap().unwrap_into();
+    assert!(matches!(res.result, SubMsgResult::Ok(_)));
+    env.drop_ack(Side::Host, sequence).unwrap();
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    assert!(callbacks(env, controller)
+        .iter()
+        .all(|callback| callback.id != "unacked"));
+}
+
+#[test]
+fn operators_act_within_their_grant() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+    let grant = OperatorGrant {
+        scopes: vec![OperatorScope::Dispatch],
+        expires: Some(Expiration::AtHeight(env.host.block.height + 1)),
+        allowed_msgs: Some(vec![CosmosMsgKind::Wasm]),
+    };
+    send(
+        env,
+        controller,
+        tunnel(env).grant_operator("operator", grant),
+    );
+    let as_operator = |env: &mut TestEnv, operator: &str, call: TunnelCall| {
+        let call = call.execute_as(controller.as_str());
+        env.host
+            .execute(operator, &env.host.tunnel(), call.msg(), vec![])
+            .map(|_| ())
+    };
+
+    // the operator dispatches the allowed kinds as the controller
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, increment(counter))
+        .job_id("operator");
+    as_operator(env, "operator", call).unwrap();
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    callback(env, controller, "operator");
+
+    // but no other kind of message
+    let send_coins = BankMsg::Send {
+        to_address: "recipient".to_string(),
+        amount: coins(1, "ucosm"),
+    };
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, send_coins.into());
+    let err = as_operator(env, "operator", call).unwrap_err();
+    assert!(err.contains("not allowed to dispatch Bank"), "{err}");
+
+    // nor operations outside of its scopes
+    let migrate = WasmMsg::Migrate {
+        contract_addr: account(env, controller).unwrap(),
+        new_code_id: 1,
+        msg: to_binary(&Empty {}).unwrap(),
+    };
+    for call in [
+        tunnel(env).migrate(HOST_CHANNEL_ID, 1, migrate.into()),
+        tunnel(env).query_remote_addr(HOST_CHANNEL_ID),
+        tunnel(env).revoke_operator("operator"),
+    ] {
+        let err = as_operator(env, "operator", call).unwrap_err();
+        assert!(err.contains("Unauthorized"), "{err}");
+    }
+
+    // an address without a grant cannot act as the controller
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, increment(counter));
+    let err = as_operator(env, "stranger", call).unwrap_err();
+    assert!(err.contains("Unauthorized"), "{err}");
+
+    // the grant expires at its height
+    env.host.next_block(5);
+    env.remote.next_block(5);
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, increment(counter));
+    let err = as_operator(env, "operator", call).unwrap_err();
+    assert!(err.contains("Operator grant has expired"), "{err}");
+
+    // and a revoked operator cannot act anymore
+    let grant = OperatorGrant {
+        scopes: vec![OperatorScope::Dispatch],
+        expires: None,
+        allowed_msgs: None,
+    };
+    send(
+        env,
+        controller,
+        tunnel(env).grant_operator("operator", grant),
+    );
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, increment(counter));
+    as_operator(env, "operator", call).unwrap();
+    send(env, controller, tunnel(env).revoke_operator("operator"));
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, increment(counter));
+    let err = as_operator(env, "operator", call).unwrap_err();
+    assert!(err.contains("Unauthorized"), "{err}");
+}
+
+#[test]
+fn cosmos_msg_kinds() {
+    let gov = cosmwasm_std::GovMsg::Vote {
+        proposal_id: 1,
+        vote: cosmwasm_std::VoteOption::Yes,
+    };
+    let stargate = CosmosMsg::Stargate {
+        type_url: "/cosmos.bank.v1beta1.MsgSend".to_string(),
+        value: Binary::default(),
+    };
+    let transfer = cosmwasm_std::IbcMsg::CloseChannel {
+        channel_id: HOST_CHANNEL_ID.to_string(),
+    };
+    for (msg, kind) in [
+        (gov.into(), CosmosMsgKind::Gov),
+        (stargate, CosmosMsgKind::Stargate),
+        (transfer.into(), CosmosMsgKind::Ibc),
+        (CosmosMsg::Custom(Empty {}), CosmosMsgKind::Custom),
+        (increment(&Addr::unchecked("counter")), CosmosMsgKind::Wasm),
+    ] {
+        assert_eq!(CosmosMsgKind::of(&msg).unwrap(), kind);
+    }
+}
+
+const ORIGIN: usize = 0;
+const HOP: usize = 1;
+const NEXT_HOP: usize = 2;
+const DESTINATION: usize = 3;
+const MALLORY: usize = 4;
+
+/// Chains in a line from `ORIGIN` to `DESTINATION`, with `MALLORY` also joined to `HOP`
+fn forwarding_network() -> Network {
+    Network::new(
+        &["origin-1", "hop-1", "hop-2", "destination-1", "mallory-1"],
+        &[(0, 1), (1, 2), (2, 3), (1, 4)],
+    )
+}
+
+fn network_callback(net: &Network, controller: &Addr, id: &str) -> Vec<ReceiveIcaResponseMsg> {
+    let callbacks: Vec<ReceiveIcaResponseMsg> = net.chains[ORIGIN]
+        .query(controller, &ControllerQueryMsg::Callbacks {})
+        .unwrap();
+    callbacks
+        .into_iter()
+        .filter(|callback| callback.id == id)
+        .collect()
+}
+
+fn forward_routes(net: &mut Network, chain: usize) -> usize {
+    net.chains[chain].with_tunnel(|deps, _| {
+        ic_tunnel::state::FORWARD_ROUTES
+            .keys(deps.storage, None, None, Order::Ascending)
+            .count()
+    })
+}
+
+#[test]
+fn forwarded_acks_return_along_the_route() {
+    let mut net = forwarding_network();
+    let controller_code = net.chains[ORIGIN].store_code(MockController);
+    let controller = net.chains[ORIGIN]
+        .instantiate("creator", controller_code, &Empty {}, None)
+        .unwrap();
+    let proxy_code = net.chains[DESTINATION].store_code(MockProxy);
+    let counter_code = net.chains[DESTINATION].store_code(MockCounter);
+    let counter = net.chains[DESTINATION]
+        .instantiate("creator", counter_code, &Empty {}, None)
+        .unwrap();
+    let tunnel = TunnelContract::new(net.chains[ORIGIN].tunnel());
+    let channel_id = net.channel_id(ORIGIN, HOP);
+    let hops = vec![
+        net.channel_id(HOP, NEXT_HOP),
+        net.channel_id(NEXT_HOP, DESTINATION),
+    ];
+    let send = |net: &mut Network, call: TunnelCall| {
+        let call = call.forward(hops.clone());
+        net.chains[ORIGIN]
+            .execute(controller.as_str(), &tunnel.addr(), call.msg(), vec![])
+            .unwrap();
+    };
+
+    let inst_msg = WasmMsg::Instantiate {
+        admin: None,
+        code_id: proxy_code,
+        msg: to_binary(&Empty {}).unwrap(),
+        funds: vec![],
+        label: "proxy".to_string(),
+    };
+    send(
+        &mut net,
+        tunnel
+            .instantiate(&channel_id, proxy_code, inst_msg.into())
+            .job_id("init"),
+    );
+    net.relay_all().unwrap();
+    let init: InstantiateResponse = network_callback(&net, &controller, "init")[0]
+        .msg
+        .clone()
+        .unwrap_into();
+    assert_eq!(init.job_id.as_deref(), Some("init"));
+
+    send(
+        &mut net,
+        tunnel
+            .dispatch(&channel_id, increment(&counter))
+            .job_id("dispatch"),
+    );
+    net.relay_all().unwrap();
+    let count: u64 = net.chains[DESTINATION]
+        .query(&counter, &CounterQueryMsg::Count {})
+        .unwrap();
+    assert_eq!(count, 1);
+    let dispatch: DispatchMigrateResponse = network_callback(&net, &controller, "dispatch")[0]
+        .msg
+        .clone()
+        .unwrap_into();
+    assert!(matches!(dispatch.result, SubMsgResult::Ok(_)));
+    // the routes are removed once the acks are returned
+    assert_eq!(forward_routes(&mut net, HOP), 0);
+    assert_eq!(forward_routes(&mut net, NEXT_HOP), 0);
+
+    // an ack for a pending forward is only accepted from the channel the forward was sent on
+    send(
+        &mut net,
+        tunnel
+            .dispatch(&channel_id, increment(&counter))
+            .job_id("pending"),
+    );
+    let forge = |net: &mut Network, from: usize, to: usize, controller: String, nonce: u64| {
+        let forged = PacketMsg::<Empty>::ForwardAck {
+            controller,
+            job_id: Some("pending".to_string()),
+            nonce,
+            ack: StdAck::Result(Binary::default()),
+        };
+        let channel_id = net.channel_id(from, to);
+        net.send_raw(from, &channel_id, to_binary(&forged).unwrap())
+            .unwrap();
+        error(&net.deliver_next(from).unwrap()).code
+    };
+    // the origin expects the nonce of the forward
+    let code = forge(&mut net, HOP, ORIGIN, controller.to_string(), 7);
+    assert_eq!(code, TunnelErrorCode::Unauthorized);
+
+    net.deliver_next(ORIGIN).unwrap();
+    assert_eq!(forward_routes(&mut net, HOP), 1);
+    let hop_controller = forwarded_controller(
+        &net.connection_id(HOP, ORIGIN),
+        &ibc_port(&tunnel.addr()),
+        controller.as_str(),
+    );
+    let sent_nonce = |net: &mut Network| {
+        let msg = QueryMsg::SentNonce {
+            connection_id: net.connection_id(HOP, NEXT_HOP),
+            controller: hop_controller.clone(),
+        };
+        let res: NonceResponse = net.chains[HOP].query(&tunnel.addr(), &msg).unwrap();
+        res.nonce
+    };
+    let nonce = sent_nonce(&mut net);
+    let code = forge(&mut net, MALLORY, HOP, hop_controller.clone(), nonce);
+    assert_eq!(code, TunnelErrorCode::Unauthorized);
+    let code = forge(&mut net, NEXT_HOP, HOP, hop_controller.clone(), nonce + 1);
+    assert_eq!(code, TunnelErrorCode::Unauthorized);
+    assert_eq!(forward_routes(&mut net, HOP), 1);
+
+    let relayed = net.relay_all().unwrap();
+    let pending = network_callback(&net, &controller, "pending");
+    assert_eq!(pending.len(), 1);
+    let dispatch: DispatchMigrateResponse = pending[0].msg.clone().unwrap_into();
+    assert!(matches!(dispatch.result, SubMsgResult::Ok(_)));
+
+    // a replayed ack finds no route
+    let returned_channel = net.channel_id(NEXT_HOP, HOP);
+    let (forward_ack, _) = relayed
+        .into_iter()
+        .find(|(packet, _)| packet.src.channel_id == returned_channel)
+        .expect("the ack is returned from the next hop");
+    net.resubmit(NEXT_HOP, forward_ack);
+    let ack = net.deliver_next(NEXT_HOP).unwrap();
+    assert_eq!(error(&ack).code, TunnelErrorCode::Unauthorized);
+    net.relay_all().unwrap();
+    assert_eq!(network_callback(&net, &controller, "pending").len(), 1);
+}
+
+#[test]
+fn forwards_reusing_a_job_id_keep_their_own_route() {
+    let mut net = forwarding_network();
+    let controller_code = net.chains[ORIGIN].store_code(MockController);
+    let controller = net.chains[ORIGIN]
+        .instantiate("creator", controller_code, &Empty {}, None)
+        .unwrap();
+    let proxy_code = net.chains[DESTINATION].store_code(MockProxy);
+    let inst_msg: CosmosMsg = WasmMsg::Instantiate {
+        admin: None,
+        code_id: proxy_code,
+        msg: to_binary(&Empty {}).unwrap(),
+        funds: vec![],
+        label: "proxy".to_string(),
+    }
+    .into();
+    let tunnel = TunnelContract::new(net.chains[ORIGIN].tunnel());
+    let channel_id = net.channel_id(ORIGIN, HOP);
+    let valid = vec![
+        net.channel_id(HOP, NEXT_HOP),
+        net.channel_id(NEXT_HOP, DESTINATION),
+    ];
+    // the next hop has no such channel, the forward fails there
+    let invalid = vec![net.channel_id(HOP, NEXT_HOP), "channel-99".to_string()];
+    let send = |net: &mut Network, hops: Vec<String>| {
+        let call = tunnel
+            .instantiate(&channel_id, proxy_code, inst_msg.clone())
+            .job_id("reused")
+            .forward(hops);
+        net.chains[ORIGIN].execute(controller.as_str(), &tunnel.addr(), call.msg(), vec![])
+    };
+
+    send(&mut net, invalid).unwrap();
+    net.deliver_next(ORIGIN).unwrap();
+    assert_eq!(forward_routes(&mut net, HOP), 1);
+
+    // the job is pending, forwarding it again is refused on the origin
+    let err = send(&mut net, valid.clone()).unwrap_err();
+    assert!(err.contains("already pending"), "{err}");
+    // and on the hop, as the route of the job is kept until its ack is returned
+    let sent: NonceResponse = net.chains[ORIGIN]
+        .query(
+            &tunnel.addr(),
+            &QueryMsg::SentNonce {
+                connection_id: net.connection_id(ORIGIN, HOP),
+                controller: controller.to_string(),
+            },
+        )
+        .unwrap();
+    let again = PacketMsg::<Empty>::Forward {
+        hops: valid.clone(),
+        visited: vec!["origin-1".to_string()],
+        packet: Box::new(PacketMsg::Instantiate {
+            controller: controller.to_string(),
+            inst_msg: inst_msg.clone(),
+            code_id: proxy_code,
+            job_id: Some("reused".to_string()),
+            nonce: sent.nonce + 5,
+        }),
+    };
+    net.send_raw(ORIGIN, &channel_id, to_binary(&again).unwrap())
+        .unwrap();
+    let ack = net.deliver_next(ORIGIN).unwrap();
+    assert_eq!(error(&ack).code, TunnelErrorCode::InvalidRoute);
+    assert_eq!(forward_routes(&mut net, HOP), 1);
+
+    // the failure of the first forward is returned to the origin and frees the job id
+    net.relay_all().unwrap();
+    let callbacks = network_callback(&net, &controller, "reused");
+    assert_eq!(callbacks.len(), 1);
+    assert!(matches!(callbacks[0].msg, StdAck::Error(_)));
+    assert_eq!(forward_routes(&mut net, HOP), 0);
+
+    send(&mut net, valid).unwrap();
+    net.relay_all().unwrap();
+    // the controller keeps the latest callback of a job
+    let callbacks = network_callback(&net, &controller, "reused");
+    let init: InstantiateResponse = callbacks[0].msg.clone().unwrap_into();
+    assert_eq!(init.job_id.as_deref(), Some("reused"));
+    assert_eq!(forward_routes(&mut net, HOP), 0);
+    assert_eq!(forward_routes(&mut net, NEXT_HOP), 0);
+}
+
+#[test]
+fn channel_metadata_exchanged_in_handshake() {
+    let Setup {
+        mut env,
+        controller,
+        counter,
+        ..
+    } = setup();
+    let metadata = |env: &TestEnv, side: Side, channel_id: &str| {
+        let chain = env.chain(side);
+        let msg = QueryMsg::ChannelMetadata {
+            channel_id: channel_id.to_string(),
+        };
+        let res: ChannelMetadataResponse = chain.query(&chain.tunnel(), &msg).unwrap();
+        res.metadata.unwrap()
+    };
+
+    let remote = metadata(&env, Side::Host, HOST_CHANNEL_ID);
+    assert_eq!(remote.chain_id, REMOTE_CHAIN_ID);
+    assert_eq!(remote.roles, vec![TunnelRole::Host, TunnelRole::Remote]);
+    assert!(remote.supports(TunnelOperation::Forward));
+    let host = metadata(&env, Side::Remote, REMOTE_CHANNEL_ID);
+    assert_eq!(host.chain_id, HOST_CHAIN_ID);
+
+    // a remote-only counterparty cannot forward packets
+    let mut remote_only = remote;
+    remote_only
+        .operations
+        .retain(|op| *op != TunnelOperation::Forward);
+    env.host.with_tunnel(|deps, _| {
+        ic_tunnel::state::CHANNEL_METADATA
+            .save(deps.storage, HOST_CHANNEL_ID, &remote_only)
+            .unwrap()
+    });
+    let call = tunnel(&env)
+        .dispatch(HOST_CHANNEL_ID, increment(&counter))
+        .forward(vec!["channel-9".to_string()]);
+    let err = env
+        .host
+        .execute(controller.as_str(), &env.host.tunnel(), call.msg(), vec![])
+        .unwrap_err();
+    assert!(err.contains("does not support Forward"), "{err}");
+}
+
+#[test]
+fn channel_open_checks_the_proposed_version() {
+    // an empty version leaves the choice to the tunnel
+    let env = TestEnv::with_init_version("");
+    let msg = QueryMsg::ChannelMetadata {
+        channel_id: HOST_CHANNEL_ID.to_string(),
+    };
+    let res: ChannelMetadataResponse = env.host.query(&env.host.tunnel(), &msg).unwrap();
+    assert_eq!(res.version, ProtocolVersion::LATEST);
+    assert_eq!(res.encoding, PacketEncoding::Json);
+
+    // another application's version is refused
+    let err = TestEnv::try_with_init_version("ics20-1").err().unwrap();
+    assert!(err.contains("Counterparty version must be"), "{err}");
+}
+
+#[test]
+fn channel_connect_checks_the_answered_encoding() {
+    let mut chain = Chain::new(HOST_CHAIN_ID);
+    let port_id = ibc_port(&chain.tunnel());
+    let channel = |version: &str| {
+        IbcChannel::new(
+            IbcEndpoint {
+                port_id: port_id.clone(),
+                channel_id: HOST_CHANNEL_ID.to_string(),
+            },
+            IbcEndpoint {
+                port_id: "wasm.counterparty".to_string(),
+                channel_id: REMOTE_CHANNEL_ID.to_string(),
+            },
+            IbcOrder::Unordered,
+            version,
+            HOST_CONNECTION_ID,
+        )
+    };
+    let metadata = |encoding| {
+        ChannelMetadata {
+            encoding,
+            ..ChannelMetadata::new(REMOTE_CHAIN_ID, vec![TunnelRole::Host, TunnelRole::Remote])
+        }
+        .to_version()
+        .unwrap()
+    };
+    let proposal = channel(&metadata(PacketEncoding::Proto));
+    let answer = metadata(PacketEncoding::Json);
+    let res = chain.with_tunnel(|mut deps, env| {
+        ic_tunnel::ibc_channel_open(
+            deps.branch(),
+            env.clone(),
+            IbcChannelOpenMsg::new_init(proposal.clone()),
+        )
+        .unwrap();
+        ic_tunnel::ibc_channel_connect(deps, env, IbcChannelConnectMsg::new_ack(proposal, &answer))
+    });
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::EncodingMismatch {
+            proposed: PacketEncoding::Proto,
+            answered: PacketEncoding::Json,
+        }
+    );
+
+    // the encoding the channel was opened with is kept
+    let proposal = channel(&metadata(PacketEncoding::Proto));
+    let answer = metadata(PacketEncoding::Proto);
+    chain.with_tunnel(|deps, env| {
+        ic_tunnel::ibc_channel_connect(deps, env, IbcChannelConnectMsg::new_ack(proposal, &answer))
+            .unwrap();
+    });
+    let msg = QueryMsg::ChannelMetadata {
+        channel_id: HOST_CHANNEL_ID.to_string(),
+    };
+    let res: ChannelMetadataResponse = chain.query(&chain.tunnel(), &msg).unwrap();
+    assert_eq!(res.encoding, PacketEncoding::Proto);
+}
+
+#[test]
+fn v1_channels_keep_working() {
+    let mut setup = setup_on(TestEnv::with_init_version(ProtocolVersion::V1.as_str()));
+    for (side, channel_id) in [
+        (Side::Host, HOST_CHANNEL_ID),
+        (Side::Remote, REMOTE_CHANNEL_ID),
+    ] {
+        let chain = setup.env.chain(side);
+        let msg = QueryMsg::ChannelMetadata {
+            channel_id: channel_id.to_string(),
+        };
+        let res: ChannelMetadataResponse = chain.query(&chain.tunnel(), &msg).unwrap();
+        assert_eq!(res.version, ProtocolVersion::V1);
+        assert_eq!(res.metadata, None);
+    }
+
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    // the host sends the packets of a v1 tunnel, without the fields it would refuse
+    let call = tunnel(env).query_remote_addr::<Empty>(HOST_CHANNEL_ID);
+    send(env, controller, call);
+    let packet = env.pending_packets(Side::Host).remove(0);
+    assert_eq!(
+        String::from_utf8(packet.data.to_vec()).unwrap(),
+        format!(r#"{{"who_am_i":{{"controller":"{controller}"}}}}"#)
+    );
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, increment(counter))
+        .job_id("dispatch");
+    send(env, controller, call);
+    let packet = env.pending_packets(Side::Host).remove(1);
+    let data = String::from_utf8(packet.data.to_vec()).unwrap();
+    assert!(
+        !data.contains("nonce") && !data.contains("fail_on_error"),
+        "{data}"
+    );
+    assert!(matches!(
+        from_slice(&packet.data).unwrap(),
+        v1::PacketMsg::<Empty>::Dispatch { job_id: Some(job_id), .. } if job_id == "dispatch"
+    ));
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    let dispatch: DispatchMigrateResponse = callback(env, controller, "dispatch").msg.unwrap_into();
+    assert!(matches!(dispatch.result, SubMsgResult::Ok(_)));
+    // the packets do not take a nonce, which would leave gaps on the v2 channels of the connection
+    let msg = QueryMsg::SentNonce {
+        connection_id: HOST_CONNECTION_ID.to_string(),
+        controller: controller.to_string(),
+    };
+    let res: NonceResponse = env.host.query(&env.host.tunnel(), &msg).unwrap();
+    assert_eq!(res.nonce, 0);
+
+    // and runs the packets of a v1 tunnel, which have no nonce
+    let msg = to_binary(&CounterExecuteMsg::Increment {}).unwrap();
+    let dispatch = format!(
+        r#"{{"dispatch":{{"controller":"{controller}","msg":{{"wasm":{{"execute":{{"contract_addr":"{counter}","msg":"{msg}","funds":[]}}}}}},"job_id":"v1"}}}}"#
+    );
+    env.send_raw(Side::Host, Binary::from(dispatch.as_bytes()))
+        .unwrap();
+    let ack = env.deliver_next(Side::Host).unwrap();
+    assert!(matches!(from_slice(&ack).unwrap(), StdAck::Result(_)));
+    assert_eq!(count(env, counter), 2);
+    let who_am_i = format!(r#"{{"who_am_i":{{"controller":"{controller}"}}}}"#);
+    env.send_raw(Side::Host, Binary::from(who_am_i.as_bytes()))
+        .unwrap();
+    let ack = env.deliver_next(Side::Host).unwrap();
+    let StdAck::Result(data) = from_slice(&ack).unwrap() else {
+        panic!("who_am_i failed");
+    };
+    let res: WhoAmIResponse = from_slice(&data).unwrap();
+    assert_eq!(res.account, proxy.as_str());
+
+    // errors are acknowledged with the plain message of a v1 tunnel
+    env.send_raw(Side::Host, Binary::from(br#"{"who_am_i":{}}"#.to_vec()))
+        .unwrap();
+    let ack = env.deliver_next(Side::Host).unwrap();
+    let StdAck::Error(err) = from_slice(&ack).unwrap() else {
+        panic!("expected an error ack");
+    };
+    assert!(err.starts_with("IBC Packet Error"), "{err}");
+    assert!(from_slice::<ErrorResponse>(err.as_bytes()).is_err());
+
+    // operations a v1 tunnel cannot run are refused before they are sent
+    for (call, reason) in [
+        (
+            tunnel(env)
+                .dispatch(HOST_CHANNEL_ID, fail(counter))
+                .fail_on_error(),
+            "fail_on_error is not supported on cw-tunnel-v1 channels",
+        ),
+        (
+            tunnel(env)
+                .dispatch(HOST_CHANNEL_ID, increment(counter))
+                .forward(vec!["channel-9".to_string()]),
+            "does not support Forward",
+        ),
+    ] {
+        let err = env
+            .host
+            .execute(controller.as_str(), &env.host.tunnel(), call.msg(), vec![])
+            .unwrap_err();
+        assert!(err.contains(reason), "{err}");
+    }
+}
+
+#[test]
+fn proto_channels_encode_packets_and_acks() {
+    let mut setup = setup_on(TestEnv::with_encoding(PacketEncoding::Proto));
+    for (side, channel_id) in [
+        (Side::Host, HOST_CHANNEL_ID),
+        (Side::Remote, REMOTE_CHANNEL_ID),
+    ] {
+        let chain = setup.env.chain(side);
+        let msg = QueryMsg::ChannelMetadata {
+            channel_id: channel_id.to_string(),
+        };
+        let res: ChannelMetadataResponse = chain.query(&chain.tunnel(), &msg).unwrap();
+        assert_eq!(res.version, ProtocolVersion::V2);
+        assert_eq!(res.encoding, PacketEncoding::Proto);
+    }
+
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+    let init: InstantiateResponse = callback(env, controller, "init").msg.unwrap_into();
+    assert_eq!(init.contract_address, proxy.as_str());
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, increment(counter))
+        .job_id("dispatch");
+    send(env, controller, call);
+    let packet = env.pending_packets(Side::Host).remove(0);
+    assert!(from_slice::<PacketMsg>(&packet.data).is_err());
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    let dispatch: DispatchMigrateResponse = callback(env, controller, "dispatch").msg.unwrap_into();
+    assert!(matches!(dispatch.result, SubMsgResult::Ok(_)));
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, fail(counter))
+        .job_id("strict")
+        .fail_on_error();
+    send(env, controller, call);
+    let relayed = env.relay_all().unwrap();
+    let (_, ack) = relayed.first().unwrap();
+    match PacketEncoding::Proto.decode_ack(ack).unwrap() {
+        StdAck::Error(err) => assert_eq!(
+            ErrorResponse::from_ack_error(&err).code,
+            TunnelErrorCode::ExecutionFailed
+        ),
+        StdAck::Result(_) => panic!("expected an error ack"),
+    }
+    let strict = callback(env, controller, "strict");
+    assert_eq!(strict.error_code, Some(TunnelErrorCode::ExecutionFailed));
+}
+
+/// An increment of the counter padded with whitespace to `size` bytes
+fn large_increment(counter: &Addr, size: usize) -> CosmosMsg {
+    let msg = format!(r#"{{"increment":{{}}}}{}"#, " ".repeat(size));
+    WasmMsg::Execute {
+        contract_addr: counter.to_string(),
+        msg: Binary::from(msg.into_bytes()),
+        funds: vec![],
+    }
+    .into()
+}
+
+fn chunk_of(packet: &IbcPacket) -> (u32, u32) {
+    match from_slice::<PacketMsg>(&packet.data).unwrap() {
+        PacketMsg::Chunk { chunk, .. } => (chunk.index, chunk.total),
+        packet => panic!("expected a chunk, got {packet:?}"),
+    }
+}
+
+#[test]
+fn large_packets_are_sent_in_chunks() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, large_increment(counter, 30 * 1024))
+        .job_id("large");
+    send(env, controller, call);
+    let packets = env.pending_packets(Side::Host);
+    let chunks: Vec<_> = packets.iter().map(chunk_of).collect();
+    assert_eq!(chunks, vec![(0, 2), (1, 2)]);
+
+    // the chunks may arrive in any order, the packet runs with the last one
+    env.delay(Side::Host, packets[0].sequence).unwrap();
+    let ack = env.relay(Side::Host, packets[1].sequence).unwrap();
+    let res: ChunkResponse = from_slice::<StdAck>(&ack).unwrap().unwrap_into();
+    assert_eq!((res.received, res.total), (1, 2));
+    assert_eq!(count(env, counter), 0);
+    assert!(callbacks(env, controller).iter().all(|c| c.id != "large"));
+
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    let dispatch: DispatchMigrateResponse = callback(env, controller, "large").msg.unwrap_into();
+    assert!(matches!(dispatch.result, SubMsgResult::Ok(_)));
+    assert_eq!(received_nonce(env, controller), 2);
+}
+
+#[test]
+fn encoded_chunks_fit_in_the_packet_size() {
+    for encoding in [PacketEncoding::Json, PacketEncoding::Proto] {
+        let mut setup = setup_on(TestEnv::with_encoding(encoding));
+        open_account(&mut setup);
+        let Setup {
+            env,
+            controller,
+            counter,
+            ..
+        } = &mut setup;
+
+        // the job id is sent in every chunk
+        let job_id = "j".repeat(1024);
+        let size = (MAX_CHUNKS as usize - 1) * MAX_PACKET_SIZE / 2;
+        let call = tunnel(env)
+            .dispatch(HOST_CHANNEL_ID, large_increment(counter, size))
+            .job_id(&job_id);
+        send(env, controller, call);
+        let packets = env.pending_packets(Side::Host);
+        assert!(packets.len() > 1);
+        for packet in &packets {
+            assert!(
+                packet.data.len() <= MAX_PACKET_SIZE,
+                "{encoding:?} chunk of {} bytes",
+                packet.data.len()
+            );
+        }
+        env.relay_all().unwrap();
+        assert_eq!(count(env, counter), 1);
+    }
+}
+
+#[test]
+fn expired_chunk_uploads_are_dropped() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, large_increment(counter, 30 * 1024))
+        .timeout(3 * CHUNK_UPLOAD_LIFETIME);
+    send(env, controller, call);
+    let first = env.pending_packets(Side::Host).remove(0);
+    env.deliver_next(Side::Host).unwrap();
+
+    env.remote.next_block(CHUNK_UPLOAD_LIFETIME + 1);
+    let ack = env.deliver_next(Side::Host).unwrap();
+    let res: ChunkResponse = from_slice::<StdAck>(&ack).unwrap().unwrap_into();
+    assert_eq!((res.received, res.total), (1, 2));
+    assert_eq!(count(env, counter), 0);
+
+    // the expired chunk has to be received again
+    env.resubmit(Side::Host, first);
+    env.deliver_next(Side::Host).unwrap();
+    assert_eq!(count(env, counter), 1);
+}
+
+#[test]
+fn chunk_uploads_are_bounded() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    // the host refuses a packet needing more chunks than the remote tunnel accepts
+    let size = MAX_CHUNKS as usize * MAX_PACKET_SIZE;
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, large_increment(counter, size));
+    let err = env
+        .host
+        .execute(controller.as_str(), &env.host.tunnel(), call.msg(), vec![])
+        .unwrap_err();
+    assert!(err.contains("chunks, more than the maximum"), "{err}");
+    assert!(env.pending_packets(Side::Host).is_empty());
+
+    // the remote tunnel keeps a bounded number of incomplete packets per controller
+    for i in 0..=MAX_UPLOADS as usize {
+        let call = tunnel(env).dispatch(HOST_CHANNEL_ID, large_increment(counter, 30 * 1024 + i));
+        send(env, controller, call);
+    }
+    let (first, rest): (Vec<_>, Vec<_>) = env
+        .pending_packets(Side::Host)
+        .into_iter()
+        .partition(|packet| chunk_of(packet).0 == 0);
+    let acks: Vec<_> = first
+        .iter()
+        .map(|packet| env.deliver(Side::Host, packet.sequence).unwrap())
+        .collect();
+    for ack in &acks[..MAX_UPLOADS as usize] {
+        let res: ChunkResponse = from_slice::<StdAck>(ack).unwrap().unwrap_into();
+        assert_eq!((res.received, res.total), (1, 2));
+    }
+    assert_eq!(
+        error(acks.last().unwrap()).code,
+        TunnelErrorCode::InvalidPacket
+    );
+
+    // completing a packet makes room for another one
+    env.deliver(Side::Host, rest[0].sequence).unwrap();
+    assert_eq!(count(env, counter), 1);
+    env.resubmit(Side::Host, first.last().unwrap().clone());
+    let ack = env.deliver_next(Side::Host).unwrap();
+    let res: ChunkResponse = from_slice::<StdAck>(&ack).unwrap().unwrap_into();
+    assert_eq!((res.received, res.total), (1, 2));
+}
+
+#[test]
+fn chunks_not_matching_the_hash_are_rejected() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, large_increment(counter, 30 * 1024))
+        .job_id("tampered");
+    send(env, controller, call);
+    let mut last = env.pending_packets(Side::Host).pop().unwrap();
+    env.drop_packet(Side::Host, last.sequence).unwrap();
+    let mut packet: PacketMsg = from_slice(&last.data).unwrap();
+    if let PacketMsg::Chunk { chunk, .. } = &mut packet {
+        let mut data = chunk.data.to_vec();
+        data[0] ^= 1;
+        chunk.data = data.into();
+    }
+    last.data = to_binary(&packet).unwrap();
+    env.resubmit(Side::Host, last);
+
+    let relayed = env.relay_all().unwrap();
+    let (_, ack) = relayed.last().unwrap();
+    assert_eq!(error(ack).code, TunnelErrorCode::InvalidPacket);
+    assert_eq!(count(env, counter), 0);
+    let tampered = callback(env, controller, "tampered");
+    assert_eq!(tampered.error_code, Some(TunnelErrorCode::InvalidPacket));
+}
+
+/// A signed dispatch of `msg` by the controller with `nonce`
+fn signed_dispatch(
+    env: &TestEnv,
+    controller: &Addr,
+    msg: CosmosMsg,
+    nonce: u64,
+    expires: Expiration,
+) -> SignedOperation {
+    SignedOperation {
+        controller: controller.to_string(),
+        chain_id: HOST_CHAIN_ID.to_string(),
+        tunnel: env.host.tunnel().to_string(),
+        nonce,
+        expires,
+        msg: tunnel(env)
+            .dispatch(HOST_CHANNEL_ID, msg)
+            .job_id(format!("signed-{nonce}"))
+            .msg()
+            .clone(),
+        session: None,
+    }
+}
+
+fn submit_signed(env: &mut TestEnv, payload: Binary, signature: Binary) -> Result<(), String> {
+    let call = tunnel(env).submit_signed::<Empty>(payload, signature);
+    env.host
+        .execute("relayer", &env.host.tunnel(), call.msg(), vec![])
+        .map(|_| ())
+}
+
+#[test]
+fn signed_operations_are_submitted_by_any_relayer() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let key = k256::ecdsa::SigningKey::from_bytes(&[1; 32]).unwrap();
+    let public_key = key.verifying_key().to_bytes().to_vec();
+    let call = tunnel(env).register_signing_key(SigningKey::Secp256k1(public_key.into()));
+    send(env, controller, call);
+
+    let op = signed_dispatch(env, controller, increment(counter), 1, Expiration::Never {});
+    let payload = op.payload().unwrap();
+    let signature: k256::ecdsa::Signature = key.sign(&payload);
+    let signature = Binary::from(signature.as_ref());
+    submit_signed(env, payload.clone(), signature.clone()).unwrap();
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    let dispatch: DispatchMigrateResponse = callback(env, controller, "signed-1").msg.unwrap_into();
+    assert!(matches!(dispatch.result, SubMsgResult::Ok(_)));
+
+    // the signed nonce is used up
+    let err = submit_signed(env, payload, signature).unwrap_err();
+    assert!(err.contains("Expected signed nonce 2, got 1"), "{err}");
+    let nonce: NonceResponse = env
+        .host
+        .query(
+            &env.host.tunnel(),
+            &QueryMsg::SignedNonce {
+                controller: controller.to_string(),
+            },
+        )
+        .unwrap();
+    assert_eq!(nonce.nonce, 1);
+}
+
+#[test]
+fn signed_operations_are_checked() {
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+
+    let key = ed25519_zebra::SigningKey::from([7; 32]);
+    let public_key = ed25519_zebra::VerificationKey::from(&key);
+    let call = tunnel(env).register_signing_key(SigningKey::Ed25519(
+        <[u8; 32]>::from(public_key).to_vec().into(),
+    ));
+    send(env, controller, call);
+    let sign = |key: &ed25519_zebra::SigningKey, op: &SignedOperation| {
+        let payload = op.payload().unwrap();
+        let signature = <[u8; 64]>::from(key.sign(&payload));
+        (payload, Binary::from(signature.to_vec()))
+    };
+
+    let expired = signed_dispatch(
+        env,
+        controller,
+        increment(counter),
+        1,
+        Expiration::AtHeight(1),
+    );
+    let (payload, signature) = sign(&key, &expired);
+    let err = submit_signed(env, payload, signature).unwrap_err();
+    assert!(err.contains("expired"), "{err}");
+
+    let op = signed_dispatch(env, controller, increment(counter), 1, Expiration::Never {});
+    let (payload, signature) = sign(&ed25519_zebra::SigningKey::from([8; 32]), &op);
+    let err = submit_signed(env, payload, signature).unwrap_err();
+    assert!(err.contains("Invalid signature"), "{err}");
+
+    // a signing key cannot grant operators
+    let mut grant = op.clone();
+    grant.msg = tunnel(env)
+        .grant_operator(
+            "operator",
+            OperatorGrant {
+                scopes: vec![OperatorScope::Dispatch],
+                expires: None,
+                allowed_msgs: None,
+            },
+        )
+        .msg()
+        .clone();
+    let (payload, signature) = sign(&key, &grant);
+    let err = submit_signed(env, payload, signature).unwrap_err();
+    assert!(err.contains("Unauthorized"), "{err}");
+
+    let (payload, signature) = sign(&key, &op);
+    send(env, controller, tunnel(env).revoke_signing_key());
+    let err = submit_signed(env, payload, signature).unwrap_err();
+    assert!(err.contains("No signing key"), "{err}");
+    assert_eq!(count(env, counter), 0);
+}
+
+#[test]
+fn session_keys_dispatch_within_their_scope() {
+    let mut setup = setup();
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+    env.remote.set_balance(&proxy, coins(100, "uatom"));
+
+    let key = ed25519_zebra::SigningKey::from([9; 32]);
+    let public_key = <[u8; 32]>::from(ed25519_zebra::VerificationKey::from(&key));
+    let session = SessionKey {
+        key: SigningKey::Ed25519(public_key.to_vec().into()),
+        expires: Expiration::AtTime(env.host.block.time.plus_seconds(3600)),
+        channels: vec![HOST_CHANNEL_ID.to_string()],
+        allowed_msgs: vec![CosmosMsgKind::Wasm, CosmosMsgKind::Bank],
+        spend_limit: coins(100, "uatom"),
+    };
+    // the spend limit cannot hold for messages spending unknown funds
+    for kind in [CosmosMsgKind::Stargate, CosmosMsgKind::Custom] {
+        let unlimited = SessionKey {
+            allowed_msgs: vec![CosmosMsgKind::Bank, kind],
+            ..session.clone()
+        };
+        let call = tunnel(env).register_session_key::<Empty>("wallet", unlimited);
+        let err = env
+            .host
+            .execute(controller.as_str(), &env.host.tunnel(), call.msg(), vec![])
+            .unwrap_err();
+        assert!(err.contains("cannot be limited"), "{err}");
+    }
+    send(
+        env,
+        controller,
+        tunnel(env).register_session_key("wallet", session.clone()),
+    );
+
+    let mut nonce = 0;
+    let mut submit = |env: &mut TestEnv, msg: ExecuteMsg| {
+        let op = SignedOperation {
+            msg,
+            session: Some("wallet".to_string()),
+            ..signed_dispatch(
+                env,
+                controller,
+                increment(counter),
+                nonce + 1,
+                Expiration::Never {},
+            )
+        };
+        let payload = op.payload().unwrap();
+        let signature = <[u8; 64]>::from(key.sign(&payload));
+        let res = submit_signed(env, payload, signature.to_vec().into());
+        if res.is_ok() {
+            nonce += 1;
+        }
+        res
+    };
+    let contract = tunnel(env);
+    let dispatch =
+        |channel_id: &str, msg: CosmosMsg| contract.dispatch(channel_id, msg).msg().clone();
+    let bank_send = |amount: u128| -> CosmosMsg {
+        BankMsg::Send {
+            to_address: "receiver".to_string(),
+            amount: coins(amount, "uatom"),
+        }
+        .into()
+    };
+
+    submit(env, dispatch(HOST_CHANNEL_ID, increment(counter))).unwrap();
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+
+    // the funds count as spent once the dispatch is sent, whatever its outcome
+    submit(env, dispatch(HOST_CHANNEL_ID, bank_send(60))).unwrap();
+    env.relay_all().unwrap();
+    let receiver = Addr::unchecked("receiver");
+    assert_eq!(env.remote.balance(&receiver, "uatom"), coin(60, "uatom"));
+    let err = submit(env, dispatch(HOST_CHANNEL_ID, bank_send(50))).unwrap_err();
+    assert!(err.contains("spend limit exceeded for uatom"), "{err}");
+    let err = submit(env, dispatch("channel-9", increment(counter))).unwrap_err();
+    assert!(
+        err.contains("cannot dispatch on channel channel-9"),
+        "{err}"
+    );
+    let stargate = CosmosMsg::Stargate {
+        type_url: "/cosmos.gov.v1beta1.MsgVote".to_string(),
+        value: Binary::default(),
+    };
+    let err = submit(env, dispatch(HOST_CHANNEL_ID, stargate)).unwrap_err();
+    assert!(err.contains("Stargate"), "{err}");
+    let query = tunnel(env).query_remote_addr(HOST_CHANNEL_ID).msg().clone();
+    let err = submit(env, query).unwrap_err();
+    assert!(err.contains("Unauthorized"), "{err}");
+
+    let res: SessionKeyResponse = env
+        .host
+        .query(
+            &env.host.tunnel(),
+            &QueryMsg::SessionKey {
+                controller: controller.to_string(),
+                name: "wallet".to_string(),
+            },
+        )
+        .unwrap();
+    assert_eq!(res.spent, coins(60, "uatom"));
+    // the session has its own nonces, the signing key of the controller is not affected
+    assert_eq!(res.nonce, 2);
+    let res: NonceResponse = env
+        .host
+        .query(
+            &env.host.tunnel(),
+            &QueryMsg::SignedNonce {
+                controller: controller.to_string(),
+            },
+        )
+        .unwrap();
+    assert_eq!(res.nonce, 0);
+
+    // registering the session again does not reset its nonces, its operations cannot be replayed
+    let op = SignedOperation {
+        session: Some("wallet".to_string()),
+        ..signed_dispatch(env, controller, increment(counter), 1, Expiration::Never {})
+    };
+    let payload = op.payload().unwrap();
+    let signature = <[u8; 64]>::from(key.sign(&payload));
+    send(
+        env,
+        controller,
+        tunnel(env).register_session_key("wallet", session),
+    );
+    let err = submit_signed(env, payload, signature.to_vec().into()).unwrap_err();
+    assert!(err.contains("Expected signed nonce 3, got 1"), "{err}");
+
+    env.host.next_block(3601);
+    let err = submit(env, dispatch(HOST_CHANNEL_ID, increment(counter))).unwrap_err();
+    assert!(err.contains("Session key has expired"), "{err}");
+}
+
+fn msg_policy(env: &TestEnv, account: Option<&Addr>) -> Option<MsgPolicy> {
+    let res: MsgPolicyResponse = env
+        .remote
+        .query(
+            &env.remote.tunnel(),
+            &QueryMsg::MsgPolicy {
+                account: account.map(Addr::to_string),
+            },
+        )
+        .unwrap();
+    res.policy
+}
+
+#[test]
+fn msg_policies_set_by_governance() {
+    let mut setup = setup();
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        proxy_code,
+    } = &mut setup;
+
+    let global = MsgPolicy {
+        allow: vec![MsgCategory::WasmExecute {
+            contracts: vec![counter.to_string()],
+        }],
+        deny: vec![],
+    };
+    let tunnel_addr = env.remote.tunnel();
+    env.remote
+        .sudo(
+            &tunnel_addr,
+            &GovernanceMsg::SetMsgPolicy {
+                account: None,
+                policy: global.clone(),
+            },
+        )
+        .unwrap();
+    assert_eq!(msg_policy(env, Some(&proxy)), Some(global.clone()));
+
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, increment(counter))
+        .job_id("allowed");
+    send(env, controller, call);
+    let bank_send: CosmosMsg = BankMsg::Send {
+        to_address: "thief".to_string(),
+        amount: coins(100, "uatom"),
+    }
+    .into();
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, bank_send.clone())
+        .job_id("denied");
+    send(env, controller, call);
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+    assert_eq!(callback(env, controller, "allowed").error_code, None);
+    let denied = callback(env, controller, "denied");
+    assert_eq!(denied.error_code, Some(TunnelErrorCode::PolicyViolation));
+
+    // the policy of the proxy replaces the global one
+    let own = MsgPolicy {
+        allow: vec![
+            MsgCategory::Bank,
+            MsgCategory::WasmExecute {
+                contracts: vec![counter.to_string()],
+            },
+        ],
+        deny: vec![
+            DenyRule::Recipient {
+                address: "thief".to_string(),
+            },
+            DenyRule::Denom {
+                denom: "uatom".to_string(),
+            },
+        ],
+    };
+    env.remote
+        .sudo(
+            &tunnel_addr,
+            &GovernanceMsg::SetMsgPolicy {
+                account: Some(proxy.to_string()),
+                policy: own.clone(),
+            },
+        )
+        .unwrap();
+    assert_eq!(msg_policy(env, Some(&proxy)), Some(own.clone()));
+    assert_eq!(msg_policy(env, None), Some(global.clone()));
+
+    // the addresses of a policy must be valid and normalized
+    for address in ["THIEF", "x"] {
+        let invalid = MsgPolicy {
+            deny: vec![DenyRule::Recipient {
+                address: address.to_string(),
+            }],
+            ..own.clone()
+        };
+        env.remote
+            .sudo(
+                &tunnel_addr,
+                &GovernanceMsg::SetMsgPolicy {
+                    account: Some(proxy.to_string()),
+                    policy: invalid,
+                },
+            )
+            .unwrap_err();
+    }
+
+    let paid_increment = WasmMsg::Execute {
+        contract_addr: counter.to_string(),
+        msg: to_binary(&CounterExecuteMsg::Increment {}).unwrap(),
+        funds: coins(1, "uatom"),
+    };
+    // the recipient is matched in any casing of its address
+    let shouting_thief = BankMsg::Send {
+        to_address: "THIEF".to_string(),
+        amount: coins(100, "ujuno"),
+    };
+    for (job_id, msg) in [
+        ("thief", bank_send),
+        ("shouting thief", shouting_thief.into()),
+        ("atom", paid_increment.into()),
+    ] {
+        let call = tunnel(env).dispatch(HOST_CHANNEL_ID, msg).job_id(job_id);
+        send(env, controller, call);
+    }
+    // a migration could replace the proxy with code ignoring the policy
+    let migrate_msg = WasmMsg::Migrate {
+        contract_addr: proxy.to_string(),
+        new_code_id: *proxy_code,
+        msg: to_binary(&Empty {}).unwrap(),
+    };
+    let call = tunnel(env)
+        .migrate(HOST_CHANNEL_ID, *proxy_code, migrate_msg.into())
+        .job_id("migrate");
+    send(env, controller, call);
+    env.relay_all().unwrap();
+    for job_id in ["thief", "shouting thief", "atom", "migrate"] {
+        let res = callback(env, controller, job_id);
+        assert_eq!(res.error_code, Some(TunnelErrorCode::PolicyViolation));
+    }
+    assert_eq!(count(env, counter), 1);
+
+    env.remote
+        .sudo(
+            &tunnel_addr,
+            &GovernanceMsg::RemoveMsgPolicy {
+                account: Some(proxy.to_string()),
+            },
+        )
+        .unwrap();
+    assert_eq!(msg_policy(env, Some(&proxy)), Some(global));
+    env.remote
+        .sudo(
+            &tunnel_addr,
+            &GovernanceMsg::RemoveMsgPolicy { account: None },
+        )
+        .unwrap();
+    assert_eq!(msg_policy(env, Some(&proxy)), None);
+}
+
+fn paid_increment(counter: &Addr, amount: u128) -> CosmosMsg {
+    WasmMsg::Execute {
+        contract_addr: counter.to_string(),
+        msg: to_binary(&CounterExecuteMsg::Increment {}).unwrap(),
+        funds: coins(amount, "uatom"),
+    }
+    .into()
+}
+
+fn spend_limit(env: &TestEnv, proxy: &Addr) -> SpendLimitResponse {
+    env.remote
+        .query(
+            &env.remote.tunnel(),
+            &QueryMsg::SpendLimit {
+                account: proxy.to_string(),
+            },
+        )
+        .unwrap()
+}
+
+#[test]
+fn spend_limits_of_proxies() {
+    let mut setup = setup();
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        proxy_code,
+    } = &mut setup;
+
+    let limit = SpendLimit {
+        max: coins(100, "uatom"),
+        period: 3600,
+    };
+    let tunnel_addr = env.remote.tunnel();
+    env.remote
+        .sudo(
+            &tunnel_addr,
+            &GovernanceMsg::SetSpendLimit {
+                account: proxy.to_string(),
+                limit: limit.clone(),
+            },
+        )
+        .unwrap();
+    env.remote.set_balance(&proxy, coins(1000, "uatom"));
+
+    let dispatch = |env: &mut TestEnv, job_id: &str, msg: CosmosMsg| {
+        let call = tunnel(env).dispatch(HOST_CHANNEL_ID, msg).job_id(job_id);
+        send(env, controller, call);
+        env.relay_all().unwrap();
+        callback(env, controller, job_id)
+    };
+    assert_eq!(
+        dispatch(env, "60", paid_increment(counter, 60)).error_code,
+        None
+    );
+    let res = dispatch(env, "50", paid_increment(counter, 50));
+    assert_eq!(res.error_code, Some(TunnelErrorCode::SpendLimitExceeded));
+    // failed dispatches spend nothing
+    let failed = WasmMsg::Execute {
+        contract_addr: counter.to_string(),
+        msg: to_binary(&CounterExecuteMsg::Fail {}).unwrap(),
+        funds: coins(30, "uatom"),
+    };
+    dispatch(env, "failed", failed.into());
+    assert_eq!(count(env, counter), 1);
+    let res = spend_limit(env, &proxy);
+    assert_eq!(res.limit, Some(limit));
+    assert_eq!(res.spent, coins(60, "uatom"));
+
+    // the controller can tighten the limit through its proxy, but not loosen it
+    let remote_tunnel = TunnelContract::new(tunnel_addr.clone());
+    let tighter = SpendLimit {
+        max: coins(80, "uatom"),
+        period: 7200,
+    };
+    let set_limit = |limit: SpendLimit| {
+        remote_tunnel
+            .set_spend_limit::<Empty>(limit)
+            .into_cosmos_msg()
+            .unwrap()
+    };
+    let res: DispatchMigrateResponse = dispatch(env, "tighten", set_limit(tighter.clone()))
+        .msg
+        .unwrap_into();
+    assert!(matches!(res.result, SubMsgResult::Ok(_)));
+    let looser = SpendLimit {
+        max: coins(1000, "uatom"),
+        period: 7200,
+    };
+    let res: DispatchMigrateResponse = dispatch(env, "loosen", set_limit(looser.clone()))
+        .msg
+        .unwrap_into();
+    assert!(matches!(res.result, SubMsgResult::Err(err) if err.contains("only tighten")));
+    assert_eq!(spend_limit(env, &proxy).limit, Some(tighter));
+    let res = dispatch(env, "30", paid_increment(counter, 30));
+    assert_eq!(res.error_code, Some(TunnelErrorCode::SpendLimitExceeded));
+
+    // spends leave the rolling period
+    env.host.next_block(7201);
+    env.remote.next_block(7201);
+    assert_eq!(spend_limit(env, &proxy).spent, vec![]);
+    assert_eq!(
+        dispatch(env, "80", paid_increment(counter, 80)).error_code,
+        None
+    );
+    assert_eq!(count(env, counter), 2);
+
+    // governance can set any limit
+    env.remote
+        .sudo(
+            &tunnel_addr,
+            &GovernanceMsg::SetSpendLimit {
+                account: proxy.to_string(),
+                limit: looser,
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        dispatch(env, "500", paid_increment(counter, 500)).error_code,
+        None
+    );
+    assert_eq!(spend_limit(env, &proxy).spent, coins(580, "uatom"));
+    // only the successful dispatches moved funds
+    assert_eq!(env.remote.balance(counter, "uatom"), coin(640, "uatom"));
+    assert_eq!(env.remote.balance(&proxy, "uatom"), coin(360, "uatom"));
+
+    // messages whose spend is not known are refused while the proxy has a limit
+    let stargate = CosmosMsg::Stargate {
+        type_url: "/cosmos.bank.v1beta1.MsgSend".to_string(),
+        value: Binary::default(),
+    };
+    let own_execute = WasmMsg::Execute {
+        contract_addr: proxy.to_string(),
+        msg: to_binary(&Empty {}).unwrap(),
+        funds: vec![],
+    };
+    let mut unknown = vec![("stargate", stargate), ("own execute", own_execute.into())];
+    #[cfg(not(feature = "token-factory"))]
+    unknown.push(("custom", CosmosMsg::Custom(Empty {})));
+    for (job_id, msg) in unknown {
+        let res = dispatch(env, job_id, msg);
+        assert_eq!(res.error_code, Some(TunnelErrorCode::SpendLimitExceeded));
+    }
+    // and so is a migration, which could replace the proxy with code ignoring the limit
+    let migrate = |env: &mut TestEnv, job_id: &str| {
+        let migrate_msg = WasmMsg::Migrate {
+            contract_addr: proxy.to_string(),
+            new_code_id: *proxy_code,
+            msg: to_binary(&Empty {}).unwrap(),
+        };
+        let call = tunnel(env)
+            .migrate(HOST_CHANNEL_ID, *proxy_code, migrate_msg.into())
+            .job_id(job_id);
+        send(env, controller, call);
+        env.relay_all().unwrap();
+        callback(env, controller, job_id)
+    };
+    let res = migrate(env, "limited migration");
+    assert_eq!(res.error_code, Some(TunnelErrorCode::SpendLimitExceeded));
+
+    env.remote
+        .sudo(
+            &tunnel_addr,
+            &GovernanceMsg::RemoveSpendLimit {
+                account: proxy.to_string(),
+            },
+        )
+        .unwrap();
+    assert_eq!(spend_limit(env, &proxy).limit, None);
+    assert_eq!(migrate(env, "migration").error_code, None);
+}
+
+#[test]
+fn custom_tunnels_override_the_hooks() {
+    let host = Chain::new_with_tunnel(
+        HOST_CHAIN_ID,
+        MinimalTunnel { proxy_code_id: 0 },
+        &MinimalTunnelInstantiateMsg {
+            controllers: vec!["alice".to_string()],
+        },
+    );
+    // the tunnel is the first code of the chain, the proxy the second
+    let mut remote = Chain::new_with_tunnel(
+        REMOTE_CHAIN_ID,
+        MinimalTunnel { proxy_code_id: 2 },
+        &MinimalTunnelInstantiateMsg {
+            controllers: vec![],
+        },
+    );
+    assert_eq!(remote.store_code(MockProxy), 2);
+    let counter_code = remote.store_code(MockCounter);
+    let counter = remote
+        .instantiate("creator", counter_code, &Empty {}, None)
+        .unwrap();
+    let mut env = TestEnv::with_chains(host, remote);
+    let contract = TunnelContract::new(env.host.tunnel());
+
+    // only the controllers of the tunnel can send operations
+    let inst_msg = WasmMsg::Instantiate {
+        admin: None,
+        code_id: 99,
+        msg: to_binary(&Empty {}).unwrap(),
+        funds: vec![],
+        label: "proxy".to_string(),
+    };
+    let call = contract.instantiate::<Empty>(HOST_CHANNEL_ID, 99, inst_msg.into());
+    let err = env
+        .host
+        .execute("mallory", &env.host.tunnel(), call.msg(), vec![])
+        .unwrap_err();
+    assert!(err.contains("Unauthorized"), "{err}");
+    env.host
+        .execute("alice", &env.host.tunnel(), call.msg(), vec![])
+        .unwrap();
+
+    // the proxy is instantiated from the code of the tunnel, without an admin
+    let relayed = env.relay_all().unwrap();
+    let init: InstantiateResponse = from_slice::<StdAck>(&relayed[0].1).unwrap().unwrap_into();
+    let proxy = Addr::unchecked(init.contract_address);
+    assert_eq!(env.remote.code_id(&proxy), Some(2));
+    let err = env
+        .remote
+        .migrate(env.remote.tunnel().as_str(), &proxy, 2, &Empty {})
+        .unwrap_err();
+    assert!(err.contains("is not the admin"), "{err}");
+
+    // the default handlers dispatch through the proxy
+    let call = contract.dispatch(HOST_CHANNEL_ID, increment(&counter));
+    env.host
+        .execute("alice", &env.host.tunnel(), call.msg(), vec![])
+        .unwrap();
+    env.relay_all().unwrap();
+    assert_eq!(count(&env, &counter), 1);
+}
+
+fn migrate_tunnel(chain: &mut Chain) -> Result<cosmwasm_std::Response, ContractError> {
+    chain.with_tunnel(|deps, env| ic_tunnel::migrate(deps, env, MigrateMsg { nonce_window: None }))
+}
+
+#[test]
+fn first_release_migrates_and_keeps_its_proxies() {
+    let mut setup = setup();
+    let proxy = open_account(&mut setup);
+    let Setup {
+        env,
+        controller,
+        counter,
+        ..
+    } = &mut setup;
+    // the state of the first release: the proxies in `accounts`, the sender of a call left
+    // in `pending` and no contract version
+    env.remote.with_tunnel(|deps, _| {
+        cw2::CONTRACT.remove(deps.storage);
+        NONCE_WINDOW.remove(deps.storage);
+        RECEIVED_NONCES.clear(deps.storage);
+        deps.storage.set(
+            b"pending",
+            br#"{"connection_id":"connection-1","port_id":"wasm.tunnel","controller":"controller","job_id":null}"#,
+        );
+    });
+
+    let res = migrate_tunnel(&mut env.remote).unwrap();
+    assert_eq!(
+        TunnelMigrated::find(&res.events),
+        Some(TunnelMigrated {
+            from_version: "0.1.0".to_string(),
+            to_version: CONTRACT_VERSION.to_string(),
+            migrations: vec!["0.2.0".to_string()],
+        })
+    );
+    env.remote.with_tunnel(|deps, _| {
+        let version = cw2::get_contract_version(deps.storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+        assert_eq!(version.version, CONTRACT_VERSION);
+        assert_eq!(deps.storage.get(b"pending"), None);
+        assert_eq!(NONCE_WINDOW.load(deps.storage).unwrap(), MAX_NONCE_WINDOW);
+    });
+
+    // the proxy of the first release keeps running the dispatches of its controller
+    assert_eq!(account(env, controller), Some(proxy.to_string()));
+    let call = tunnel(env).dispatch(HOST_CHANNEL_ID, increment(counter));
+    send(env, controller, call);
+    env.relay_all().unwrap();
+    assert_eq!(count(env, counter), 1);
+
+    // migrating to the same version applies nothing and leaves the migrations out of the event
+    let res = migrate_tunnel(&mut env.remote).unwrap();
+    let event = res
+        .events
+        .iter()
+        .find(|event| TunnelMigrated::matches(event))
+        .unwrap();
+    assert!(event.attributes.iter().all(|attr| attr.key != "migrations"));
+    let migrated = TunnelMigrated::try_from(event).unwrap();
+    assert_eq!(migrated.from_version, CONTRACT_VERSION);
+    assert_eq!(migrated.migrations, Vec::<String>::new());
+}
+
+#[test]
+fn migrations_to_older_versions_are_refused() {
+    let mut env = TestEnv::new();
+    env.remote.with_tunnel(|deps, _| {
+        cw2::set_contract_version(deps.storage, CONTRACT_NAME, "9.0.0").unwrap()
+    });
+    assert_eq!(
+        migrate_tunnel(&mut env.remote).unwrap_err(),
+        ContractError::CannotMigrateDowngrade {
+            from: "9.0.0".to_string(),
+            to: CONTRACT_VERSION.to_string(),
+        }
+    );
+
+    // nor from another contract
+    env.remote.with_tunnel(|deps, _| {
+        cw2::set_contract_version(deps.storage, "crates.io:cw1-whitelist", "0.1.0").unwrap()
+    });
+    assert_eq!(
+        migrate_tunnel(&mut env.remote).unwrap_err(),
+        ContractError::InvalidMigrationContract("crates.io:cw1-whitelist".to_string())
+    );
+    env.remote.with_tunnel(|deps, _| {
+        let version = cw2::get_contract_version(deps.storage).unwrap();
+        assert_eq!(version.version, "0.1.0");
+    });
+}
+
+/// A tunnel built for the token factory binding, run with
+/// `cargo test -p tunnel-testing --features token-factory`
+#[cfg(feature = "token-factory")]
+#[test]
+fn binding_messages_are_dispatched() {
+    use cosmwasm_tunnel::bindings::{TokenFactoryMsg, TokenMsg};
+
+    let mut setup = setup();
+    open_account(&mut setup);
+    let Setup {
+        env, controller, ..
+    } = &mut setup;
+    let create_denom = CosmosMsg::Custom(TokenFactoryMsg::Token(TokenMsg::CreateDenom {
+        subdenom: "ticket".to_string(),
+    }));
+    let call = tunnel(env)
+        .dispatch(HOST_CHANNEL_ID, create_denom.clone())
+        .job_id("denom");
+    env.host
+        .execute(controller.as_str(), &env.host.tunnel(), call.msg(), vec![])
+        .unwrap();
+    let packet: PacketMsg<TokenFactoryMsg> =
+        from_binary(&env.pending_packets(Side::Host)[0].data).unwrap();
+    assert!(matches!(packet, PacketMsg::Dispatch { msg, .. } if msg == create_denom));
+
+    // the simulated chain has no token factory, so the proxy cannot run it
+    env.relay_all().unwrap();
+    let res: DispatchMigrateResponse = callback(env, controller, "denom").msg.unwrap_into();
+    assert!(matches!(res.result, SubMsgResult::Err(_)));
+}
@@ -0,0 +1,113 @@
+//! The checks of the tunnel that need no chain: the replay window, the nesting limit and forwarded controllers
+use cosmwasm_std::StdError;
+use cosmwasm_tunnel::{
+    forwarded_controller, split_forwarded_controller, PacketMsg, MAX_NONCE_WINDOW, MAX_PACKET_DEPTH,
+};
+use ic_tunnel::error::ContractError;
+use ic_tunnel::state::ReceivedNonces;
+
+#[test]
+fn nonces_are_received_once_within_the_window() {
+    let mut nonces = ReceivedNonces::default();
+    assert_eq!(nonces.receive(0, 8), Err(ContractError::InvalidNonce(0)));
+    for nonce in [1, 2, 3] {
+        nonces.receive(nonce, 8).unwrap();
+    }
+    assert_eq!(nonces.receive(2, 8), Err(ContractError::InvalidNonce(2)));
+
+    // nonces below the highest are received out of order, once
+    nonces.receive(10, 8).unwrap();
+    nonces.receive(5, 8).unwrap();
+    assert_eq!(nonces.receive(5, 8), Err(ContractError::InvalidNonce(5)));
+    assert_eq!(nonces.highest, 10);
+    // the window is the `window` nonces up to the highest, the ones received in it are refused
+    assert_eq!(nonces.receive(2, 8), Err(ContractError::InvalidNonce(2)));
+    nonces.receive(4, 8).unwrap();
+    assert_eq!(nonces.receive(3, 8), Err(ContractError::InvalidNonce(3)));
+}
+
+#[test]
+fn nonces_far_ahead_clear_the_window() {
+    let window = MAX_NONCE_WINDOW;
+    let mut nonces = ReceivedNonces::default();
+    nonces.receive(100, window).unwrap();
+    nonces.receive(100 + window, window).unwrap();
+    assert_eq!(nonces.received, 1);
+    // the nonces skipped over are still received in the window
+    nonces.receive(101, window).unwrap();
+    assert_eq!(
+        nonces.receive(100, window),
+        Err(ContractError::InvalidNonce(100))
+    );
+    nonces.receive(u64::MAX, window).unwrap();
+    assert_eq!(nonces.received, 1);
+    assert_eq!(
+        nonces.receive(u64::MAX, window),
+        Err(ContractError::InvalidNonce(u64::MAX))
+    );
+}
+
+fn decode_err(data: &[u8]) -> String {
+    match PacketMsg::<cosmwasm_std::Empty>::decode(data) {
+        Err(StdError::ParseErr { msg, .. }) => msg,
+        res => panic!("expected a parse error, got {res:?}"),
+    }
+}
+
+#[test]
+fn packets_nested_deeper_than_the_limit_are_refused() {
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    // within the limit the data is decoded, and is not a packet
+    let err = decode_err(nested(MAX_PACKET_DEPTH).as_bytes());
+    assert!(!err.contains("nested deeper"), "{err}");
+    let err = decode_err(nested(MAX_PACKET_DEPTH + 1).as_bytes());
+    assert!(
+        err.contains(&format!("nested deeper than {MAX_PACKET_DEPTH}")),
+        "{err}"
+    );
+    // objects count as arrays do, closing brackets do not
+    let objects = format!(
+        "{}{}",
+        r#"{"a":"#.repeat(MAX_PACKET_DEPTH + 1),
+        "}".repeat(MAX_PACKET_DEPTH + 1)
+    );
+    assert!(decode_err(objects.as_bytes()).contains("nested deeper"));
+    let closed = "[]".repeat(MAX_PACKET_DEPTH + 1);
+    assert!(!decode_err(closed.as_bytes()).contains("nested deeper"));
+}
+
+#[test]
+fn brackets_in_strings_are_not_nesting() {
+    let controller = format!(r#""{}"#, "[{".repeat(MAX_PACKET_DEPTH));
+    let packet: PacketMsg = PacketMsg::WhoAmI {
+        controller: controller.clone(),
+        nonce: 1,
+    };
+    let data = cosmwasm_std::to_vec(&packet).unwrap();
+    assert_eq!(PacketMsg::decode(&data).unwrap(), packet);
+    // a string ending with an escaped backslash is closed, the brackets after it count
+    let data = format!(
+        r#"["\\",{}{}]"#,
+        "[".repeat(MAX_PACKET_DEPTH),
+        "]".repeat(MAX_PACKET_DEPTH)
+    );
+    assert!(decode_err(data.as_bytes()).contains("nested deeper"));
+}
+
+#[test]
+fn forwarded_controllers_split_into_their_origin() {
+    let controller = forwarded_controller("connection-0", "wasm.tunnel", "juno1controller");
+    assert_eq!(controller, "connection-0/wasm.tunnel/juno1controller");
+    assert_eq!(
+        split_forwarded_controller(&controller),
+        Some(("connection-0", "wasm.tunnel", "juno1controller"))
+    );
+    assert_eq!(split_forwarded_controller("juno1controller"), None);
+    assert_eq!(split_forwarded_controller("connection-0/wasm.tunnel"), None);
+
+    // forwarded twice, the controller on the previous chain is itself forwarded
+    let twice = forwarded_controller("connection-3", "wasm.tunnel", &controller);
+    let (connection_id, port_id, previous) = split_forwarded_controller(&twice).unwrap();
+    assert_eq!((connection_id, port_id), ("connection-3", "wasm.tunnel"));
+    assert_eq!(previous, controller);
+}
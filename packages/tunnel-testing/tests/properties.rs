@@ -0,0 +1,412 @@
+#![cfg(all(feature = "host", feature = "remote"))]
+use cosmwasm_std::{
+    from_slice, to_vec, Addr, BankMsg, Binary, Coin, CosmosMsg, Empty, IbcAcknowledgement,
+    IbcEndpoint, IbcPacket, IbcPacketAckMsg, IbcPacketReceiveMsg, IbcReceiveResponse, IbcTimeout,
+    WasmMsg,
+};
+use cosmwasm_tunnel::{
+    ErrorResponse, ExecuteMsg, PacketEncoding, PacketMsg, ReceiveIcaResponseMsg, StdAck, TunnelAck,
+    TunnelContract, TunnelErrorCode, TunnelReceiverMsg, MAX_FORWARD_HOPS, PACKET_LIFETIME,
+};
+use proptest::prelude::*;
+use tunnel_testing::{ibc_port, Side, TestEnv, HOST_CHAIN_ID, HOST_CHANNEL_ID, REMOTE_CHANNEL_ID};
+
+const CONTROLLER: &str = "controller";
+
+fn addr() -> impl Strategy<Value = String> {
+    "[a-z0-9]{3,40}"
+}
+
+fn coins() -> impl Strategy<Value = Vec<Coin>> {
+    prop::collection::vec(
+        (any::<u128>(), "[a-z]{3,12}").prop_map(|(amount, denom)| Coin::new(amount, denom)),
+        0..3,
+    )
+}
+
+fn binary() -> impl Strategy<Value = Binary> {
+    prop::collection::vec(any::<u8>(), 0..64).prop_map(Binary::from)
+}
+
+fn cosmos_msg() -> impl Strategy<Value = CosmosMsg> {
+    prop_oneof![
+        (addr(), coins())
+            .prop_map(|(to_address, amount)| BankMsg::Send { to_address, amount }.into()),
+        (addr(), binary(), coins()).prop_map(|(contract_addr, msg, funds)| WasmMsg::Execute {
+            contract_addr,
+            msg,
+            funds
+        }
+        .into()),
+        (
+            prop::option::of(addr()),
+            any::<u64>(),
+            binary(),
+            coins(),
+            any::<String>()
+        )
+            .prop_map(|(admin, code_id, msg, funds, label)| WasmMsg::Instantiate {
+                admin,
+                code_id,
+                msg,
+                funds,
+                label
+            }
+            .into()),
+        (addr(), any::<u64>(), binary()).prop_map(|(contract_addr, new_code_id, msg)| {
+            WasmMsg::Migrate {
+                contract_addr,
+                new_code_id,
+                msg,
+            }
+            .into()
+        }),
+    ]
+}
+
+fn operation() -> impl Strategy<Value = ExecuteMsg> {
+    let job_id = prop::option::of(any::<String>());
+    let timeout = prop::option::of(1..10 * PACKET_LIFETIME);
+    let channel_id = Just(HOST_CHANNEL_ID.to_string());
+    prop_oneof![
+        (
+            cosmos_msg(),
+            job_id.clone(),
+            any::<u64>(),
+            channel_id.clone(),
+            timeout.clone()
+        )
+            .prop_map(|(inst_msg, job_id, code_id, channel_id, timeout)| {
+                ExecuteMsg::RemoteInstantiate {
+                    inst_msg,
+                    job_id,
+                    code_id,
+                    channel_id,
+                    timeout,
+                }
+            }),
+        (
+            cosmos_msg(),
+            job_id.clone(),
+            any::<u64>(),
+            channel_id.clone(),
+            any::<bool>(),
+            timeout.clone()
+        )
+            .prop_map(
+                |(migrate_msg, job_id, new_code_id, channel_id, fail_on_error, timeout)| {
+                    ExecuteMsg::RemoteMigrate {
+                        migrate_msg,
+                        job_id,
+                        new_code_id,
+                        channel_id,
+                        fail_on_error,
+                        timeout,
+                    }
+                }
+            ),
+        (
+            cosmos_msg(),
+            job_id,
+            channel_id.clone(),
+            any::<bool>(),
+            timeout.clone()
+        )
+            .prop_map(
+                |(dispatch_msg, job_id, channel_id, fail_on_error, timeout)| {
+                    ExecuteMsg::RemoteDispatch {
+                        dispatch_msg,
+                        job_id,
+                        channel_id,
+                        fail_on_error,
+                        timeout,
+                    }
+                }
+            ),
+        (channel_id, timeout).prop_map(|(channel_id, timeout)| ExecuteMsg::QueryRemoteAddr {
+            channel_id,
+            timeout
+        }),
+    ]
+}
+
+/// An operation, forwarded through up to `MAX_FORWARD_HOPS` chains or not
+fn execute_msg() -> impl Strategy<Value = ExecuteMsg> {
+    (
+        operation(),
+        prop::option::of(prop::collection::vec(
+            "channel-[0-9]{1,4}",
+            1..=MAX_FORWARD_HOPS,
+        )),
+    )
+        .prop_map(|(msg, hops)| match hops {
+            Some(hops) => ExecuteMsg::RemoteForward {
+                msg: Box::new(msg),
+                hops,
+            },
+            None => msg,
+        })
+}
+
+/// The packet the host tunnel sends for `msg`, and its timeout
+fn expected_packet(msg: ExecuteMsg, nonce: u64) -> (PacketMsg, Option<u64>) {
+    let controller = CONTROLLER.to_string();
+    match msg {
+        ExecuteMsg::RemoteInstantiate {
+            inst_msg,
+            job_id,
+            code_id,
+            timeout,
+            ..
+        } => (
+            PacketMsg::Instantiate {
+                controller,
+                inst_msg,
+                job_id,
+                code_id,
+                nonce,
+            },
+            timeout,
+        ),
+        ExecuteMsg::RemoteMigrate {
+            migrate_msg,
+            job_id,
+            new_code_id,
+            fail_on_error,
+            timeout,
+            ..
+        } => (
+            PacketMsg::Migrate {
+                controller,
+                migration_msg: migrate_msg,
+                new_code_id,
+                job_id,
+                fail_on_error,
+                nonce,
+            },
+            timeout,
+        ),
+        ExecuteMsg::RemoteDispatch {
+            dispatch_msg,
+            job_id,
+            fail_on_error,
+            timeout,
+            ..
+        } => (
+            PacketMsg::Dispatch {
+                controller,
+                msg: dispatch_msg,
+                job_id,
+                fail_on_error,
+                nonce,
+            },
+            timeout,
+        ),
+        ExecuteMsg::QueryRemoteAddr { timeout, .. } => {
+            (PacketMsg::WhoAmI { controller, nonce }, timeout)
+        }
+        ExecuteMsg::RemoteForward { msg, hops } => {
+            let (packet, timeout) = expected_packet(*msg, nonce);
+            let packet = PacketMsg::Forward {
+                hops,
+                visited: vec![HOST_CHAIN_ID.to_string()],
+                packet: Box::new(packet),
+            };
+            (packet, timeout)
+        }
+        msg => panic!("not an operation: {msg:?}"),
+    }
+}
+
+/// A packet from the host tunnel to the remote tunnel carrying `data`
+fn raw_packet(env: &TestEnv, data: Vec<u8>) -> IbcPacket {
+    IbcPacket::new(
+        data,
+        IbcEndpoint {
+            port_id: ibc_port(&env.host.tunnel()),
+            channel_id: HOST_CHANNEL_ID.to_string(),
+        },
+        IbcEndpoint {
+            port_id: ibc_port(&env.remote.tunnel()),
+            channel_id: REMOTE_CHANNEL_ID.to_string(),
+        },
+        1,
+        IbcTimeout::with_timestamp(env.remote.block.time.plus_seconds(PACKET_LIFETIME)),
+    )
+}
+
+/// Runs `ibc_packet_receive` of the remote tunnel on `data`,
+/// the entry point must acknowledge rather than fail, as a failure aborts the relayer's transaction.
+/// Valid operations are acknowledged later, in the reply of their submessage.
+fn receive(env: &mut TestEnv, data: Vec<u8>) -> IbcReceiveResponse {
+    let packet = raw_packet(env, data);
+    let msg = IbcPacketReceiveMsg::new(packet, Addr::unchecked("relayer"));
+    env.remote
+        .with_tunnel(|deps, env| ic_tunnel::remote::ibc_packet_receive(deps, env, msg))
+        .expect("ibc_packet_receive acknowledges")
+}
+
+/// Packet data that is valid JSON but nested `depth` times
+fn nested_forward(depth: usize) -> Vec<u8> {
+    let open = r#"{"forward":{"hops":["channel-0"],"visited":[],"packet":"#.repeat(depth);
+    let inner = r#"{"who_am_i":{"controller":"controller","nonce":1}}"#;
+    format!("{open}{inner}{}", "}}".repeat(depth)).into_bytes()
+}
+
+fn assert_invalid_packet(res: IbcReceiveResponse) {
+    match from_slice(&res.acknowledgement).expect("the ack is a StdAck") {
+        StdAck::Error(err) => {
+            assert_eq!(
+                ErrorResponse::from_ack_error(&err).code,
+                TunnelErrorCode::InvalidPacket
+            )
+        }
+        StdAck::Result(_) => panic!("malformed packet acknowledged with a result"),
+    }
+}
+
+proptest! {
+    #[test]
+    fn execute_msg_packets_round_trip(msg in execute_msg()) {
+        let mut env = TestEnv::new();
+        env.host
+            .execute(CONTROLLER, &env.host.tunnel(), &msg, vec![])
+            .unwrap();
+        let packets = env.pending_packets(Side::Host);
+        prop_assert_eq!(packets.len(), 1);
+        let packet = &packets[0];
+
+        let decoded: PacketMsg = PacketMsg::decode(&packet.data).unwrap();
+        prop_assert_eq!(to_vec(&decoded).unwrap(), packet.data.to_vec());
+        let (expected, timeout) = expected_packet(msg, 1);
+        prop_assert_eq!(decoded, expected);
+        let timeout = env.host.block.time.plus_seconds(timeout.unwrap_or(PACKET_LIFETIME));
+        prop_assert_eq!(packet.timeout.timestamp(), Some(timeout));
+    }
+
+    #[test]
+    fn proto_packets_round_trip(msg in execute_msg()) {
+        let mut env = TestEnv::with_encoding(PacketEncoding::Proto);
+        env.host
+            .execute(CONTROLLER, &env.host.tunnel(), &msg, vec![])
+            .unwrap();
+        let packet = &env.pending_packets(Side::Host)[0];
+
+        let decoded: PacketMsg = PacketEncoding::Proto.decode_packet(&packet.data).unwrap();
+        prop_assert_eq!(PacketEncoding::Proto.encode_packet(&decoded).unwrap(), packet.data.clone());
+        prop_assert_eq!(decoded, expected_packet(msg, 1).0);
+    }
+
+    #[test]
+    fn arbitrary_proto_packet_data_is_acknowledged(data in prop::collection::vec(any::<u8>(), 0..512)) {
+        let mut env = TestEnv::with_encoding(PacketEncoding::Proto);
+        let res = receive(&mut env, data.clone());
+        if PacketEncoding::Proto.decode_packet::<Empty>(&data).is_err() {
+            match PacketEncoding::Proto.decode_ack(&res.acknowledgement).expect("the ack is proto") {
+                StdAck::Error(err) => prop_assert_eq!(
+                    ErrorResponse::from_ack_error(&err).code,
+                    TunnelErrorCode::InvalidPacket
+                ),
+                StdAck::Result(_) => panic!("malformed packet acknowledged with a result"),
+            }
+        }
+    }
+
+    #[test]
+    fn arbitrary_packet_data_is_acknowledged(data in prop::collection::vec(any::<u8>(), 0..512)) {
+        let mut env = TestEnv::new();
+        let ack = receive(&mut env, data.clone());
+        if PacketMsg::<Empty>::decode(&data).is_err() {
+            assert_invalid_packet(ack);
+        }
+    }
+
+    #[test]
+    fn corrupted_packets_are_acknowledged(
+        msg in execute_msg(),
+        cut in any::<prop::sample::Index>(),
+        flip in any::<prop::sample::Index>(),
+        byte in any::<u8>(),
+    ) {
+        let mut env = TestEnv::new();
+        env.host
+            .execute(CONTROLLER, &env.host.tunnel(), &msg, vec![])
+            .unwrap();
+        let data = env.pending_packets(Side::Host)[0].data.to_vec();
+
+        let truncated = data[..cut.index(data.len())].to_vec();
+        assert_invalid_packet(receive(&mut env, truncated));
+
+        let mut flipped = data;
+        let index = flip.index(flipped.len());
+        flipped[index] = byte;
+        let valid = PacketMsg::<Empty>::decode(&flipped).is_ok();
+        let ack = receive(&mut env, flipped);
+        if !valid {
+            assert_invalid_packet(ack);
+        }
+    }
+
+    #[test]
+    fn deeply_nested_packets_are_acknowledged(depth in 32usize..5000) {
+        let mut env = TestEnv::new();
+        assert_invalid_packet(receive(&mut env, nested_forward(depth)));
+    }
+
+    #[test]
+    fn arbitrary_acks_are_delivered(
+        msg in operation(),
+        ack in prop::collection::vec(any::<u8>(), 0..512),
+    ) {
+        // `WhoAmI` has no job id, so its ack is not delivered
+        prop_assume!(!matches!(msg, ExecuteMsg::QueryRemoteAddr { .. }));
+        let mut env = TestEnv::new();
+        let msg = TunnelContract::new(env.host.tunnel())
+            .call(msg)
+            .job_id("job")
+            .msg()
+            .clone();
+        env.host
+            .execute(CONTROLLER, &env.host.tunnel(), &msg, vec![])
+            .unwrap();
+        let packet = env.pending_packets(Side::Host).remove(0);
+        let sent: PacketMsg = from_slice(&packet.data).unwrap();
+
+        // decoding never panics, whatever the counterparty acknowledged with
+        let _ = TunnelAck::decode(&sent, &Binary::from(ack.clone()));
+
+        let msg = IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(ack.clone()),
+            packet,
+            Addr::unchecked("relayer"),
+        );
+        let res = env
+            .host
+            .with_tunnel(|deps, env| ic_tunnel::host::ibc_packet_ack(deps, env, msg))
+            .expect("ibc_packet_ack accepts any ack");
+
+        // the controller is called back, with an error if the ack is not a StdAck
+        prop_assert_eq!(res.messages.len(), 1);
+        if from_slice::<StdAck>(&ack).is_err() {
+            let callback = callback_msg(&res.messages[0].msg);
+            prop_assert_eq!(callback.error_code, Some(TunnelErrorCode::InvalidAck));
+        }
+    }
+}
+
+/// The callback in a message from the tunnel to the controller
+fn callback_msg(msg: &CosmosMsg) -> ReceiveIcaResponseMsg {
+    match msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+            let TunnelReceiverMsg::ReceiveTunnelResponse(callback) = from_slice(msg).unwrap();
+            callback
+        }
+        msg => panic!("not a callback: {msg:?}"),
+    }
+}
+
+#[test]
+fn nesting_within_the_limit_is_decoded() {
+    let packet: PacketMsg = PacketMsg::decode(&nested_forward(8)).unwrap();
+    assert!(matches!(packet, PacketMsg::Forward { .. }));
+}
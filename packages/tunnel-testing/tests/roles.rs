@@ -0,0 +1,198 @@
+//! Tunnels built with a single role, run with
+//! `cargo test -p tunnel-testing --no-default-features --features remote` (or `host`)
+#![cfg(not(all(feature = "host", feature = "remote")))]
+
+use cosmwasm_std::{from_slice, Binary, Empty};
+use cosmwasm_tunnel::{ErrorResponse, StdAck, TunnelContract, TunnelErrorCode};
+
+const CONTROLLER: &str = "controller";
+
+fn error(ack: &Binary) -> ErrorResponse {
+    match from_slice::<StdAck>(ack).unwrap() {
+        StdAck::Error(err) => ErrorResponse::from_ack_error(&err),
+        StdAck::Result(_) => panic!("expected an error ack"),
+    }
+}
+
+#[cfg(not(feature = "host"))]
+mod remote_only {
+    use cosmwasm_std::{coins, to_binary, BankMsg, CosmosMsg};
+    use cosmwasm_tunnel::ica::IcaSudoMsg;
+    use cosmwasm_tunnel::{
+        ExecuteMsg, PacketMsg, QueryMsg, SpendLimit, SpendLimitResponse, SudoMsg,
+    };
+    use tunnel_testing::{Side, TestEnv, HOST_CHANNEL_ID, HOST_CONNECTION_ID};
+
+    use super::*;
+
+    fn send_coins() -> CosmosMsg {
+        BankMsg::Send {
+            to_address: "recipient".to_string(),
+            amount: coins(100, "ucosm"),
+        }
+        .into()
+    }
+
+    #[test]
+    fn operations_of_controllers_are_rejected() {
+        let mut env = TestEnv::new();
+        let tunnel = TunnelContract::new(env.host.tunnel());
+        for msg in [
+            tunnel.dispatch(HOST_CHANNEL_ID, send_coins()).msg().clone(),
+            tunnel
+                .query_remote_addr::<Empty>(HOST_CHANNEL_ID)
+                .msg()
+                .clone(),
+            tunnel
+                .dispatch(HOST_CHANNEL_ID, send_coins())
+                .execute_as(CONTROLLER)
+                .msg()
+                .clone(),
+            ExecuteMsg::RevokeSigningKey {},
+        ] {
+            let err = env
+                .host
+                .execute(CONTROLLER, &env.host.tunnel(), &msg, vec![])
+                .unwrap_err();
+            assert!(err.contains("not built with the Host role"), "{err}");
+        }
+        assert_eq!(env.pending_packets(Side::Host), vec![]);
+
+        let err = env
+            .host
+            .query::<Empty>(
+                &env.host.tunnel(),
+                &QueryMsg::SentNonce {
+                    connection_id: HOST_CONNECTION_ID.to_string(),
+                    controller: CONTROLLER.to_string(),
+                },
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("Host role"), "{err}");
+
+        let open_ack = SudoMsg::Ica(IcaSudoMsg::OpenAck {
+            port_id: "icacontroller-controller".to_string(),
+            channel_id: "channel-5".to_string(),
+            counterparty_channel_id: "channel-6".to_string(),
+            counterparty_version: String::new(),
+        });
+        let err = env.host.sudo(&env.host.tunnel(), &open_ack).unwrap_err();
+        assert!(err.contains("Host role"), "{err}");
+    }
+
+    #[test]
+    fn packets_for_the_host_are_rejected() {
+        let mut env = TestEnv::new();
+        let forward_ack: PacketMsg = PacketMsg::ForwardAck {
+            controller: CONTROLLER.to_string(),
+            job_id: Some("job".to_string()),
+            nonce: 1,
+            ack: StdAck::Result(to_binary(&Empty {}).unwrap()),
+        };
+        // a forward is sent on by the receiving tunnel, as a host
+        let forward: PacketMsg = PacketMsg::Forward {
+            hops: vec!["channel-7".to_string()],
+            visited: vec![],
+            packet: Box::new(PacketMsg::WhoAmI {
+                controller: CONTROLLER.to_string(),
+                nonce: 1,
+            }),
+        };
+        for packet in [forward_ack, forward] {
+            env.send_raw(Side::Host, to_binary(&packet).unwrap())
+                .unwrap();
+            let ack = env.deliver_next(Side::Host).unwrap();
+            assert_eq!(error(&ack).code, TunnelErrorCode::WrongRole);
+        }
+
+        // the operations are run, here for a controller without a proxy
+        let who_am_i: PacketMsg = PacketMsg::WhoAmI {
+            controller: CONTROLLER.to_string(),
+            nonce: 1,
+        };
+        env.send_raw(Side::Host, to_binary(&who_am_i).unwrap())
+            .unwrap();
+        let ack = env.deliver_next(Side::Host).unwrap();
+        assert_eq!(error(&ack).code, TunnelErrorCode::AccountNotFound);
+    }
+
+    #[test]
+    fn proxies_set_their_spend_limit() {
+        let mut env = TestEnv::new();
+        let limit = SpendLimit {
+            max: coins(100, "uatom"),
+            period: 3600,
+        };
+        let call = TunnelContract::new(env.remote.tunnel()).set_spend_limit::<Empty>(limit.clone());
+        env.remote
+            .execute("proxy", &env.remote.tunnel(), call.msg(), vec![])
+            .unwrap();
+        let res: SpendLimitResponse = env
+            .remote
+            .query(
+                &env.remote.tunnel(),
+                &QueryMsg::SpendLimit {
+                    account: "proxy".to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(res.limit, Some(limit));
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+mod host_only {
+    use cosmwasm_std::{coins, to_binary};
+    use cosmwasm_tunnel::{GovernanceMsg, MsgPolicy, PacketMsg, SpendLimit, SudoMsg};
+    use tunnel_testing::{Side, TestEnv, HOST_CHANNEL_ID};
+
+    use super::*;
+
+    #[test]
+    fn packets_to_run_are_rejected() {
+        let mut env = TestEnv::new();
+        // the counterparty announces it runs no operation, so they are not sent
+        let call =
+            TunnelContract::new(env.host.tunnel()).query_remote_addr::<Empty>(HOST_CHANNEL_ID);
+        let err = env
+            .host
+            .execute(CONTROLLER, &env.host.tunnel(), call.msg(), vec![])
+            .unwrap_err();
+        assert!(err.contains("does not support WhoAmI"), "{err}");
+
+        // and a packet sent anyway is rejected
+        let who_am_i: PacketMsg = PacketMsg::WhoAmI {
+            controller: CONTROLLER.to_string(),
+            nonce: 1,
+        };
+        env.send_raw(Side::Host, to_binary(&who_am_i).unwrap())
+            .unwrap();
+        let ack = env.deliver_next(Side::Host).unwrap();
+        assert_eq!(error(&ack).code, TunnelErrorCode::WrongRole);
+    }
+
+    #[test]
+    fn governance_and_spend_limits_are_rejected() {
+        let mut env = TestEnv::new();
+        let tunnel = env.remote.tunnel();
+        let policy = SudoMsg::Governance(GovernanceMsg::SetMsgPolicy {
+            account: None,
+            policy: MsgPolicy {
+                allow: vec![],
+                deny: vec![],
+            },
+        });
+        let err = env.remote.sudo(&tunnel, &policy).unwrap_err();
+        assert!(err.contains("not built with the Remote role"), "{err}");
+
+        let call = TunnelContract::new(tunnel.clone()).set_spend_limit::<Empty>(SpendLimit {
+            max: coins(100, "uatom"),
+            period: 3600,
+        });
+        let err = env
+            .remote
+            .execute("proxy", &tunnel, call.msg(), vec![])
+            .unwrap_err();
+        assert!(err.contains("not built with the Remote role"), "{err}");
+    }
+}